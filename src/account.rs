@@ -0,0 +1,467 @@
+//! Object-safe facade of the API traits
+//!
+//! Some API traits can't be used as trait objects, e.g. [UpdateDeviceData] is generic over its parameters. [GpodderAccount] and [GpodderDevice] cover the whole API with object-safe methods, so clients can be stored as `Box<dyn GpodderAccount>` or `Box<dyn GpodderDevice>` without knowing their concrete type.
+//!
+//! Both traits are implemented by the wrapper [Facade] for every type implementing the corresponding API traits, e.g. `Facade<AuthenticatedClient>` implements [GpodderAccount] and `Facade<DeviceClient>` implements both. The clients themselves don't implement the facade, so it can be imported together with the API traits without making method calls ambiguous.
+
+use crate::device::{
+    Device, DeviceType, DeviceUpdates, GetDeviceUpdates, ListDevices, UpdateDeviceData,
+};
+use crate::directory::{
+    Episode, PodcastSearch, PodcastToplist, RetrieveEpisodeData, RetrievePodcastData,
+    RetrievePodcastsForTag, RetrieveTopTags, Tag,
+};
+use crate::episode::{
+    ChunkedUpload, ChunkedUploadReport, EpisodeAction, EpisodeActionQuery, GetEpisodeActions,
+    GetEpisodeActionsResponse, UploadEpisodeActions, UploadEpisodeActionsInChunks,
+    UploadEpisodeActionsResponse,
+};
+use crate::error::Error;
+use crate::favorite::GetFavoriteEpisodes;
+use crate::settings::{
+    GetAccountSettings, GetDeviceSettings, GetEpisodeSettings, GetPodcastSettings,
+    SaveAccountSettings, SaveDeviceSettings, SaveEpisodeSettings, SavePodcastSettings,
+};
+use crate::subscription::{
+    GetAllSubscriptions, GetSubscriptionChangesResponse, MergeMode, MergeSubscriptions,
+    MergeSubscriptionsReport, Podcast, SubscriptionChanges, SubscriptionsOfDevice,
+    UploadSubscriptionChangesResponse,
+};
+use crate::suggestion::{RetrieveSuggestedPodcasts, Suggestion};
+use std::collections::HashMap;
+use url::Url;
+
+/// Object-safe facade of all API traits which don't need a device, see [module documentation](self)
+///
+/// # Examples
+///
+/// ```no_run
+/// use mygpoclient::account::{Facade, GpodderAccount};
+/// use mygpoclient::client::{AuthenticatedClient, DeviceClient};
+///
+/// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+/// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+/// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
+/// #
+/// let accounts: Vec<Box<dyn GpodderAccount>> = vec![
+///     Box::new(Facade(AuthenticatedClient::new(&username, &password)?)),
+///     Box::new(Facade(DeviceClient::new(&username, &password, &deviceid)?)),
+/// ];
+///
+/// for account in &accounts {
+///     account.list_devices()?;
+/// }
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+pub trait GpodderAccount {
+    /// see [RetrieveTopTags::retrieve_top_tags]
+    fn retrieve_top_tags(&self, count: u8) -> Result<Vec<Tag>, Error>;
+
+    /// see [RetrievePodcastsForTag::retrieve_podcasts_for_tag]
+    fn retrieve_podcasts_for_tag(&self, tag: &str, count: u8) -> Result<Vec<Podcast>, Error>;
+
+    /// see [RetrievePodcastData::retrieve_podcast_data]
+    fn retrieve_podcast_data(&self, url: Url) -> Result<Podcast, Error>;
+
+    /// see [RetrieveEpisodeData::retrieve_episode_data]
+    fn retrieve_episode_data(&self, podcast: Url, url: Url) -> Result<Episode, Error>;
+
+    /// see [PodcastToplist::podcast_toplist]
+    fn podcast_toplist(&self, number: u8, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error>;
+
+    /// see [PodcastSearch::podcast_search]
+    fn podcast_search(&self, q: &str, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error>;
+
+    /// see [RetrieveSuggestedPodcasts::retrieve_suggested_podcasts]
+    fn retrieve_suggested_podcasts(&self, max_results: u8) -> Result<Vec<Suggestion>, Error>;
+
+    /// see [GetFavoriteEpisodes::get_favorite_episodes]
+    fn get_favorite_episodes(&self) -> Result<Vec<Episode>, Error>;
+
+    /// see [GetAllSubscriptions::get_all_subscriptions]
+    fn get_all_subscriptions(&self) -> Result<Vec<Podcast>, Error>;
+
+    /// see [MergeSubscriptions::merge_subscriptions]
+    fn merge_subscriptions(
+        &self,
+        source_device_id: &str,
+        target_device_id: &str,
+        mode: MergeMode,
+    ) -> Result<MergeSubscriptionsReport, Error>;
+
+    /// see [ListDevices::list_devices]
+    fn list_devices(&self) -> Result<Vec<Device>, Error>;
+
+    /// see [UploadEpisodeActions::upload_episode_actions]
+    fn upload_episode_actions(
+        &self,
+        actions: &[EpisodeAction],
+    ) -> Result<UploadEpisodeActionsResponse, Error>;
+
+    /// see [UploadEpisodeActionsInChunks::upload_episode_actions_in_chunks]
+    fn upload_episode_actions_in_chunks(
+        &self,
+        actions: &[EpisodeAction],
+        options: &ChunkedUpload,
+    ) -> ChunkedUploadReport;
+
+    /// see [GetEpisodeActions::get_episode_actions]
+    fn get_episode_actions(
+        &self,
+        query: &EpisodeActionQuery,
+    ) -> Result<GetEpisodeActionsResponse, Error>;
+
+    /// see [SaveAccountSettings::save_account_settings]
+    fn save_account_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error>;
+
+    /// see [SavePodcastSettings::save_podcast_settings]
+    fn save_podcast_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+        podcast: Url,
+    ) -> Result<HashMap<String, String>, Error>;
+
+    /// see [SaveEpisodeSettings::save_episode_settings]
+    fn save_episode_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+        podcast: Url,
+        episode: Url,
+    ) -> Result<HashMap<String, String>, Error>;
+
+    /// see [GetAccountSettings::get_account_settings]
+    fn get_account_settings(&self) -> Result<HashMap<String, String>, Error>;
+
+    /// see [GetPodcastSettings::get_podcast_settings]
+    fn get_podcast_settings(&self, podcast: Url) -> Result<HashMap<String, String>, Error>;
+
+    /// see [GetEpisodeSettings::get_episode_settings]
+    fn get_episode_settings(
+        &self,
+        podcast: Url,
+        episode: Url,
+    ) -> Result<HashMap<String, String>, Error>;
+}
+
+/// Object-safe facade of all API traits, including those which need a device, see [module documentation](self)
+///
+/// # Examples
+///
+/// ```no_run
+/// use mygpoclient::account::{Facade, GpodderDevice};
+/// use mygpoclient::client::DeviceClient;
+/// use mygpoclient::device::DeviceType;
+///
+/// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+/// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+/// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
+/// #
+/// let device: Box<dyn GpodderDevice> = Box::new(Facade(DeviceClient::new(&username, &password, &deviceid)?));
+///
+/// device.update_device_data(None, Some(DeviceType::Laptop))?;
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+pub trait GpodderDevice: GpodderAccount {
+    /// see [SubscriptionsOfDevice::get_subscriptions_of_device]
+    fn get_subscriptions_of_device(&self) -> Result<Vec<Url>, Error>;
+
+    /// see [SubscriptionsOfDevice::upload_subscriptions_of_device]
+    fn upload_subscriptions_of_device(&self, subscriptions: &[Url]) -> Result<(), Error>;
+
+    /// see [SubscriptionChanges::upload_subscription_changes]
+    fn upload_subscription_changes(
+        &self,
+        add: &[Url],
+        remove: &[Url],
+    ) -> Result<UploadSubscriptionChangesResponse, Error>;
+
+    /// see [SubscriptionChanges::get_subscription_changes]
+    fn get_subscription_changes(
+        &self,
+        timestamp: u64,
+    ) -> Result<GetSubscriptionChangesResponse, Error>;
+
+    /// see [UpdateDeviceData::update_device_data]
+    fn update_device_data(
+        &self,
+        caption: Option<String>,
+        device_type: Option<DeviceType>,
+    ) -> Result<(), Error>;
+
+    /// see [GetDeviceUpdates::get_device_updates]
+    fn get_device_updates(&self, since: u64, include_actions: bool)
+        -> Result<DeviceUpdates, Error>;
+
+    /// see [SaveDeviceSettings::save_device_settings]
+    fn save_device_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error>;
+
+    /// see [GetDeviceSettings::get_device_settings]
+    fn get_device_settings(&self) -> Result<HashMap<String, String>, Error>;
+}
+
+/// Wrapper implementing the facade traits for a client, see [module documentation](self)
+#[derive(Debug, Clone)]
+pub struct Facade<T>(pub T);
+
+impl<T> GpodderAccount for Facade<T>
+where
+    T: RetrieveTopTags
+        + RetrievePodcastsForTag
+        + RetrievePodcastData
+        + RetrieveEpisodeData
+        + PodcastToplist
+        + PodcastSearch
+        + RetrieveSuggestedPodcasts
+        + GetFavoriteEpisodes
+        + GetAllSubscriptions
+        + MergeSubscriptions
+        + ListDevices
+        + UploadEpisodeActions
+        + GetEpisodeActions
+        + SaveAccountSettings
+        + SavePodcastSettings
+        + SaveEpisodeSettings
+        + GetAccountSettings
+        + GetPodcastSettings
+        + GetEpisodeSettings,
+{
+    fn retrieve_top_tags(&self, count: u8) -> Result<Vec<Tag>, Error> {
+        RetrieveTopTags::retrieve_top_tags(&self.0, count)
+    }
+
+    fn retrieve_podcasts_for_tag(&self, tag: &str, count: u8) -> Result<Vec<Podcast>, Error> {
+        RetrievePodcastsForTag::retrieve_podcasts_for_tag(&self.0, tag, count)
+    }
+
+    fn retrieve_podcast_data(&self, url: Url) -> Result<Podcast, Error> {
+        RetrievePodcastData::retrieve_podcast_data(&self.0, url)
+    }
+
+    fn retrieve_episode_data(&self, podcast: Url, url: Url) -> Result<Episode, Error> {
+        RetrieveEpisodeData::retrieve_episode_data(&self.0, podcast, url)
+    }
+
+    fn podcast_toplist(&self, number: u8, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error> {
+        PodcastToplist::podcast_toplist(&self.0, number, scale_logo)
+    }
+
+    fn podcast_search(&self, q: &str, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error> {
+        PodcastSearch::podcast_search(&self.0, q, scale_logo)
+    }
+
+    fn retrieve_suggested_podcasts(&self, max_results: u8) -> Result<Vec<Suggestion>, Error> {
+        RetrieveSuggestedPodcasts::retrieve_suggested_podcasts(&self.0, max_results)
+    }
+
+    fn get_favorite_episodes(&self) -> Result<Vec<Episode>, Error> {
+        GetFavoriteEpisodes::get_favorite_episodes(&self.0)
+    }
+
+    fn get_all_subscriptions(&self) -> Result<Vec<Podcast>, Error> {
+        GetAllSubscriptions::get_all_subscriptions(&self.0)
+    }
+
+    fn merge_subscriptions(
+        &self,
+        source_device_id: &str,
+        target_device_id: &str,
+        mode: MergeMode,
+    ) -> Result<MergeSubscriptionsReport, Error> {
+        MergeSubscriptions::merge_subscriptions(&self.0, source_device_id, target_device_id, mode)
+    }
+
+    fn list_devices(&self) -> Result<Vec<Device>, Error> {
+        ListDevices::list_devices(&self.0)
+    }
+
+    fn upload_episode_actions(
+        &self,
+        actions: &[EpisodeAction],
+    ) -> Result<UploadEpisodeActionsResponse, Error> {
+        UploadEpisodeActions::upload_episode_actions(&self.0, actions)
+    }
+
+    fn upload_episode_actions_in_chunks(
+        &self,
+        actions: &[EpisodeAction],
+        options: &ChunkedUpload,
+    ) -> ChunkedUploadReport {
+        UploadEpisodeActionsInChunks::upload_episode_actions_in_chunks(&self.0, actions, options)
+    }
+
+    fn get_episode_actions(
+        &self,
+        query: &EpisodeActionQuery,
+    ) -> Result<GetEpisodeActionsResponse, Error> {
+        GetEpisodeActions::get_episode_actions(&self.0, query)
+    }
+
+    fn save_account_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error> {
+        SaveAccountSettings::save_account_settings(&self.0, set, remove)
+    }
+
+    fn save_podcast_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+        podcast: Url,
+    ) -> Result<HashMap<String, String>, Error> {
+        SavePodcastSettings::save_podcast_settings(&self.0, set, remove, podcast)
+    }
+
+    fn save_episode_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+        podcast: Url,
+        episode: Url,
+    ) -> Result<HashMap<String, String>, Error> {
+        SaveEpisodeSettings::save_episode_settings(&self.0, set, remove, podcast, episode)
+    }
+
+    fn get_account_settings(&self) -> Result<HashMap<String, String>, Error> {
+        GetAccountSettings::get_account_settings(&self.0)
+    }
+
+    fn get_podcast_settings(&self, podcast: Url) -> Result<HashMap<String, String>, Error> {
+        GetPodcastSettings::get_podcast_settings(&self.0, podcast)
+    }
+
+    fn get_episode_settings(
+        &self,
+        podcast: Url,
+        episode: Url,
+    ) -> Result<HashMap<String, String>, Error> {
+        GetEpisodeSettings::get_episode_settings(&self.0, podcast, episode)
+    }
+}
+
+impl<T> GpodderDevice for Facade<T>
+where
+    Facade<T>: GpodderAccount,
+    T: SubscriptionsOfDevice
+        + SubscriptionChanges
+        + UpdateDeviceData
+        + GetDeviceUpdates
+        + SaveDeviceSettings
+        + GetDeviceSettings,
+{
+    fn get_subscriptions_of_device(&self) -> Result<Vec<Url>, Error> {
+        SubscriptionsOfDevice::get_subscriptions_of_device(&self.0)
+    }
+
+    fn upload_subscriptions_of_device(&self, subscriptions: &[Url]) -> Result<(), Error> {
+        SubscriptionsOfDevice::upload_subscriptions_of_device(&self.0, subscriptions)
+    }
+
+    fn upload_subscription_changes(
+        &self,
+        add: &[Url],
+        remove: &[Url],
+    ) -> Result<UploadSubscriptionChangesResponse, Error> {
+        SubscriptionChanges::upload_subscription_changes(&self.0, add, remove)
+    }
+
+    fn get_subscription_changes(
+        &self,
+        timestamp: u64,
+    ) -> Result<GetSubscriptionChangesResponse, Error> {
+        SubscriptionChanges::get_subscription_changes(&self.0, timestamp)
+    }
+
+    fn update_device_data(
+        &self,
+        caption: Option<String>,
+        device_type: Option<DeviceType>,
+    ) -> Result<(), Error> {
+        UpdateDeviceData::update_device_data(&self.0, caption, device_type)
+    }
+
+    fn get_device_updates(
+        &self,
+        since: u64,
+        include_actions: bool,
+    ) -> Result<DeviceUpdates, Error> {
+        GetDeviceUpdates::get_device_updates(&self.0, since, include_actions)
+    }
+
+    fn save_device_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error> {
+        SaveDeviceSettings::save_device_settings(&self.0, set, remove)
+    }
+
+    fn get_device_settings(&self) -> Result<HashMap<String, String>, Error> {
+        GetDeviceSettings::get_device_settings(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Facade, GpodderAccount, GpodderDevice};
+    use crate::cassette::Cassette;
+    use crate::client::{AuthenticatedClient, DeviceClient};
+    use crate::device::{DeviceType, ListDevices};
+    use crate::test_util::interaction;
+
+    #[test]
+    fn trait_objects() {
+        let cassette = Cassette::from_interactions(vec![
+            interaction(
+                "GET",
+                "https://gpodder.net/api/2/devices/alice.json",
+                None,
+                "[]",
+            ),
+            interaction(
+                "GET",
+                "https://gpodder.net/api/2/devices/alice.json",
+                None,
+                "[]",
+            ),
+            interaction(
+                "POST",
+                "https://gpodder.net/api/2/devices/alice/phone.json",
+                Some(r#"{"type": "mobile"}"#),
+                "",
+            ),
+        ]);
+        let account: Box<dyn GpodderAccount> = Box::new(Facade(
+            AuthenticatedClient::new("alice", "secret")
+                .unwrap()
+                .with_cassette(cassette.clone()),
+        ));
+        let device: Box<dyn GpodderDevice> = Box::new(Facade(
+            DeviceClient::new("alice", "secret", "phone")
+                .unwrap()
+                .with_cassette(cassette.clone()),
+        ));
+
+        assert!(account.list_devices().unwrap().is_empty());
+        // the API traits are imported as well, calls on clients aren't ambiguous
+        let client = AuthenticatedClient::new("alice", "secret")
+            .unwrap()
+            .with_cassette(cassette.clone());
+        assert!(client.list_devices().unwrap().is_empty());
+        device
+            .update_device_data(None, Some(DeviceType::Mobile))
+            .unwrap();
+        assert!(cassette.interactions().is_empty());
+    }
+}
@@ -0,0 +1,388 @@
+//! Listening statistics computed from the [episode action](crate::episode) history
+//!
+//! Listening time is derived from [Play](EpisodeActionType::Play) actions with a [started](EpisodeActionType::Play::started) position: each such action contributes the seconds between `started` and `position`. Play actions without `started` only count towards the completion of an episode.
+
+use crate::episode::{EpisodeAction, EpisodeActionType, GetEpisodeActionsResponse};
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use url::Url;
+
+/// Length of the periods in [ListeningStatistics::by_period]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    /// calendar day (UTC)
+    Day,
+    /// ISO week starting on Monday (UTC)
+    Week,
+}
+
+/// Statistics of a single podcast
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PodcastStatistics {
+    /// feed URL
+    pub podcast: Url,
+    /// listening time in seconds
+    pub listening_time: u64,
+    /// number of episodes with at least one play action
+    pub episodes: usize,
+    /// average share (0 to 1) of the episodes which has been listened to; `None` if the length of no episode is known
+    pub completion_rate: Option<f64>,
+}
+
+/// Statistics of a single device
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeviceStatistics {
+    /// device ID; `None` for actions without device
+    pub device: Option<String>,
+    /// listening time in seconds
+    pub listening_time: u64,
+}
+
+/// Statistics of a single day or week
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PeriodStatistics {
+    /// first day of the period
+    pub start: NaiveDate,
+    /// listening time in seconds
+    pub listening_time: u64,
+}
+
+/// Listening statistics of an episode action history
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::analytics::{ListeningStatistics, Period};
+/// use mygpoclient::episode::EpisodeAction;
+/// use chrono::NaiveDate;
+/// use url::Url;
+///
+/// let podcast = Url::parse("http://example.org/podcast.php").unwrap();
+/// let episode = Url::parse("http://ftp.example.org/foo.ogg").unwrap();
+/// let timestamp = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap().and_hms_opt(9, 0, 0).unwrap();
+/// let actions = vec![EpisodeAction::new_play(podcast.clone(), episode, Some(timestamp), 250, 0, 500)];
+///
+/// let statistics = ListeningStatistics::from_actions(&actions, Period::Day);
+/// assert_eq!(250, statistics.by_podcast[0].listening_time);
+/// assert_eq!(Some(0.5), statistics.by_podcast[0].completion_rate);
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ListeningStatistics {
+    /// statistics per podcast, most played first
+    pub by_podcast: Vec<PodcastStatistics>,
+    /// statistics per device, most used first
+    pub by_device: Vec<DeviceStatistics>,
+    /// statistics per period in chronological order; actions without timestamp are not included
+    pub by_period: Vec<PeriodStatistics>,
+}
+
+#[derive(Default)]
+struct PodcastAccumulator<'a> {
+    listening_time: u64,
+    // furthest position and length of each episode
+    episodes: HashMap<&'a str, (u32, Option<u32>)>,
+}
+
+impl ListeningStatistics {
+    /// Compute statistics of the actions of a [get_episode_actions](crate::episode::GetEpisodeActions::get_episode_actions) response
+    pub fn from_response(response: &GetEpisodeActionsResponse, period: Period) -> Self {
+        Self::from_actions(&response.actions, period)
+    }
+
+    /// Compute statistics of the given actions
+    pub fn from_actions(actions: &[EpisodeAction], period: Period) -> Self {
+        let mut podcasts: HashMap<&Url, PodcastAccumulator<'_>> = HashMap::new();
+        let mut devices: HashMap<Option<&str>, u64> = HashMap::new();
+        let mut periods: HashMap<NaiveDate, u64> = HashMap::new();
+
+        for action in actions {
+            if let EpisodeActionType::Play {
+                position,
+                started,
+                total,
+            } = action.action
+            {
+                let listening_time =
+                    started.map_or(0, |started| u64::from(position.saturating_sub(started)));
+
+                let podcast = podcasts.entry(&action.podcast).or_default();
+                podcast.listening_time += listening_time;
+                let episode_key = action
+                    .guid
+                    .as_deref()
+                    .unwrap_or_else(|| action.episode.as_str());
+                let episode = podcast.episodes.entry(episode_key).or_insert((0, None));
+                episode.0 = episode.0.max(position);
+                episode.1 = total.or(episode.1);
+
+                *devices.entry(action.device.as_deref()).or_default() += listening_time;
+
+                if let Some(timestamp) = action.timestamp {
+                    *periods
+                        .entry(period.start_of(timestamp.date()))
+                        .or_default() += listening_time;
+                }
+            }
+        }
+
+        let mut by_podcast: Vec<PodcastStatistics> = podcasts
+            .into_iter()
+            .map(|(podcast, accumulator)| {
+                let completions: Vec<f64> = accumulator
+                    .episodes
+                    .values()
+                    .filter_map(|&(position, total)| match total {
+                        Some(total) if total > 0 => {
+                            Some((f64::from(position) / f64::from(total)).min(1.0))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                PodcastStatistics {
+                    podcast: podcast.clone(),
+                    listening_time: accumulator.listening_time,
+                    episodes: accumulator.episodes.len(),
+                    completion_rate: if completions.is_empty() {
+                        None
+                    } else {
+                        Some(completions.iter().sum::<f64>() / completions.len() as f64)
+                    },
+                }
+            })
+            .collect();
+        by_podcast.sort_by(|a, b| {
+            b.listening_time
+                .cmp(&a.listening_time)
+                .then_with(|| a.podcast.cmp(&b.podcast))
+        });
+
+        let mut by_device: Vec<DeviceStatistics> = devices
+            .into_iter()
+            .map(|(device, listening_time)| DeviceStatistics {
+                device: device.map(str::to_owned),
+                listening_time,
+            })
+            .collect();
+        by_device.sort_by(|a, b| {
+            b.listening_time
+                .cmp(&a.listening_time)
+                .then_with(|| a.device.cmp(&b.device))
+        });
+
+        let mut by_period: Vec<PeriodStatistics> = periods
+            .into_iter()
+            .map(|(start, listening_time)| PeriodStatistics {
+                start,
+                listening_time,
+            })
+            .collect();
+        by_period.sort_by_key(|period| period.start);
+
+        ListeningStatistics {
+            by_podcast,
+            by_device,
+            by_period,
+        }
+    }
+
+    /// Podcasts with the longest listening time, at most `count`
+    pub fn most_played_podcasts(&self, count: usize) -> &[PodcastStatistics] {
+        &self.by_podcast[..count.min(self.by_podcast.len())]
+    }
+
+    /// Total listening time in seconds
+    pub fn total_listening_time(&self) -> u64 {
+        self.by_podcast
+            .iter()
+            .map(|podcast| podcast.listening_time)
+            .sum()
+    }
+
+    /// [by_podcast](ListeningStatistics::by_podcast) as CSV with header line
+    pub fn podcasts_csv(&self) -> String {
+        let mut csv = String::from("podcast,listening_time,episodes,completion_rate\n");
+        for podcast in &self.by_podcast {
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                csv_field(podcast.podcast.as_str()),
+                podcast.listening_time,
+                podcast.episodes,
+                podcast
+                    .completion_rate
+                    .map_or_else(String::new, |rate| rate.to_string())
+            ));
+        }
+        csv
+    }
+
+    /// [by_device](ListeningStatistics::by_device) as CSV with header line
+    pub fn devices_csv(&self) -> String {
+        let mut csv = String::from("device,listening_time\n");
+        for device in &self.by_device {
+            csv.push_str(&format!(
+                "{},{}\n",
+                csv_field(device.device.as_deref().unwrap_or_default()),
+                device.listening_time
+            ));
+        }
+        csv
+    }
+
+    /// [by_period](ListeningStatistics::by_period) as CSV with header line
+    pub fn periods_csv(&self) -> String {
+        let mut csv = String::from("start,listening_time\n");
+        for period in &self.by_period {
+            csv.push_str(&format!("{},{}\n", period.start, period.listening_time));
+        }
+        csv
+    }
+}
+
+impl Period {
+    fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - Duration::days(i64::from(date.weekday().num_days_from_monday())),
+        }
+    }
+}
+
+// quote fields according to RFC 4180 if necessary
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_field, ListeningStatistics, Period};
+    use crate::episode::EpisodeAction;
+    use crate::test_util::url;
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    fn timestamp(day: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(2020, 1, day)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+    }
+
+    fn actions() -> Vec<EpisodeAction> {
+        let mut on_laptop = EpisodeAction::new_play(
+            url("http://example.com/feed1.rss"),
+            url("http://example.com/1.mp3"),
+            timestamp(6),
+            400,
+            100,
+            400,
+        );
+        on_laptop.device = Some(String::from("laptop"));
+        vec![
+            EpisodeAction::new_play(
+                url("http://example.com/feed1.rss"),
+                url("http://example.com/1.mp3"),
+                timestamp(5),
+                100,
+                0,
+                400,
+            ),
+            on_laptop,
+            EpisodeAction::new_play(
+                url("http://example.com/feed1.rss"),
+                url("http://example.com/2.mp3"),
+                None,
+                50,
+                0,
+                200,
+            ),
+            EpisodeAction::new_play_stop(
+                url("http://example.com/feed2.rss"),
+                url("http://example.com/3.mp3"),
+                timestamp(6),
+                300,
+            ),
+            EpisodeAction::new_download(
+                url("http://example.com/feed2.rss"),
+                url("http://example.com/4.mp3"),
+                timestamp(6),
+            ),
+        ]
+    }
+
+    #[test]
+    fn statistics_by_podcast() {
+        let statistics = ListeningStatistics::from_actions(&actions(), Period::Day);
+
+        assert_eq!(450, statistics.total_listening_time());
+        assert_eq!(2, statistics.by_podcast.len());
+        let most_played = &statistics.most_played_podcasts(1)[0];
+        assert_eq!(url("http://example.com/feed1.rss"), most_played.podcast);
+        assert_eq!(450, most_played.listening_time);
+        assert_eq!(2, most_played.episodes);
+        assert_eq!(Some(0.625), most_played.completion_rate);
+        assert_eq!(0, statistics.by_podcast[1].listening_time);
+        assert_eq!(None, statistics.by_podcast[1].completion_rate);
+    }
+
+    #[test]
+    fn statistics_by_device() {
+        let statistics = ListeningStatistics::from_actions(&actions(), Period::Day);
+
+        assert_eq!(2, statistics.by_device.len());
+        assert_eq!(Some("laptop"), statistics.by_device[0].device.as_deref());
+        assert_eq!(300, statistics.by_device[0].listening_time);
+        assert_eq!(None, statistics.by_device[1].device);
+        assert_eq!(150, statistics.by_device[1].listening_time);
+    }
+
+    #[test]
+    fn statistics_by_period() {
+        let by_day = ListeningStatistics::from_actions(&actions(), Period::Day).by_period;
+        assert_eq!(
+            vec![
+                (NaiveDate::from_ymd_opt(2020, 1, 5).unwrap(), 100),
+                (NaiveDate::from_ymd_opt(2020, 1, 6).unwrap(), 300),
+            ],
+            by_day
+                .iter()
+                .map(|period| (period.start, period.listening_time))
+                .collect::<Vec<_>>()
+        );
+
+        // 2020-01-05 is a Sunday, 2020-01-06 a Monday
+        let by_week = ListeningStatistics::from_actions(&actions(), Period::Week).by_period;
+        assert_eq!(
+            vec![
+                (NaiveDate::from_ymd_opt(2019, 12, 30).unwrap(), 100),
+                (NaiveDate::from_ymd_opt(2020, 1, 6).unwrap(), 300),
+            ],
+            by_week
+                .iter()
+                .map(|period| (period.start, period.listening_time))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn csv() {
+        let statistics = ListeningStatistics::from_actions(&actions(), Period::Day);
+
+        assert_eq!(
+            "podcast,listening_time,episodes,completion_rate\nhttp://example.com/feed1.rss,450,2,0.625\nhttp://example.com/feed2.rss,0,1,\n",
+            statistics.podcasts_csv()
+        );
+        assert_eq!(
+            "device,listening_time\nlaptop,300\n,150\n",
+            statistics.devices_csv()
+        );
+        assert_eq!(
+            "start,listening_time\n2020-01-05,100\n2020-01-06,300\n",
+            statistics.periods_csv()
+        );
+        assert_eq!("\"a,\"\"b\"\"\"", csv_field("a,\"b\""));
+    }
+}
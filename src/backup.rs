@@ -0,0 +1,868 @@
+//! Backup and restore of a complete account
+//!
+//! A [Backup] contains all devices with their subscriptions and settings, all episode actions, account and podcast settings and the favorite episodes with their settings. Settings of other episodes aren't backed up, as they can only be fetched one episode at a time. It is stored as JSON file in a tar archive.
+//!
+//! Restoring a backup only applies the differences to the current state of the target account, so it can be repeated safely. Settings and subscriptions, which only exist on the target account, are kept, except for subscriptions of restored devices. A failed step doesn't stop the restore, the failures are listed in the [RestoreReport].
+
+use crate::client::AuthenticatedClient;
+use crate::device::{Device, ListDevices, UpdateDeviceData};
+use crate::directory::Episode;
+use crate::episode::{
+    ChunkedUpload, EpisodeAction, EpisodeActionQuery, EpisodeActionType, GetEpisodeActions,
+    UploadEpisodeActionsInChunks,
+};
+use crate::error::Error;
+use crate::favorite::GetFavoriteEpisodes;
+use crate::profile::Endpoint;
+use crate::settings::{
+    GetAccountSettings, GetDeviceSettings, GetEpisodeSettings, GetPodcastSettings,
+    SaveAccountSettings, SaveDeviceSettings, SaveEpisodeSettings, SavePodcastSettings,
+};
+use crate::subscription::{
+    subscription_changes, DeviceSubscriptionChanges, SubscriptionChanges, SubscriptionsOfDevice,
+};
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::io::{self, Read, Write};
+use url::Url;
+
+/// Version of the backup format written by this library
+pub const BACKUP_VERSION: u32 = 1;
+
+const ARCHIVE_ENTRY: &str = "backup.json";
+
+/// episode setting which marks an episode as favorite
+pub(crate) const FAVORITE_SETTING: &str = "is_favorite";
+
+/// Snapshot of a complete account
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Backup {
+    /// version of the backup format, see [BACKUP_VERSION]
+    pub version: u32,
+    /// UTC timestamp when the backup was created
+    pub created: NaiveDateTime,
+    /// devices with their subscriptions and settings
+    pub devices: Vec<DeviceBackup>,
+    /// complete episode action history
+    pub episode_actions: Vec<EpisodeAction>,
+    /// account settings
+    pub account_settings: HashMap<String, String>,
+    /// settings of all subscribed podcasts which have settings
+    pub podcast_settings: BTreeMap<Url, HashMap<String, String>>,
+    /// settings of the favorite episodes
+    #[serde(default)]
+    pub episode_settings: Vec<EpisodeSettings>,
+    /// favorite episodes
+    pub favorites: Vec<Episode>,
+}
+
+/// Settings of an episode as contained in a [Backup]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EpisodeSettings {
+    /// feed URL of the podcast
+    pub podcast: Url,
+    /// media URL of the episode
+    pub episode: Url,
+    /// settings
+    pub settings: HashMap<String, String>,
+}
+
+/// Device as contained in a [Backup]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeviceBackup {
+    /// device
+    pub device: Device,
+    /// subscriptions of the device
+    pub subscriptions: Vec<Url>,
+    /// device settings
+    pub settings: HashMap<String, String>,
+}
+
+/// Changes applied to an account by [restore](Backup::restore)
+///
+/// Its [Display](fmt::Display) implementation lists all changes in a diff-like format.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RestorePlan {
+    /// devices which don't exist yet
+    pub added_devices: Vec<Device>,
+    /// devices whose caption or type is changed
+    pub changed_devices: Vec<Device>,
+    /// subscription changes per device
+    pub subscriptions: Vec<DeviceSubscriptionChanges>,
+    /// episode actions which are missing
+    pub episode_actions: Vec<EpisodeAction>,
+    /// account settings to be set
+    pub account_settings: HashMap<String, String>,
+    /// device settings to be set per device ID
+    pub device_settings: BTreeMap<String, HashMap<String, String>>,
+    /// podcast settings to be set per podcast
+    pub podcast_settings: BTreeMap<Url, HashMap<String, String>>,
+    /// episode settings to be set
+    pub episode_settings: Vec<EpisodeSettings>,
+    /// episodes to be marked as favorite
+    pub favorites: Vec<Episode>,
+}
+
+/// Outcome of [applying](RestorePlan::apply) a [RestorePlan]
+///
+/// Every step of the plan is tried, even if an earlier one failed.
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    /// number of steps which have been applied
+    pub applied: usize,
+    /// steps which failed, described like the lines of the plan, with their errors
+    pub failed: Vec<(String, Error)>,
+}
+
+impl Backup {
+    /// Download a backup of the account of the given client.
+    ///
+    /// Settings and favorites are left empty if the [profile](AuthenticatedClient::profile) of the client doesn't support them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mygpoclient::backup::Backup;
+    /// use mygpoclient::client::AuthenticatedClient;
+    /// use std::fs::File;
+    ///
+    /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+    /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+    /// #
+    /// let client = AuthenticatedClient::new(&username, &password)?;
+    ///
+    /// let backup = Backup::create(&client)?;
+    /// backup.write_archive(File::create("gpodder-backup.tar")?)?;
+    /// #
+    /// # Ok::<(), mygpoclient::error::Error>(())
+    /// ```
+    pub fn create(client: &AuthenticatedClient) -> Result<Backup, Error> {
+        let devices = device_backups(client)?;
+        let podcast_settings = podcast_settings(client, &devices)?;
+        let episode_actions = client
+            .get_episode_actions(&EpisodeActionQuery::new())?
+            .actions;
+        let favorites = favorites(client)?;
+        let episode_settings = episode_settings(client, &favorites)?;
+
+        Ok(Backup {
+            version: BACKUP_VERSION,
+            created: Utc::now().naive_utc(),
+            episode_actions,
+            account_settings: account_settings(client)?,
+            podcast_settings,
+            episode_settings,
+            favorites,
+            devices,
+        })
+    }
+
+    /// Write backup as tar archive
+    pub fn write_archive<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let json = serde_json::to_vec_pretty(self)?;
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(json.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(self.created.and_utc().timestamp().max(0) as u64);
+        header.set_cksum();
+
+        let mut builder = tar::Builder::new(writer);
+        builder.append_data(&mut header, ARCHIVE_ENTRY, json.as_slice())?;
+        builder.into_inner()?.flush()?;
+        Ok(())
+    }
+
+    /// Read backup from tar archive written by [write_archive](Backup::write_archive)
+    ///
+    /// Archives of newer, unknown backup versions are rejected.
+    pub fn read_archive<R: Read>(reader: R) -> Result<Backup, Error> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let entry = entry?;
+            if entry.path()?.to_str() == Some(ARCHIVE_ENTRY) {
+                let backup: Backup = serde_json::from_reader(entry)?;
+                if backup.version > BACKUP_VERSION {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unsupported backup version {}", backup.version),
+                    )
+                    .into());
+                }
+                return Ok(backup);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("archive doesn't contain {}", ARCHIVE_ENTRY),
+        )
+        .into())
+    }
+
+    /// Restore backup to the account of the given client.
+    ///
+    /// The current state of the account is downloaded first and only the differences are applied. If `dry_run` is true, nothing is changed and the report is empty. The returned plan describes the (planned) changes and can be displayed as a diff, the report lists the steps which failed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mygpoclient::backup::Backup;
+    /// use mygpoclient::client::AuthenticatedClient;
+    /// use std::fs::File;
+    ///
+    /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+    /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+    /// #
+    /// let client = AuthenticatedClient::new(&username, &password)?;
+    /// let backup = Backup::read_archive(File::open("gpodder-backup.tar")?)?;
+    ///
+    /// let (plan, report) = backup.restore(&client, false)?;
+    /// print!("{}", plan);
+    /// for (step, error) in &report.failed {
+    ///     eprintln!("failed: {} ({})", step, error);
+    /// }
+    /// #
+    /// # Ok::<(), mygpoclient::error::Error>(())
+    /// ```
+    pub fn restore(
+        &self,
+        target: &AuthenticatedClient,
+        dry_run: bool,
+    ) -> Result<(RestorePlan, RestoreReport), Error> {
+        let plan = self.plan_restore(&Backup::create(target)?);
+        let report = if dry_run {
+            RestoreReport::default()
+        } else {
+            plan.apply(target)
+        };
+        Ok((plan, report))
+    }
+
+    /// Changes needed to restore this backup on an account with the `current` state
+    pub fn plan_restore(&self, current: &Backup) -> RestorePlan {
+        let mut plan = RestorePlan::default();
+
+        for device_backup in &self.devices {
+            let device = &device_backup.device;
+            let current_device = current
+                .devices
+                .iter()
+                .find(|current_device| current_device.device.id == device.id);
+
+            match current_device {
+                None => plan.added_devices.push(device.clone()),
+                Some(current_device)
+                    if current_device.device.caption != device.caption
+                        || current_device.device.device_type != device.device_type =>
+                {
+                    plan.changed_devices.push(device.clone())
+                }
+                Some(_) => {}
+            }
+
+            let empty_subscriptions = Vec::new();
+            let current_subscriptions =
+                current_device.map_or(&empty_subscriptions, |device| &device.subscriptions);
+            let changes = subscription_changes(
+                &device.id,
+                current_subscriptions,
+                &device_backup.subscriptions,
+            );
+            if !changes.add.is_empty() || !changes.remove.is_empty() {
+                plan.subscriptions.push(changes);
+            }
+
+            let empty_settings = HashMap::new();
+            let settings = changed_settings(
+                &device_backup.settings,
+                current_device.map_or(&empty_settings, |device| &device.settings),
+            );
+            if !settings.is_empty() {
+                plan.device_settings.insert(device.id.to_string(), settings);
+            }
+        }
+
+        // servers may change the format of timestamps and drop unknown fields, so actions are compared by episode, type and timestamp in seconds
+        let mut current_actions: HashMap<_, Vec<&EpisodeAction>> = HashMap::new();
+        for action in &current.episode_actions {
+            current_actions
+                .entry(action_key(action))
+                .or_default()
+                .push(action);
+        }
+        plan.episode_actions = self
+            .episode_actions
+            .iter()
+            .filter(|action| {
+                !current_actions
+                    .get(&action_key(action))
+                    .is_some_and(|current_actions| {
+                        current_actions.iter().any(|current_action| {
+                            current_action.podcast == action.podcast
+                                && current_action.is_same_episode(action)
+                        })
+                    })
+            })
+            .cloned()
+            .collect();
+
+        plan.account_settings = changed_settings(&self.account_settings, &current.account_settings);
+
+        let empty_settings = HashMap::new();
+        for (podcast, settings) in &self.podcast_settings {
+            let settings = changed_settings(
+                settings,
+                current
+                    .podcast_settings
+                    .get(podcast)
+                    .unwrap_or(&empty_settings),
+            );
+            if !settings.is_empty() {
+                plan.podcast_settings.insert(podcast.clone(), settings);
+            }
+        }
+
+        for episode_settings in &self.episode_settings {
+            let current_settings = current
+                .episode_settings
+                .iter()
+                .find(|current_settings| {
+                    current_settings.podcast == episode_settings.podcast
+                        && current_settings.episode == episode_settings.episode
+                })
+                .map_or(&empty_settings, |current_settings| {
+                    &current_settings.settings
+                });
+            let settings = changed_settings(&episode_settings.settings, current_settings);
+            if !settings.is_empty() {
+                plan.episode_settings.push(EpisodeSettings {
+                    settings,
+                    ..episode_settings.clone()
+                });
+            }
+        }
+
+        plan.favorites = self
+            .favorites
+            .iter()
+            .filter(|favorite| !current.favorites.contains(favorite))
+            .cloned()
+            .collect();
+
+        plan
+    }
+}
+
+impl RestorePlan {
+    /// Whether restoring doesn't change anything
+    pub fn is_empty(&self) -> bool {
+        self == &RestorePlan::default()
+    }
+
+    /// Apply changes to the account of the given client
+    ///
+    /// Failed steps are skipped and listed in the returned report.
+    pub fn apply(&self, target: &AuthenticatedClient) -> RestoreReport {
+        let mut report = RestoreReport::default();
+
+        for device in self.added_devices.iter().chain(&self.changed_devices) {
+            report.record(format!("device {}", device.id), || {
+                target
+                    .device_client(device.id.clone())
+                    .update_device_data(device.caption.clone(), device.device_type)
+            });
+        }
+
+        for changes in &self.subscriptions {
+            report.record(format!("subscriptions {}", changes.device), || {
+                target
+                    .device(&changes.device)?
+                    .upload_subscription_changes(&changes.add, &changes.remove)
+            });
+        }
+
+        let upload =
+            target.upload_episode_actions_in_chunks(&self.episode_actions, &ChunkedUpload::new());
+        if upload.timestamp.is_some() {
+            report.applied += 1;
+        }
+        for error in upload.errors {
+            report.failed.push((String::from("episode actions"), error));
+        }
+
+        if !self.account_settings.is_empty() {
+            report.record(String::from("account settings"), || {
+                target.save_account_settings(self.account_settings.clone(), Vec::new())
+            });
+        }
+        for (device, settings) in &self.device_settings {
+            report.record(format!("device settings {}", device), || {
+                target
+                    .device(device)?
+                    .save_device_settings(settings.clone(), Vec::new())
+            });
+        }
+        for (podcast, settings) in &self.podcast_settings {
+            report.record(format!("podcast settings {}", podcast), || {
+                target.save_podcast_settings(settings.clone(), Vec::new(), podcast.clone())
+            });
+        }
+        for episode_settings in &self.episode_settings {
+            report.record(
+                format!("episode settings {}", episode_settings.episode),
+                || {
+                    target.save_episode_settings(
+                        episode_settings.settings.clone(),
+                        Vec::new(),
+                        episode_settings.podcast.clone(),
+                        episode_settings.episode.clone(),
+                    )
+                },
+            );
+        }
+
+        for favorite in &self.favorites {
+            report.record(format!("favorite {}", favorite.url), || {
+                let mut settings = HashMap::new();
+                settings.insert(FAVORITE_SETTING.to_owned(), true.to_string());
+                target.save_episode_settings(
+                    settings,
+                    Vec::new(),
+                    favorite.podcast_url.clone(),
+                    favorite.url.clone(),
+                )
+            });
+        }
+
+        report
+    }
+}
+
+impl RestoreReport {
+    /// Whether all steps have been applied
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    fn record<T>(&mut self, step: String, apply: impl FnOnce() -> Result<T, Error>) {
+        match apply() {
+            Ok(_) => self.applied += 1,
+            Err(error) => self.failed.push((step, error)),
+        }
+    }
+}
+
+/// All devices of the account with their subscriptions and settings
+pub(crate) fn device_backups(client: &AuthenticatedClient) -> Result<Vec<DeviceBackup>, Error> {
+    let settings_supported = client.profile().supports(Endpoint::Settings);
+    client
+        .list_devices()?
+        .into_iter()
+        .map(|device| {
+            let device_client = client.device_client(device.id.clone());
+            Ok(DeviceBackup {
+                subscriptions: device_client.get_subscriptions_of_device()?,
+                settings: if settings_supported {
+                    device_client.get_device_settings()?
+                } else {
+                    HashMap::new()
+                },
+                device,
+            })
+        })
+        .collect()
+}
+
+/// Account settings, if the server supports settings
+pub(crate) fn account_settings(
+    client: &AuthenticatedClient,
+) -> Result<HashMap<String, String>, Error> {
+    if client.profile().supports(Endpoint::Settings) {
+        client.get_account_settings()
+    } else {
+        Ok(HashMap::new())
+    }
+}
+
+/// Favorite episodes, if the server supports favorites
+pub(crate) fn favorites(client: &AuthenticatedClient) -> Result<Vec<Episode>, Error> {
+    if client.profile().supports(Endpoint::Favorites) {
+        client.get_favorite_episodes()
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Settings of all podcasts subscribed on any of the devices, if they have settings and the server supports settings
+pub(crate) fn podcast_settings(
+    client: &AuthenticatedClient,
+    devices: &[DeviceBackup],
+) -> Result<BTreeMap<Url, HashMap<String, String>>, Error> {
+    let podcasts: BTreeSet<&Url> = devices
+        .iter()
+        .flat_map(|device| &device.subscriptions)
+        .collect();
+    let mut podcast_settings = BTreeMap::new();
+    if !client.profile().supports(Endpoint::Settings) {
+        return Ok(podcast_settings);
+    }
+    for podcast in podcasts {
+        let settings = client.get_podcast_settings(podcast.clone())?;
+        if !settings.is_empty() {
+            podcast_settings.insert(podcast.clone(), settings);
+        }
+    }
+    Ok(podcast_settings)
+}
+
+/// Settings of the given episodes, if they have settings and the server supports settings
+fn episode_settings(
+    client: &AuthenticatedClient,
+    episodes: &[Episode],
+) -> Result<Vec<EpisodeSettings>, Error> {
+    let episodes: BTreeSet<(&Url, &Url)> = episodes
+        .iter()
+        .map(|episode| (&episode.podcast_url, &episode.url))
+        .collect();
+    let mut episode_settings = Vec::new();
+    if !client.profile().supports(Endpoint::Settings) {
+        return Ok(episode_settings);
+    }
+    for (podcast, episode) in episodes {
+        let settings = client.get_episode_settings(podcast.clone(), episode.clone())?;
+        if !settings.is_empty() {
+            episode_settings.push(EpisodeSettings {
+                podcast: podcast.clone(),
+                episode: episode.clone(),
+                settings,
+            });
+        }
+    }
+    Ok(episode_settings)
+}
+
+fn action_key(action: &EpisodeAction) -> (&EpisodeActionType, Option<i64>) {
+    (
+        &action.action,
+        action
+            .timestamp
+            .map(|timestamp| timestamp.and_utc().timestamp()),
+    )
+}
+
+pub(crate) fn changed_settings(
+    wanted: &HashMap<String, String>,
+    current: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    wanted
+        .iter()
+        .filter(|(key, value)| current.get(*key) != Some(value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+fn write_settings(
+    f: &mut fmt::Formatter<'_>,
+    scope: &str,
+    settings: &HashMap<String, String>,
+) -> fmt::Result {
+    let sorted: BTreeMap<&String, &String> = settings.iter().collect();
+    for (key, value) in sorted {
+        writeln!(f, "~ {} setting {} = {}", scope, key, value)?;
+    }
+    Ok(())
+}
+
+/// Added and changed devices in the diff format of [RestorePlan]
+pub(crate) fn write_devices(
+    f: &mut fmt::Formatter<'_>,
+    added: &[Device],
+    changed: &[Device],
+) -> fmt::Result {
+    for device in added {
+        writeln!(f, "+ device {}", device)?;
+    }
+    for device in changed {
+        writeln!(f, "~ device {}", device)?;
+    }
+    Ok(())
+}
+
+/// Subscription changes in the diff format of [RestorePlan]
+pub(crate) fn write_subscriptions(
+    f: &mut fmt::Formatter<'_>,
+    subscriptions: &[DeviceSubscriptionChanges],
+) -> fmt::Result {
+    for changes in subscriptions {
+        for url in &changes.add {
+            writeln!(f, "+ subscription {} {}", changes.device, url)?;
+        }
+        for url in &changes.remove {
+            writeln!(f, "- subscription {} {}", changes.device, url)?;
+        }
+    }
+    Ok(())
+}
+
+/// Favorites in the diff format of [RestorePlan], prefixed with `sign`
+pub(crate) fn write_favorites(
+    f: &mut fmt::Formatter<'_>,
+    sign: char,
+    favorites: &[Episode],
+) -> fmt::Result {
+    for favorite in favorites {
+        writeln!(f, "{} favorite {}", sign, favorite.url)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for RestorePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_devices(f, &self.added_devices, &self.changed_devices)?;
+        write_subscriptions(f, &self.subscriptions)?;
+        for action in &self.episode_actions {
+            writeln!(
+                f,
+                "+ episode action {} {}",
+                action.action.name(),
+                action.episode
+            )?;
+        }
+        write_settings(f, "account", &self.account_settings)?;
+        for (device, settings) in &self.device_settings {
+            write_settings(f, &format!("device {}", device), settings)?;
+        }
+        for (podcast, settings) in &self.podcast_settings {
+            write_settings(f, &format!("podcast {}", podcast), settings)?;
+        }
+        for episode_settings in &self.episode_settings {
+            write_settings(
+                f,
+                &format!("episode {}", episode_settings.episode),
+                &episode_settings.settings,
+            )?;
+        }
+        write_favorites(f, '+', &self.favorites)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backup, EpisodeSettings, RestorePlan, BACKUP_VERSION};
+    use crate::cassette::Cassette;
+    use crate::client::AuthenticatedClient;
+    use crate::episode::EpisodeAction;
+    use crate::profile::ServerProfile;
+    use crate::test_util::{created, device_backup, interaction, login, url};
+    use chrono::Duration;
+    use std::collections::{BTreeMap, HashMap};
+
+    fn empty_backup() -> Backup {
+        Backup {
+            version: BACKUP_VERSION,
+            created: created(),
+            devices: Vec::new(),
+            episode_actions: Vec::new(),
+            account_settings: HashMap::new(),
+            podcast_settings: BTreeMap::new(),
+            episode_settings: Vec::new(),
+            favorites: Vec::new(),
+        }
+    }
+
+    fn backup() -> Backup {
+        let mut backup = empty_backup();
+        backup.devices = vec![
+            device_backup(
+                "phone",
+                "My Phone",
+                vec![url("http://example.com/feed1.rss")],
+            ),
+            device_backup(
+                "laptop",
+                "My Laptop",
+                vec![url("http://example.com/feed2.rss")],
+            ),
+        ];
+        backup.episode_actions = vec![EpisodeAction::new_download(
+            url("http://example.com/feed1.rss"),
+            url("http://example.com/1.mp3"),
+            None,
+        )];
+        backup
+            .account_settings
+            .insert(String::from("public"), String::from("false"));
+        backup.episode_settings = vec![EpisodeSettings {
+            podcast: url("http://example.com/feed1.rss"),
+            episode: url("http://example.com/1.mp3"),
+            settings: vec![(String::from("rating"), String::from("5"))]
+                .into_iter()
+                .collect(),
+        }];
+        backup
+    }
+
+    #[test]
+    fn archive_round_trip() {
+        let backup = backup();
+        let mut archive = Vec::new();
+        backup.write_archive(&mut archive).unwrap();
+
+        assert_eq!(backup, Backup::read_archive(archive.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn newer_archive_version_is_rejected() {
+        let mut backup = backup();
+        backup.version = BACKUP_VERSION + 1;
+        let mut archive = Vec::new();
+        backup.write_archive(&mut archive).unwrap();
+
+        assert!(Backup::read_archive(archive.as_slice()).is_err());
+    }
+
+    #[test]
+    fn restore_on_identical_account_changes_nothing() {
+        assert!(backup().plan_restore(&backup()).is_empty());
+    }
+
+    #[test]
+    fn restore_plan_diff() {
+        let mut current = empty_backup();
+        current.devices = vec![device_backup(
+            "phone",
+            "Old Phone",
+            vec![url("http://example.com/feed3.rss")],
+        )];
+        current
+            .account_settings
+            .insert(String::from("public"), String::from("true"));
+
+        let plan = backup().plan_restore(&current);
+
+        assert_eq!(
+            "+ device Mobile My Laptop (id=laptop)\n\
+             ~ device Mobile My Phone (id=phone)\n\
+             + subscription phone http://example.com/feed1.rss\n\
+             - subscription phone http://example.com/feed3.rss\n\
+             + subscription laptop http://example.com/feed2.rss\n\
+             + episode action download http://example.com/1.mp3\n\
+             ~ account setting public = false\n\
+             ~ episode http://example.com/1.mp3 setting rating = 5\n",
+            plan.to_string()
+        );
+    }
+
+    #[test]
+    fn restore_ignores_reformatted_actions() {
+        let mut download = EpisodeAction::new_download(
+            url("http://example.com/feed1.rss"),
+            url("http://example.com/1.mp3"),
+            Some(created() + Duration::milliseconds(500)),
+        );
+        download.guid = Some(String::from("episode-1"));
+        let delete = EpisodeAction::new_delete(
+            url("http://example.com/feed1.rss"),
+            url("http://example.com/1.mp3"),
+            Some(created()),
+        );
+        let mut backup = empty_backup();
+        backup.episode_actions = vec![download, delete.clone()];
+        // the server truncated the timestamp and dropped the GUID
+        let mut current = empty_backup();
+        current.episode_actions = vec![EpisodeAction::new_download(
+            url("http://example.com/feed1.rss"),
+            url("http://example.com/1.mp3"),
+            Some(created()),
+        )];
+
+        assert_eq!(vec![delete], backup.plan_restore(&current).episode_actions);
+    }
+
+    #[test]
+    fn apply_reports_failed_steps() {
+        // saving account settings isn't recorded and fails
+        let cassette = Cassette::from_interactions(vec![
+            interaction(
+                "POST",
+                "https://gpodder.net/api/2/devices/alice/phone.json",
+                Some(r#"{"caption": "My Phone", "type": "mobile"}"#),
+                "",
+            ),
+            interaction(
+                "POST",
+                "https://gpodder.net/api/2/settings/alice/podcast.json?podcast=http%3A%2F%2Fexample.com%2Ffeed1.rss",
+                Some(r#"{"set": {"auto_download": "true"}, "remove": []}"#),
+                r#"{"auto_download": "true"}"#,
+            ),
+        ]);
+        let client = AuthenticatedClient::new("alice", "secret")
+            .unwrap()
+            .with_cassette(cassette.clone());
+        let mut plan = RestorePlan::default();
+        plan.added_devices.push(backup().devices[0].device.clone());
+        plan.account_settings
+            .insert(String::from("public"), String::from("false"));
+        plan.podcast_settings.insert(
+            url("http://example.com/feed1.rss"),
+            vec![(String::from("auto_download"), String::from("true"))]
+                .into_iter()
+                .collect(),
+        );
+
+        let report = plan.apply(&client);
+
+        assert_eq!(2, report.applied);
+        assert_eq!(1, report.failed.len());
+        assert_eq!("account settings", report.failed[0].0);
+        assert!(!report.is_success());
+        assert!(cassette.interactions().is_empty());
+    }
+
+    #[test]
+    fn create_skips_unsupported_endpoints() {
+        let cassette = Cassette::from_interactions(vec![
+            login(
+                "https://opodsync.example.com/api/2/auth/alice/login.json",
+                "abc",
+            ),
+            interaction(
+                "GET",
+                "https://opodsync.example.com/api/2/devices/alice.json",
+                None,
+                r#"[{"id": "phone", "caption": "My Phone", "type": "mobile", "subscriptions": 1}]"#,
+            ),
+            interaction(
+                "GET",
+                "https://opodsync.example.com/subscriptions/alice/phone.json",
+                None,
+                r#"["http://example.com/feed1.rss"]"#,
+            ),
+            interaction(
+                "GET",
+                "https://opodsync.example.com/api/2/episodes/alice.json?aggregated=false",
+                None,
+                r#"{"actions": [], "timestamp": 10}"#,
+            ),
+        ]);
+        let client =
+            AuthenticatedClient::with_server("https://opodsync.example.com", "alice", "secret")
+                .unwrap()
+                .with_profile(ServerProfile::opodsync())
+                .with_cassette(cassette.clone());
+
+        let mut backup = Backup::create(&client).unwrap();
+
+        assert!(cassette.interactions().is_empty());
+        backup.created = created();
+        let mut expected = empty_backup();
+        expected.devices = vec![device_backup(
+            "phone",
+            "My Phone",
+            vec![url("http://example.com/feed1.rss")],
+        )];
+        assert_eq!(expected, backup);
+    }
+}
@@ -0,0 +1,313 @@
+//! Recording and replaying of HTTP interactions
+//!
+//! A [Cassette] attached to a client either records all requests and responses into a JSON file, or serves recorded responses instead of sending requests. This allows testing code based on the API traits without network access or credentials, once a cassette has been recorded with real credentials or built with [from_interactions](Cassette::from_interactions).
+//!
+//! The `Authorization` and `Cookie` request headers and the values of cookies in `Set-Cookie` response headers are redacted when recording.
+
+use crate::error::Error;
+use crate::outbox::write_atomically;
+use reqwest::blocking::{Client, Request, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// value of redacted headers
+const REDACTED: &str = "[REDACTED]";
+
+const REDACTED_HEADERS: [&str; 2] = ["authorization", "cookie"];
+
+/// response headers which don't match the recorded body
+const SKIPPED_HEADERS: [&str; 3] = ["content-encoding", "content-length", "transfer-encoding"];
+
+/// Recorded request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    /// HTTP method
+    pub method: String,
+    /// URL including query
+    pub url: String,
+    /// request headers with lowercase names
+    pub headers: BTreeMap<String, String>,
+    /// request body
+    pub body: Option<String>,
+}
+
+/// Recorded response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecordedResponse {
+    /// HTTP status code
+    pub status: u16,
+    /// response headers with lowercase names
+    pub headers: BTreeMap<String, String>,
+    /// response body
+    pub body: String,
+}
+
+/// Request with its response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Interaction {
+    /// request
+    pub request: RecordedRequest,
+    /// response
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+/// Recorded HTTP interactions, see [module documentation](self)
+///
+/// Attach it to a client with `with_cassette`, e.g. [PublicClient::with_cassette](crate::client::PublicClient::with_cassette). Clones share their interactions.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mygpoclient::cassette::Cassette;
+/// use mygpoclient::client::PublicClient;
+/// use mygpoclient::directory::RetrieveTopTags;
+///
+/// // record once with network access
+/// let client = PublicClient::default().with_cassette(Cassette::record("tests/cassettes/top_tags.json"));
+/// let recorded = client.retrieve_top_tags(10)?;
+///
+/// // replay without network access
+/// let client = PublicClient::default().with_cassette(Cassette::replay("tests/cassettes/top_tags.json")?);
+/// assert_eq!(recorded, client.retrieve_top_tags(10)?);
+/// #
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    mode: Mode,
+    path: PathBuf,
+    interactions: Arc<Mutex<Vec<Interaction>>>,
+}
+
+impl Cassette {
+    /// Send requests and record them with their responses into a new JSON file at `path`.
+    ///
+    /// The file is written after every interaction.
+    pub fn record<P: AsRef<Path>>(path: P) -> Cassette {
+        Cassette {
+            mode: Mode::Record,
+            path: path.as_ref().to_path_buf(),
+            interactions: Default::default(),
+        }
+    }
+
+    /// Serve the responses recorded in the JSON file at `path` instead of sending requests.
+    ///
+    /// Every recorded interaction is served once, in the recorded order for equal requests. Requests without matching interaction fail with [UnmatchedRequest](Error::UnmatchedRequest).
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Cassette, Error> {
+        let path = path.as_ref().to_path_buf();
+        let interactions = serde_json::from_reader(File::open(&path)?)?;
+        Ok(Cassette {
+            mode: Mode::Replay,
+            path,
+            interactions: Arc::new(Mutex::new(interactions)),
+        })
+    }
+
+    /// Serve the given interactions instead of sending requests, like [replay](Cassette::replay)
+    pub fn from_interactions(interactions: Vec<Interaction>) -> Cassette {
+        Cassette {
+            mode: Mode::Replay,
+            path: PathBuf::new(),
+            interactions: Arc::new(Mutex::new(interactions)),
+        }
+    }
+
+    /// Recorded interactions, or interactions which haven't been replayed yet
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.interactions.lock().unwrap().clone()
+    }
+
+    pub(crate) fn send(&self, client: &Client, request: Request) -> Result<Response, Error> {
+        let recorded_request = record_request(&request);
+
+        let response = match self.mode {
+            Mode::Replay => {
+                let mut interactions = self.interactions.lock().unwrap();
+                let index = interactions
+                    .iter()
+                    .position(|interaction| matches(&interaction.request, &recorded_request))
+                    .ok_or_else(|| {
+                        Error::UnmatchedRequest(format!(
+                            "{} {}",
+                            recorded_request.method, recorded_request.url
+                        ))
+                    })?;
+                interactions.remove(index).response
+            }
+            Mode::Record => {
+                let response = client.execute(request)?;
+                let recorded_response = RecordedResponse {
+                    status: response.status().as_u16(),
+                    headers: record_headers(response.headers())
+                        .into_iter()
+                        .filter(|(name, _)| !SKIPPED_HEADERS.contains(&name.as_str()))
+                        .collect(),
+                    body: response.text()?,
+                };
+                // not locked while the request is sent, so clones can send requests in parallel
+                let mut interactions = self.interactions.lock().unwrap();
+                interactions.push(Interaction {
+                    request: recorded_request,
+                    response: recorded_response.clone(),
+                });
+                write_atomically(&self.path, &serde_json::to_vec_pretty(&*interactions)?)?;
+                recorded_response
+            }
+        };
+
+        let mut builder = http::Response::builder().status(response.status);
+        for (name, value) in &response.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        Ok(builder
+            .body(response.body.into_bytes())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?
+            .into())
+    }
+}
+
+fn record_request(request: &Request) -> RecordedRequest {
+    RecordedRequest {
+        method: request.method().to_string(),
+        url: request.url().to_string(),
+        headers: record_headers(request.headers()),
+        body: request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(|bytes| String::from_utf8_lossy(bytes).into_owned()),
+    }
+}
+
+fn record_headers(headers: &reqwest::header::HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                REDACTED.to_owned()
+            } else if name == reqwest::header::SET_COOKIE {
+                // keep the cookie name, so sessions can be replayed
+                let cookie = String::from_utf8_lossy(value.as_bytes());
+                let name = cookie.split('=').next().unwrap_or_default();
+                format!("{}={}", name, REDACTED)
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.as_str().to_owned(), value)
+        })
+        .collect()
+}
+
+/// Whether a recorded request matches a new one by method, URL and body; JSON bodies are compared as JSON values
+fn matches(recorded: &RecordedRequest, request: &RecordedRequest) -> bool {
+    let json = |body: &Option<String>| {
+        body.as_deref()
+            .and_then(|body| serde_json::from_str::<Value>(body).ok())
+    };
+    recorded.method == request.method
+        && recorded.url == request.url
+        && (recorded.body == request.body
+            || json(&recorded.body).is_some() && json(&recorded.body) == json(&request.body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cassette, REDACTED};
+    use crate::client::AuthenticatedClient;
+    use crate::error::Error;
+    use crate::settings::SaveAccountSettings;
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serve a single HTTP request with a fixed JSON response
+    fn serve_once(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            loop {
+                let read = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let content_length = text[..end]
+                        .lines()
+                        .filter_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(str::to_owned)
+                        })
+                        .find_map(|length| length.trim().parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + content_length {
+                        break;
+                    }
+                }
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+        format!("http://{}", address)
+    }
+
+    fn settings() -> HashMap<String, String> {
+        let mut settings = HashMap::new();
+        settings.insert(String::from("public"), String::from("false"));
+        settings.insert(String::from("theme"), String::from("dark"));
+        settings
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let path =
+            std::env::temp_dir().join(format!("mygpoclient-cassette-{}.json", std::process::id()));
+        let server = serve_once(r#"{"public": "false", "theme": "dark"}"#);
+
+        let client = AuthenticatedClient::with_server(&server, "alice", "secret")
+            .unwrap()
+            .with_cassette(Cassette::record(&path));
+        let recorded = client
+            .save_account_settings(settings(), Vec::new())
+            .unwrap();
+        assert_eq!(settings(), recorded);
+
+        let cassette = std::fs::read_to_string(&path).unwrap();
+        assert!(cassette.contains(REDACTED));
+        assert!(!cassette.contains("Basic "));
+
+        let client = AuthenticatedClient::with_server(&server, "alice", "secret")
+            .unwrap()
+            .with_cassette(Cassette::replay(&path).unwrap());
+        assert_eq!(
+            recorded,
+            client
+                .save_account_settings(settings(), Vec::new())
+                .unwrap()
+        );
+        assert!(matches!(
+            client.save_account_settings(settings(), Vec::new()),
+            Err(Error::UnmatchedRequest(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,186 +1,593 @@
 //! Clients for communication with the service
 
-use reqwest::blocking::{Client, Response};
-use reqwest::IntoUrl;
+use crate::cassette::Cassette;
+use crate::credentials::{CredentialProvider, Secret};
+use crate::device::{Device, ListDevices};
+use crate::error::Error;
+use crate::id::{DeviceId, Username};
+use crate::profile::{AuthMode, Endpoint, ServerProfile, Session};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{IntoUrl, StatusCode};
 use serde::Serialize;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Condvar, Mutex};
 
 const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
 const PACKAGE_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub(crate) const DEFAULT_SERVER: &str = "https://gpodder.net";
 
 /// Client without authenticatication
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct PublicClient {
     pub(crate) client: Client,
+    pub(crate) server: String,
+    pub(crate) cassette: Option<Cassette>,
+    pub(crate) profile: ServerProfile,
+    pub(crate) limiter: Option<Arc<Limiter>>,
 }
 
 /// Client authenticated with username and password
 #[derive(Debug, Clone)]
 pub struct AuthenticatedClient {
-    pub(crate) username: String,
-    pub(crate) password: String,
+    pub(crate) username: Username,
+    pub(crate) credentials: Arc<dyn CredentialProvider>,
     pub(crate) public_client: PublicClient,
+    pub(crate) session: Session,
 }
 
 /// Device-specific [AuthenticatedClient]
 #[derive(Debug, Clone)]
 pub struct DeviceClient {
-    pub(crate) device_id: String,
+    pub(crate) device_id: DeviceId,
     pub(crate) authenticated_client: AuthenticatedClient,
 }
 
 impl PublicClient {
     /// Create [PublicClient] locally
     pub fn new() -> PublicClient {
+        PublicClient::with_server(DEFAULT_SERVER)
+    }
+
+    /// Create [PublicClient] for a gpodder.net compatible server other than gpodder.net, e.g. `https://gpodder.example.com`
+    pub fn with_server(server: &str) -> PublicClient {
         PublicClient {
             client: Default::default(),
+            server: server.trim_end_matches('/').to_owned(),
+            cassette: None,
+            profile: Default::default(),
+            limiter: None,
         }
     }
 
-    pub(crate) fn get<U: IntoUrl>(&self, url: U) -> Result<Response, reqwest::Error> {
+    /// Send requests with the given reqwest [Client], e.g. to share its connection pool with other clients
+    pub fn with_http_client(mut self, client: Client) -> PublicClient {
+        self.client = client;
+        self
+    }
+
+    /// Send at most `limit` requests at the same time; clones share the limit
+    pub fn with_concurrency_limit(mut self, limit: NonZeroUsize) -> PublicClient {
+        self.limiter = Some(Arc::new(Limiter::new(limit)));
+        self
+    }
+
+    /// Use the given profile for a server which isn't gpodder.net
+    pub fn with_profile(mut self, profile: ServerProfile) -> PublicClient {
+        self.profile = profile;
+        self
+    }
+
+    /// Profile of the server
+    pub fn profile(&self) -> &ServerProfile {
+        &self.profile
+    }
+
+    /// Record requests into or replay responses from the given [Cassette]
+    pub fn with_cassette(mut self, cassette: Cassette) -> PublicClient {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    pub(crate) fn server(&self) -> &str {
+        &self.server
+    }
+
+    #[cfg(test)]
+    pub(crate) fn concurrency_limit(&self) -> Option<usize> {
+        self.limiter.as_ref().map(|limiter| limiter.limit)
+    }
+
+    pub(crate) fn get<U: IntoUrl>(&self, endpoint: Endpoint, url: U) -> Result<Response, Error> {
         let empty_slice: &[&String] = &[];
-        self.get_with_query(url, empty_slice)
+        self.get_with_query(endpoint, url, empty_slice)
     }
 
     pub(crate) fn get_with_query<U: IntoUrl, T: Serialize + ?Sized>(
         &self,
+        endpoint: Endpoint,
         url: U,
         query_parameters: &[&T],
-    ) -> Result<Response, reqwest::Error> {
-        self.client
+    ) -> Result<Response, Error> {
+        let request = self
+            .client
             .get(url)
             .header(
                 reqwest::header::USER_AGENT,
                 &format!("{}/{}", PACKAGE_NAME, PACKAGE_VERSION),
             )
-            .query(query_parameters)
-            .send()
+            .query(query_parameters);
+        self.send(Some(endpoint), request)
+    }
+
+    /// Send request to the given endpoint, `None` for requests which don't belong to an [Endpoint], e.g. login
+    pub(crate) fn send(
+        &self,
+        endpoint: Option<Endpoint>,
+        request: RequestBuilder,
+    ) -> Result<Response, Error> {
+        if let Some(endpoint) = endpoint {
+            self.profile.check(endpoint)?;
+        }
+        let request = request.build()?;
+        let permit = self.limiter.as_ref().map(|limiter| limiter.acquire());
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::RequestSpan::enter(endpoint, &request);
+        let response = match &self.cassette {
+            Some(cassette) => cassette.send(&self.client, request),
+            None => self.client.execute(request).map_err(Error::from),
+        };
+        // the body is read after the headers, so it is buffered to count the whole transfer against the limit
+        let response = match (permit, response) {
+            (Some(_permit), Ok(response)) => buffered(response),
+            (_, response) => response,
+        };
+        #[cfg(feature = "tracing")]
+        span.finish(&response);
+        response
     }
 }
 
 impl AuthenticatedClient {
     /// Create [AuthenticatedClient] locally
-    pub fn new(username: &str, password: &str) -> AuthenticatedClient {
+    ///
+    /// Fails with [Error::InvalidIdentifier] if `username` isn't a valid [Username].
+    pub fn new(username: &str, password: &str) -> Result<AuthenticatedClient, Error> {
+        AuthenticatedClient::with_server(DEFAULT_SERVER, username, password)
+    }
+
+    /// Create [AuthenticatedClient] for a gpodder.net compatible server other than gpodder.net
+    pub fn with_server(
+        server: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<AuthenticatedClient, Error> {
+        AuthenticatedClient::with_credentials(server, username, Secret::from(password))
+    }
+
+    /// Create [AuthenticatedClient] which fetches the password from `credentials` whenever it is needed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mygpoclient::client::AuthenticatedClient;
+    /// use mygpoclient::credentials::EnvCredentials;
+    ///
+    /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+    /// #
+    /// let client = AuthenticatedClient::with_credentials(
+    ///     "https://gpodder.net",
+    ///     &username,
+    ///     EnvCredentials::new("GPODDER_NET_PASSWORD"),
+    /// )?;
+    /// # Ok::<(), mygpoclient::error::Error>(())
+    /// ```
+    pub fn with_credentials<P: CredentialProvider + 'static>(
+        server: &str,
+        username: &str,
+        credentials: P,
+    ) -> Result<AuthenticatedClient, Error> {
+        Ok(AuthenticatedClient::with_username(
+            server,
+            Username::new(username)?,
+            credentials,
+        ))
+    }
+
+    pub(crate) fn with_username<P: CredentialProvider + 'static>(
+        server: &str,
+        username: Username,
+        credentials: P,
+    ) -> AuthenticatedClient {
         AuthenticatedClient {
-            username: username.to_owned(),
-            password: password.to_owned(),
-            public_client: PublicClient::new(),
+            username,
+            credentials: Arc::new(credentials),
+            public_client: PublicClient::with_server(server),
+            session: Default::default(),
         }
     }
 
-    pub(crate) fn get<U: IntoUrl>(&self, url: U) -> Result<Response, reqwest::Error> {
+    /// Send requests with the given reqwest [Client], see [PublicClient::with_http_client]
+    pub fn with_http_client(mut self, client: Client) -> AuthenticatedClient {
+        self.public_client = self.public_client.with_http_client(client);
+        self
+    }
+
+    /// Send at most `limit` requests at the same time, see [PublicClient::with_concurrency_limit]
+    ///
+    /// The limit is shared with clones and [device clients](AuthenticatedClient::device).
+    pub fn with_concurrency_limit(mut self, limit: NonZeroUsize) -> AuthenticatedClient {
+        self.public_client = self.public_client.with_concurrency_limit(limit);
+        self
+    }
+
+    /// Use the given profile for a server which isn't gpodder.net
+    pub fn with_profile(mut self, profile: ServerProfile) -> AuthenticatedClient {
+        self.public_client = self.public_client.with_profile(profile);
+        self
+    }
+
+    /// Profile of the server
+    pub fn profile(&self) -> &ServerProfile {
+        self.public_client.profile()
+    }
+
+    pub(crate) fn server(&self) -> &str {
+        self.public_client.server()
+    }
+
+    /// Create [DeviceClient] for the given device, which shares the connection pool, credentials and session with this client
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mygpoclient::client::AuthenticatedClient;
+    ///
+    /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+    /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+    /// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
+    /// #
+    /// let client = AuthenticatedClient::new(&username, &password)?;
+    /// let device_client = client.device(&deviceid)?;
+    ///
+    /// assert_eq!(deviceid, device_client.device_id().as_str());
+    /// # Ok::<(), mygpoclient::error::Error>(())
+    /// ```
+    pub fn device(&self, device_id: &str) -> Result<DeviceClient, Error> {
+        Ok(self.device_client(DeviceId::new(device_id)?))
+    }
+
+    pub(crate) fn device_client(&self, device_id: DeviceId) -> DeviceClient {
+        DeviceClient {
+            device_id,
+            authenticated_client: self.clone(),
+        }
+    }
+
+    /// List devices with a [DeviceClient] for each, see [device](AuthenticatedClient::device)
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mygpoclient::client::AuthenticatedClient;
+    /// use mygpoclient::subscription::SubscriptionsOfDevice;
+    ///
+    /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+    /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+    /// #
+    /// let client = AuthenticatedClient::new(&username, &password)?;
+    ///
+    /// for (device, device_client) in client.device_clients()? {
+    ///     println!("{}: {:?}", device, device_client.get_subscriptions_of_device()?);
+    /// }
+    /// # Ok::<(), mygpoclient::error::Error>(())
+    /// ```
+    pub fn device_clients(
+        &self,
+    ) -> Result<impl Iterator<Item = (Device, DeviceClient)> + '_, Error> {
+        Ok(self.list_devices()?.into_iter().map(move |device| {
+            let device_client = self.device_client(device.id.clone());
+            (device, device_client)
+        }))
+    }
+
+    /// Record requests into or replay responses from the given [Cassette]
+    pub fn with_cassette(mut self, cassette: Cassette) -> AuthenticatedClient {
+        self.public_client = self.public_client.with_cassette(cassette);
+        self
+    }
+
+    pub(crate) fn get<U: IntoUrl>(&self, endpoint: Endpoint, url: U) -> Result<Response, Error> {
         let empty_slice: &[&String] = &[];
-        self.get_with_query(url, empty_slice)
+        self.get_with_query(endpoint, url, empty_slice)
     }
 
     pub(crate) fn get_with_query<U: IntoUrl, T: Serialize + ?Sized>(
         &self,
+        endpoint: Endpoint,
         url: U,
         query_parameters: &[&T],
-    ) -> Result<Response, reqwest::Error> {
-        self.public_client
+    ) -> Result<Response, Error> {
+        let request = self
+            .public_client
             .client
             .get(url)
-            .basic_auth(&self.username, Some(&self.password))
             .header(
                 reqwest::header::USER_AGENT,
                 &format!("{}/{}", PACKAGE_NAME, PACKAGE_VERSION),
             )
-            .query(query_parameters)
-            .send()
+            .query(query_parameters);
+        self.send(endpoint, request)
     }
 
     pub(crate) fn put<T: Serialize + ?Sized, U: IntoUrl>(
         &self,
+        endpoint: Endpoint,
         url: U,
         json: &T,
-    ) -> Result<Response, reqwest::Error> {
-        self.public_client
+    ) -> Result<Response, Error> {
+        let request = self
+            .public_client
             .client
             .put(url)
-            .basic_auth(&self.username, Some(&self.password))
             .header(
                 reqwest::header::USER_AGENT,
                 &format!("{}/{}", PACKAGE_NAME, PACKAGE_VERSION),
             )
-            .json(json)
-            .send()
+            .json(json);
+        self.send(endpoint, request)
     }
 
     pub(crate) fn post<T: Serialize + ?Sized, U: IntoUrl>(
         &self,
+        endpoint: Endpoint,
         url: U,
         json: &T,
-    ) -> Result<Response, reqwest::Error> {
+    ) -> Result<Response, Error> {
         let empty_slice: &[&String] = &[];
-        self.post_with_query(url, json, empty_slice)
+        self.post_with_query(endpoint, url, json, empty_slice)
     }
 
     pub(crate) fn post_with_query<T: Serialize + ?Sized, V: Serialize + ?Sized, U: IntoUrl>(
         &self,
+        endpoint: Endpoint,
         url: U,
         json: &T,
         query_parameters: &[&V],
-    ) -> Result<Response, reqwest::Error> {
-        self.public_client
+    ) -> Result<Response, Error> {
+        let request = self
+            .public_client
             .client
             .post(url)
-            .basic_auth(&self.username, Some(&self.password))
             .header(
                 reqwest::header::USER_AGENT,
                 &format!("{}/{}", PACKAGE_NAME, PACKAGE_VERSION),
             )
             .query(query_parameters)
-            .json(json)
-            .send()
+            .json(json);
+        self.send(endpoint, request)
+    }
+}
+
+impl AuthenticatedClient {
+    /// Send request with credentials according to the [AuthMode] of the profile
+    ///
+    /// If the server rejects the session, e.g. because it expired, the client logs in again and retries the request once.
+    fn send(&self, endpoint: Endpoint, request: RequestBuilder) -> Result<Response, Error> {
+        match self.profile().auth {
+            AuthMode::Basic => self.public_client.send(
+                Some(endpoint),
+                request.basic_auth(&self.username, Some(self.password()?.expose())),
+            ),
+            AuthMode::Session => {
+                let retry = request.try_clone();
+                let session_id = self.session_id()?;
+                let response = self
+                    .public_client
+                    .send(Some(endpoint), with_session(request, &session_id))?;
+                match retry {
+                    Some(request) if response.status() == StatusCode::UNAUTHORIZED => {
+                        self.session.expire(&session_id);
+                        let session_id = self.session_id()?;
+                        self.public_client
+                            .send(Some(endpoint), with_session(request, &session_id))
+                    }
+                    _ => Ok(response),
+                }
+            }
+        }
+    }
+
+    fn password(&self) -> Result<Secret, Error> {
+        self.credentials.password(self.server(), &self.username)
     }
+
+    /// ID of the current session, logs in if there is none yet
+    ///
+    /// The session isn't locked while logging in, so concurrent requests may log in more than once.
+    fn session_id(&self) -> Result<String, Error> {
+        if let Some(session_id) = self.session.get() {
+            return Ok(session_id);
+        }
+
+        let request = self
+            .public_client
+            .client
+            .post(&format!(
+                "{}/api/2/auth/{}/login.json",
+                self.server(),
+                self.username.path_segment()
+            ))
+            .basic_auth(&self.username, Some(self.password()?.expose()))
+            .header(
+                reqwest::header::USER_AGENT,
+                &format!("{}/{}", PACKAGE_NAME, PACKAGE_VERSION),
+            );
+        let response = self.public_client.send(None, request)?.error_for_status()?;
+        let session_id = response
+            .headers()
+            .get_all(reqwest::header::SET_COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .filter_map(|cookie| cookie.split(';').next())
+            .find_map(|cookie| cookie.trim().strip_prefix("sessionid="))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "login response doesn't contain a session cookie",
+                )
+            })?
+            .to_owned();
+        self.session.set(session_id.clone());
+        Ok(session_id)
+    }
+}
+
+fn with_session(request: RequestBuilder, session_id: &str) -> RequestBuilder {
+    request.header(reqwest::header::COOKIE, format!("sessionid={}", session_id))
 }
 
 impl DeviceClient {
     /// Create [DeviceClient]
-    pub fn new(username: &str, password: &str, device_id: &str) -> DeviceClient {
-        DeviceClient {
-            device_id: device_id.to_owned(),
-            authenticated_client: AuthenticatedClient::new(username, password),
-        }
+    ///
+    /// Fails with [Error::InvalidIdentifier] if `username` isn't a valid [Username] or `device_id` isn't a valid [DeviceId].
+    pub fn new(username: &str, password: &str, device_id: &str) -> Result<DeviceClient, Error> {
+        AuthenticatedClient::new(username, password)?.device(device_id)
+    }
+
+    /// Create [DeviceClient] for a gpodder.net compatible server other than gpodder.net
+    pub fn with_server(
+        server: &str,
+        username: &str,
+        password: &str,
+        device_id: &str,
+    ) -> Result<DeviceClient, Error> {
+        AuthenticatedClient::with_server(server, username, password)?.device(device_id)
     }
 
-    pub(crate) fn get<U: IntoUrl>(&self, url: U) -> Result<Response, reqwest::Error> {
-        self.authenticated_client.get(url)
+    /// ID of the device
+    pub fn device_id(&self) -> &DeviceId {
+        &self.device_id
+    }
+
+    /// Record requests into or replay responses from the given [Cassette]
+    pub fn with_cassette(mut self, cassette: Cassette) -> DeviceClient {
+        self.authenticated_client = self.authenticated_client.with_cassette(cassette);
+        self
+    }
+
+    /// Use the given profile for a server which isn't gpodder.net
+    pub fn with_profile(mut self, profile: ServerProfile) -> DeviceClient {
+        self.authenticated_client = self.authenticated_client.with_profile(profile);
+        self
+    }
+
+    /// Profile of the server
+    pub fn profile(&self) -> &ServerProfile {
+        self.authenticated_client.profile()
+    }
+
+    pub(crate) fn server(&self) -> &str {
+        self.authenticated_client.server()
+    }
+
+    pub(crate) fn get<U: IntoUrl>(&self, endpoint: Endpoint, url: U) -> Result<Response, Error> {
+        self.authenticated_client.get(endpoint, url)
     }
 
     pub(crate) fn get_with_query<U: IntoUrl, T: Serialize + ?Sized>(
         &self,
+        endpoint: Endpoint,
         url: U,
         query_parameters: &[&T],
-    ) -> Result<Response, reqwest::Error> {
+    ) -> Result<Response, Error> {
         self.authenticated_client
-            .get_with_query(url, query_parameters)
+            .get_with_query(endpoint, url, query_parameters)
     }
 
     pub(crate) fn put<T: Serialize + ?Sized, U: IntoUrl>(
         &self,
+        endpoint: Endpoint,
         url: U,
         json: &T,
-    ) -> Result<Response, reqwest::Error> {
-        self.authenticated_client.put(url, json)
+    ) -> Result<Response, Error> {
+        self.authenticated_client.put(endpoint, url, json)
     }
 
     pub(crate) fn post<T: Serialize + ?Sized, U: IntoUrl>(
         &self,
+        endpoint: Endpoint,
         url: U,
         json: &T,
-    ) -> Result<Response, reqwest::Error> {
-        self.authenticated_client.post(url, json)
+    ) -> Result<Response, Error> {
+        self.authenticated_client.post(endpoint, url, json)
     }
 
     pub(crate) fn post_with_query<T: Serialize + ?Sized, V: Serialize + ?Sized, U: IntoUrl>(
         &self,
+        endpoint: Endpoint,
         url: U,
         json: &T,
         query_parameters: &[&V],
-    ) -> Result<Response, reqwest::Error> {
+    ) -> Result<Response, Error> {
         self.authenticated_client
-            .post_with_query(url, json, query_parameters)
+            .post_with_query(endpoint, url, json, query_parameters)
+    }
+}
+
+/// Response with the complete body read into memory
+fn buffered(response: Response) -> Result<Response, Error> {
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version());
+    for (name, value) in response.headers() {
+        builder = builder.header(name, value);
+    }
+    Ok(builder
+        .body(response.bytes()?.to_vec())
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?
+        .into())
+}
+
+/// Counting semaphore limiting concurrent requests
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    limit: usize,
+    active: Mutex<usize>,
+    released: Condvar,
+}
+
+/// Permission to send a request, released on drop
+struct Permit<'a>(&'a Limiter);
+
+impl Limiter {
+    fn new(limit: NonZeroUsize) -> Limiter {
+        Limiter {
+            limit: limit.get(),
+            active: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) -> Permit<'_> {
+        let mut active = self.active.lock().unwrap();
+        while *active >= self.limit {
+            active = self.released.wait(active).unwrap();
+        }
+        *active += 1;
+        Permit(self)
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.0.active.lock().unwrap() -= 1;
+        self.0.released.notify_one();
+    }
+}
+
+impl Default for PublicClient {
+    fn default() -> Self {
+        PublicClient::new()
     }
 }
 
@@ -195,3 +602,106 @@ impl AsRef<AuthenticatedClient> for DeviceClient {
         &self.authenticated_client
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AuthenticatedClient, Limiter};
+    use crate::cassette::Cassette;
+    use crate::profile::ServerProfile;
+    use crate::subscription::SubscriptionsOfDevice;
+    use crate::test_util::{interaction, login};
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn device_clients_share_session() {
+        let cassette = Cassette::from_interactions(vec![
+            login(
+                "https://opodsync.example.com/api/2/auth/alice/login.json",
+                "abc",
+            ),
+            interaction(
+                "GET",
+                "https://opodsync.example.com/api/2/devices/alice.json",
+                None,
+                r#"[{"id": "phone", "caption": "Phone", "type": "mobile", "subscriptions": 1},
+                    {"id": "laptop", "caption": "Laptop", "type": "laptop", "subscriptions": 0}]"#,
+            ),
+            interaction(
+                "GET",
+                "https://opodsync.example.com/subscriptions/alice/phone.json",
+                None,
+                r#"["http://example.com/feed.rss"]"#,
+            ),
+            interaction(
+                "GET",
+                "https://opodsync.example.com/subscriptions/alice/laptop.json",
+                None,
+                "[]",
+            ),
+        ]);
+        let client =
+            AuthenticatedClient::with_server("https://opodsync.example.com", "alice", "secret")
+                .unwrap()
+                .with_profile(ServerProfile::opodsync())
+                .with_cassette(cassette.clone());
+
+        let subscriptions: Vec<(String, usize)> = client
+            .device_clients()
+            .unwrap()
+            .map(|(device, device_client)| {
+                assert_eq!(device.id, *device_client.device_id());
+                let subscriptions = device_client.get_subscriptions_of_device().unwrap();
+                (device.id.to_string(), subscriptions.len())
+            })
+            .collect();
+
+        assert_eq!(
+            vec![(String::from("phone"), 1), (String::from("laptop"), 0)],
+            subscriptions
+        );
+        assert!(cassette.interactions().is_empty());
+    }
+
+    #[test]
+    fn limited_client_buffers_responses() {
+        let cassette = Cassette::from_interactions(vec![interaction(
+            "GET",
+            "https://gpodder.example.com/subscriptions/alice/phone.json",
+            None,
+            r#"["http://example.com/feed.rss"]"#,
+        )]);
+        let client =
+            AuthenticatedClient::with_server("https://gpodder.example.com", "alice", "secret")
+                .unwrap()
+                .with_concurrency_limit(NonZeroUsize::new(1).unwrap())
+                .with_cassette(cassette)
+                .device("phone")
+                .unwrap();
+
+        assert_eq!(1, client.get_subscriptions_of_device().unwrap().len());
+    }
+
+    #[test]
+    fn limit_concurrent_requests() {
+        let limiter = Limiter::new(NonZeroUsize::new(2).unwrap());
+        let active = AtomicUsize::new(0);
+        let max_active = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let _permit = limiter.acquire();
+                    let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_active.fetch_max(now_active, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(10));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        assert_eq!(2, max_active.load(Ordering::SeqCst));
+    }
+}
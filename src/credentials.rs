@@ -0,0 +1,314 @@
+//! Sources of passwords
+//!
+//! An [AuthenticatedClient](crate::client::AuthenticatedClient) fetches the password from its [CredentialProvider] whenever it needs it, i.e. for every request with basic authentication or for the login with session authentication. Passwords are held as [Secret], which is overwritten with zeros when dropped and never printed.
+
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use url::Url;
+use zeroize::Zeroize;
+
+/// value printed instead of secrets
+const REDACTED: &str = "[REDACTED]";
+
+/// Password which is zeroed on drop and redacted in [Debug](fmt::Debug) output
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::credentials::Secret;
+///
+/// let secret = Secret::from("hunter2");
+///
+/// assert_eq!("hunter2", secret.expose());
+/// assert_eq!("[REDACTED]", format!("{:?}", secret));
+/// ```
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Access the password
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Secret(secret)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Secret(secret.to_owned())
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Source of the password of a user
+pub trait CredentialProvider: fmt::Debug + Send + Sync {
+    /// Password of `username` on `server`, e.g. `https://gpodder.net`
+    fn password(&self, server: &str, username: &str) -> Result<Secret, Error>;
+}
+
+impl CredentialProvider for Secret {
+    fn password(&self, _server: &str, _username: &str) -> Result<Secret, Error> {
+        Ok(self.clone())
+    }
+}
+
+/// Password from an environment variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvCredentials {
+    variable: String,
+}
+
+impl EnvCredentials {
+    /// Read the password from the environment variable `variable`
+    pub fn new(variable: &str) -> EnvCredentials {
+        EnvCredentials {
+            variable: variable.to_owned(),
+        }
+    }
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn password(&self, _server: &str, _username: &str) -> Result<Secret, Error> {
+        std::env::var(&self.variable).map(Secret).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("environment variable {} not set", self.variable),
+            )
+            .into()
+        })
+    }
+}
+
+/// Password from the first line of a file, e.g. a container secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCredentials {
+    path: PathBuf,
+}
+
+impl FileCredentials {
+    /// Read the password from the file at `path`
+    pub fn new<P: AsRef<Path>>(path: P) -> FileCredentials {
+        FileCredentials {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl CredentialProvider for FileCredentials {
+    fn password(&self, _server: &str, _username: &str) -> Result<Secret, Error> {
+        let content = Secret(fs::read_to_string(&self.path)?);
+        Ok(Secret::from(
+            content.expose().lines().next().unwrap_or_default(),
+        ))
+    }
+}
+
+/// Password from a [.netrc](https://www.gnu.org/software/inetutils/manual/html_node/The-_002enetrc-file.html) file
+///
+/// The entry of the server's host and the username is used, or the `default` entry if there is none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetrcCredentials {
+    path: PathBuf,
+}
+
+impl NetrcCredentials {
+    /// Read passwords from `.netrc` in the home directory
+    pub fn new() -> NetrcCredentials {
+        let home = std::env::var_os("HOME").unwrap_or_default();
+        NetrcCredentials::with_path(Path::new(&home).join(".netrc"))
+    }
+
+    /// Read passwords from the netrc file at `path`
+    pub fn with_path<P: AsRef<Path>>(path: P) -> NetrcCredentials {
+        NetrcCredentials {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl Default for NetrcCredentials {
+    fn default() -> Self {
+        NetrcCredentials::new()
+    }
+}
+
+impl CredentialProvider for NetrcCredentials {
+    fn password(&self, server: &str, username: &str) -> Result<Secret, Error> {
+        let content = Secret(fs::read_to_string(&self.path)?);
+        let host = Url::parse(server)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_owned))
+            .unwrap_or_default();
+        netrc_password(content.expose(), &host, username).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no netrc entry for {} on {}", username, host),
+            )
+            .into()
+        })
+    }
+}
+
+/// Password of the matching machine entry or else of the default entry
+fn netrc_password(content: &str, host: &str, username: &str) -> Option<Secret> {
+    // (machine, login, password); machine is `None` for the default entry
+    let mut entries: Vec<(Option<&str>, Option<&str>, Option<&str>)> = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let mut tokens = line.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "machine" => entries.push((tokens.next(), None, None)),
+                "default" => entries.push((None, None, None)),
+                "login" => {
+                    if let Some(entry) = entries.last_mut() {
+                        entry.1 = tokens.next();
+                    }
+                }
+                "password" => {
+                    if let Some(entry) = entries.last_mut() {
+                        entry.2 = tokens.next();
+                    }
+                }
+                "macdef" => {
+                    // macro definitions end with an empty line
+                    lines.by_ref().find(|line| line.trim().is_empty());
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let matches = |machine: Option<&str>| {
+        entries.iter().find(|(entry_machine, login, _)| {
+            *entry_machine == machine && login.is_none_or(|login| login == username)
+        })
+    };
+    matches(Some(host))
+        .or_else(|| matches(None))
+        .and_then(|(_, _, password)| password.map(Secret::from))
+}
+
+/// Password from a callback, e.g. to ask the user or query a keyring
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::client::AuthenticatedClient;
+/// use mygpoclient::credentials::{CallbackCredentials, Secret};
+///
+/// let credentials = CallbackCredentials::new(|_server: &str, _username: &str| {
+///     Ok(Secret::from("secret from keyring"))
+/// });
+/// let client = AuthenticatedClient::with_credentials("https://gpodder.net", "alice", credentials)?;
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+pub struct CallbackCredentials<F> {
+    callback: F,
+}
+
+impl<F> CallbackCredentials<F>
+where
+    F: Fn(&str, &str) -> Result<Secret, Error> + Send + Sync,
+{
+    /// Call `callback` with server and username whenever the password is needed
+    pub fn new(callback: F) -> CallbackCredentials<F> {
+        CallbackCredentials { callback }
+    }
+}
+
+impl<F> CredentialProvider for CallbackCredentials<F>
+where
+    F: Fn(&str, &str) -> Result<Secret, Error> + Send + Sync,
+{
+    fn password(&self, server: &str, username: &str) -> Result<Secret, Error> {
+        (self.callback)(server, username)
+    }
+}
+
+impl<F> fmt::Debug for CallbackCredentials<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CallbackCredentials")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{netrc_password, CredentialProvider, FileCredentials, Secret};
+    use crate::client::AuthenticatedClient;
+
+    const NETRC: &str = "
+machine example.com login bob password other
+macdef init
+cd /pub
+password ignored
+
+machine gpodder.net
+    login alice
+    password netrc-secret
+default login anonymous password guest
+";
+
+    #[test]
+    fn netrc_entries() {
+        assert_eq!(
+            Some(Secret::from("netrc-secret")),
+            netrc_password(NETRC, "gpodder.net", "alice")
+        );
+        assert_eq!(
+            Some(Secret::from("guest")),
+            netrc_password(NETRC, "gpodder.net", "anonymous")
+        );
+        assert_eq!(
+            Some(Secret::from("other")),
+            netrc_password(NETRC, "example.com", "bob")
+        );
+        assert_eq!(None, netrc_password(NETRC, "gpodder.net", "bob"));
+    }
+
+    #[test]
+    fn file_credentials() {
+        let path = std::env::temp_dir().join(format!(
+            "mygpoclient-credentials-{}.txt",
+            std::process::id()
+        ));
+        std::fs::write(&path, "file-secret\n").unwrap();
+
+        let password = FileCredentials::new(&path)
+            .password("https://gpodder.net", "alice")
+            .unwrap();
+
+        assert_eq!("file-secret", password.expose());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn debug_output_is_redacted() {
+        let client = AuthenticatedClient::new("alice", "hunter2").unwrap();
+
+        assert!(!format!("{:?}", client).contains("hunter2"));
+        assert!(!format!("{:?}", client.device("phone").unwrap()).contains("hunter2"));
+    }
+}
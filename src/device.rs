@@ -4,6 +4,8 @@ use crate::client::{AuthenticatedClient, DeviceClient};
 use crate::directory::Episode;
 use crate::episode::EpisodeActionType;
 use crate::error::Error;
+use crate::id::DeviceId;
+use crate::profile::Endpoint;
 use crate::subscription::Podcast;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
@@ -12,8 +14,8 @@ use std::hash::{Hash, Hasher};
 use url::Url;
 
 /// Type of the [Device]
-#[serde(rename_all = "lowercase")]
 #[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "lowercase")]
 pub enum DeviceType {
     /// desktop computer
     Desktop,
@@ -33,7 +35,7 @@ pub struct Device {
     /// A device ID can be any string matching the regular expression `[\w.-]+`. The client application MUST generate a string to be used as its device ID, and SHOULD ensure that it is unique within the user account. A good approach is to combine the application name and the name of the host it is running on.
     ///
     /// If two applications share a device ID, this might cause subscriptions to be overwritten on the server side. While it is possible to retrieve a list of devices and their IDs from the server, this SHOULD NOT be used to let a user select an existing device ID.
-    pub id: String,
+    pub id: DeviceId,
     /// Human readable label for the device
     pub caption: String,
     /// Type of the device
@@ -43,7 +45,7 @@ pub struct Device {
     pub subscriptions: u16,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub(crate) struct DeviceData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) caption: Option<String>,
@@ -94,7 +96,7 @@ pub trait UpdateDeviceData {
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
     /// #
-    /// let client = DeviceClient::new(&username, &password, &deviceid);
+    /// let client = DeviceClient::new(&username, &password, &deviceid)?;
     ///
     /// client.update_device_data("My Phone".to_owned(), DeviceType::Mobile)?;
     /// # Ok::<(), mygpoclient::error::Error>(())
@@ -125,7 +127,7 @@ pub trait ListDevices {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     ///
     /// let devices = client.list_devices()?;
     ///
@@ -154,7 +156,7 @@ pub trait GetDeviceUpdates {
     /// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
     /// # let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() - 86400;
     /// #
-    /// let client = DeviceClient::new(&username, &password, &deviceid);
+    /// let client = DeviceClient::new(&username, &password, &deviceid)?;
     ///
     /// let device_updates = client.get_device_updates(timestamp, true)?;
     ///
@@ -169,6 +171,7 @@ pub trait GetDeviceUpdates {
 }
 
 impl UpdateDeviceData for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn update_device_data<T: Into<Option<String>>, U: Into<Option<DeviceType>>>(
         &self,
         caption: T,
@@ -179,9 +182,12 @@ impl UpdateDeviceData for DeviceClient {
             device_type: device_type.into(),
         };
         self.post(
+            Endpoint::Devices,
             &format!(
-                "https://gpodder.net/api/2/devices/{}/{}.json",
-                self.authenticated_client.username, self.device_id
+                "{}/api/2/devices/{}/{}.json",
+                self.server(),
+                self.authenticated_client.username.path_segment(),
+                self.device_id.path_segment()
             ),
             &input,
         )?;
@@ -190,23 +196,30 @@ impl UpdateDeviceData for DeviceClient {
 }
 
 impl ListDevices for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn list_devices(&self) -> Result<Vec<Device>, Error> {
         Ok(self
-            .get(&format!(
-                "https://gpodder.net/api/2/devices/{}.json",
-                self.username
-            ))?
+            .get(
+                Endpoint::Devices,
+                &format!(
+                    "{}/api/2/devices/{}.json",
+                    self.server(),
+                    self.username.path_segment()
+                ),
+            )?
             .json()?)
     }
 }
 
 impl ListDevices for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn list_devices(&self) -> Result<Vec<Device>, Error> {
         self.as_ref().list_devices()
     }
 }
 
 impl GetDeviceUpdates for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(since = since)))]
     fn get_device_updates(
         &self,
         since: u64,
@@ -224,9 +237,12 @@ impl GetDeviceUpdates for DeviceClient {
 
         Ok(self
             .get_with_query(
+                Endpoint::DeviceUpdates,
                 &format!(
-                    "https://gpodder.net/api/2/updates/{}/{}.json",
-                    self.authenticated_client.username, self.device_id
+                    "{}/api/2/updates/{}/{}.json",
+                    self.server(),
+                    self.authenticated_client.username.path_segment(),
+                    self.device_id.path_segment()
                 ),
                 &query_parameters,
             )?
@@ -273,6 +289,7 @@ impl fmt::Display for Device {
 #[cfg(test)]
 mod tests {
     use super::{Device, DeviceType};
+    use crate::id::DeviceId;
     use std::cmp::Ordering;
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
@@ -280,13 +297,13 @@ mod tests {
     #[test]
     fn equal_device_means_equal_hash() {
         let device1 = Device {
-            id: String::from("abcdef"),
+            id: DeviceId::new("abcdef").unwrap(),
             caption: String::from("gPodder on my Lappy"),
             device_type: DeviceType::Laptop,
             subscriptions: 27,
         };
         let device2 = Device {
-            id: String::from("abcdef"),
+            id: DeviceId::new("abcdef").unwrap(),
             caption: String::from("unnamed"),
             device_type: DeviceType::Other,
             subscriptions: 1,
@@ -307,13 +324,13 @@ mod tests {
     #[test]
     fn not_equal_devices_have_non_equal_ordering() {
         let device1 = Device {
-            id: String::from("abcdef"),
+            id: DeviceId::new("abcdef").unwrap(),
             caption: String::from("gPodder on my Lappy"),
             device_type: DeviceType::Laptop,
             subscriptions: 27,
         };
         let device2 = Device {
-            id: String::from("phone-au90f923023.203f9j23f"),
+            id: DeviceId::new("phone-au90f923023.203f9j23f").unwrap(),
             caption: String::from("My Phone"),
             device_type: DeviceType::Mobile,
             subscriptions: 5,
@@ -334,7 +351,7 @@ mod tests {
     #[test]
     fn display() {
         let device = Device {
-            id: String::from("abcdef"),
+            id: DeviceId::new("abcdef").unwrap(),
             caption: String::from("gPodder on my Lappy"),
             device_type: DeviceType::Laptop,
             subscriptions: 27,
@@ -2,6 +2,7 @@
 
 use crate::client::{AuthenticatedClient, DeviceClient, PublicClient};
 use crate::error::Error;
+use crate::profile::Endpoint;
 use crate::subscription::Podcast;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
@@ -209,48 +210,58 @@ pub trait PodcastSearch {
 }
 
 impl RetrieveTopTags for PublicClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(count = count)))]
     fn retrieve_top_tags(&self, count: u8) -> Result<Vec<Tag>, Error> {
         Ok(self
-            .get(&format!(
-                "https://gpodder.net/api/2/tags/{}.json",
-                count.to_string()
-            ))?
+            .get(
+                Endpoint::Directory,
+                &format!("{}/api/2/tags/{}.json", self.server(), count),
+            )?
             .json()?)
     }
 }
 
 impl RetrieveTopTags for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(count = count)))]
     fn retrieve_top_tags(&self, count: u8) -> Result<Vec<Tag>, Error> {
         self.public_client.retrieve_top_tags(count)
     }
 }
 
 impl RetrieveTopTags for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(count = count)))]
     fn retrieve_top_tags(&self, count: u8) -> Result<Vec<Tag>, Error> {
         self.authenticated_client.retrieve_top_tags(count)
     }
 }
 
 impl RetrievePodcastsForTag for PublicClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(count = count)))]
     fn retrieve_podcasts_for_tag(&self, tag: &str, count: u8) -> Result<Vec<Podcast>, Error> {
         let tag_urlencoded: String = byte_serialize(tag.as_bytes()).collect();
         Ok(self
-            .get(&format!(
-                "https://gpodder.net/api/2/tag/{}/{}.json",
-                tag_urlencoded,
-                count.to_string()
-            ))?
+            .get(
+                Endpoint::Directory,
+                &format!(
+                    "{}/api/2/tag/{}/{}.json",
+                    self.server(),
+                    tag_urlencoded,
+                    count
+                ),
+            )?
             .json()?)
     }
 }
 
 impl RetrievePodcastsForTag for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(count = count)))]
     fn retrieve_podcasts_for_tag(&self, tag: &str, count: u8) -> Result<Vec<Podcast>, Error> {
         self.public_client.retrieve_podcasts_for_tag(tag, count)
     }
 }
 
 impl RetrievePodcastsForTag for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(count = count)))]
     fn retrieve_podcasts_for_tag(&self, tag: &str, count: u8) -> Result<Vec<Podcast>, Error> {
         self.authenticated_client
             .retrieve_podcasts_for_tag(tag, count)
@@ -258,10 +269,12 @@ impl RetrievePodcastsForTag for DeviceClient {
 }
 
 impl RetrievePodcastData for PublicClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn retrieve_podcast_data(&self, url: Url) -> Result<Podcast, Error> {
         Ok(self
             .get_with_query(
-                "https://gpodder.net/api/2/data/podcast.json",
+                Endpoint::Directory,
+                &format!("{}/api/2/data/podcast.json", self.server()),
                 &[&("url", url.as_str())],
             )?
             .json()?)
@@ -269,22 +282,26 @@ impl RetrievePodcastData for PublicClient {
 }
 
 impl RetrievePodcastData for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn retrieve_podcast_data(&self, url: Url) -> Result<Podcast, Error> {
         self.public_client.retrieve_podcast_data(url)
     }
 }
 
 impl RetrievePodcastData for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn retrieve_podcast_data(&self, url: Url) -> Result<Podcast, Error> {
         self.authenticated_client.retrieve_podcast_data(url)
     }
 }
 
 impl RetrieveEpisodeData for PublicClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn retrieve_episode_data(&self, url: Url, podcast: Url) -> Result<Episode, Error> {
         Ok(self
             .get_with_query(
-                "https://gpodder.net/api/2/data/episode.json",
+                Endpoint::Directory,
+                &format!("{}/api/2/data/episode.json", self.server()),
                 &[&("url", url.as_str()), &("podcast", podcast.as_str())],
             )?
             .json()?)
@@ -292,12 +309,14 @@ impl RetrieveEpisodeData for PublicClient {
 }
 
 impl RetrieveEpisodeData for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn retrieve_episode_data(&self, url: Url, podcast: Url) -> Result<Episode, Error> {
         self.public_client.retrieve_episode_data(url, podcast)
     }
 }
 
 impl RetrieveEpisodeData for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn retrieve_episode_data(&self, url: Url, podcast: Url) -> Result<Episode, Error> {
         self.authenticated_client
             .retrieve_episode_data(url, podcast)
@@ -305,26 +324,33 @@ impl RetrieveEpisodeData for DeviceClient {
 }
 
 impl PodcastToplist for PublicClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(number = number)))]
     fn podcast_toplist(&self, number: u8, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error> {
-        let url = &format!("https://gpodder.net/toplist/{}.json", number);
+        let url = &format!("{}/toplist/{}.json", self.server(), number);
 
         if let Some(size) = scale_logo {
             Ok(self
-                .get_with_query(url, &[&("scale_logo", size.to_string())])?
+                .get_with_query(
+                    Endpoint::Directory,
+                    url,
+                    &[&("scale_logo", size.to_string())],
+                )?
                 .json()?)
         } else {
-            Ok(self.get(url)?.json()?)
+            Ok(self.get(Endpoint::Directory, url)?.json()?)
         }
     }
 }
 
 impl PodcastToplist for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(number = number)))]
     fn podcast_toplist(&self, number: u8, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error> {
         self.public_client.podcast_toplist(number, scale_logo)
     }
 }
 
 impl PodcastToplist for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(number = number)))]
     fn podcast_toplist(&self, number: u8, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error> {
         self.authenticated_client
             .podcast_toplist(number, scale_logo)
@@ -332,6 +358,7 @@ impl PodcastToplist for DeviceClient {
 }
 
 impl PodcastSearch for PublicClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn podcast_search(&self, q: &str, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error> {
         let mut query_parameters: Vec<&(&str, &str)> = Vec::new();
 
@@ -349,18 +376,24 @@ impl PodcastSearch for PublicClient {
         }
 
         Ok(self
-            .get_with_query("https://gpodder.net/search.json", &query_parameters)?
+            .get_with_query(
+                Endpoint::Directory,
+                &format!("{}/search.json", self.server()),
+                &query_parameters,
+            )?
             .json()?)
     }
 }
 
 impl PodcastSearch for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn podcast_search(&self, q: &str, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error> {
         self.public_client.podcast_search(q, scale_logo)
     }
 }
 
 impl PodcastSearch for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn podcast_search(&self, q: &str, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error> {
         self.authenticated_client.podcast_search(q, scale_logo)
     }
@@ -507,7 +540,7 @@ mod tests {
             description: String::from("[...]"),
             website: Some(Url::parse("http://www.podtrac.com/pts/redirect.mp3/aolradio.podcast.aol.com/twit/twit0245.mp3").unwrap()),
             mygpo_link: Url::parse("http://gpodder.net/episode/1046492").unwrap(),
-            released: NaiveDate::from_ymd(2010, 12, 25).and_hms(0, 30, 0),
+            released: NaiveDate::from_ymd_opt(2010, 12, 25).unwrap().and_hms_opt(0, 30, 0).unwrap(),
         };
         let episode2 = Episode {
             title: String::from("Climate Change, News Corp, and the Australian Fires"),
@@ -517,7 +550,7 @@ mod tests {
             description: String::from("[...]"),
             website: Some(Url::parse("http://www.wnycstudios.org/story/climate-change-news-corp-and-australian-fires/").unwrap()),
             mygpo_link: Url::parse("http://gpodder.net/podcast/on-the-media-1/climate-change-news-corp-and-the-australian-fires").unwrap(),
-            released: NaiveDate::from_ymd(2020, 1, 15).and_hms(17, 0, 0),
+            released: NaiveDate::from_ymd_opt(2020, 1, 15).unwrap().and_hms_opt(17, 0, 0).unwrap(),
         };
 
         assert_eq!(episode1, episode2);
@@ -542,7 +575,7 @@ mod tests {
             description: String::from("[...]"),
             website: Some(Url::parse("http://www.podtrac.com/pts/redirect.mp3/aolradio.podcast.aol.com/twit/twit0245.mp3").unwrap()),
             mygpo_link: Url::parse("http://gpodder.net/episode/1046492").unwrap(),
-            released: NaiveDate::from_ymd(2010, 12, 25).and_hms(0, 30, 0),
+            released: NaiveDate::from_ymd_opt(2010, 12, 25).unwrap().and_hms_opt(0, 30, 0).unwrap(),
         };
         let episode2 = Episode {
             title: String::from("Climate Change, News Corp, and the Australian Fires"),
@@ -552,7 +585,7 @@ mod tests {
             description: String::from("[...]"),
             website: Some(Url::parse("http://www.wnycstudios.org/story/climate-change-news-corp-and-australian-fires/").unwrap()),
             mygpo_link: Url::parse("http://gpodder.net/podcast/on-the-media-1/climate-change-news-corp-and-the-australian-fires").unwrap(),
-            released: NaiveDate::from_ymd(2020, 1, 15).and_hms(17, 0, 0),
+            released: NaiveDate::from_ymd_opt(2020, 1, 15).unwrap().and_hms_opt(17, 0, 0).unwrap(),
         };
 
         assert_ne!(episode1, episode2);
@@ -577,7 +610,7 @@ mod tests {
             description: String::from("[...]"),
             website: Some(Url::parse("http://www.podtrac.com/pts/redirect.mp3/aolradio.podcast.aol.com/twit/twit0245.mp3").unwrap()),
             mygpo_link: Url::parse("http://gpodder.net/episode/1046492").unwrap(),
-            released: NaiveDate::from_ymd(2010, 12, 25).and_hms(0, 30, 0),
+            released: NaiveDate::from_ymd_opt(2010, 12, 25).unwrap().and_hms_opt(0, 30, 0).unwrap(),
         };
 
         assert_eq!("TWiT 245: No Hitler For You: http://www.podtrac.com/pts/redirect.mp3/aolradio.podcast.aol.com/twit/twit0245.mp3".to_owned(), format!("{}", episode));
@@ -7,18 +7,30 @@
 //! - Clients can send play events with position information so that other clients know where to start playback.
 //! - Clients can send new states to reset previous events. This state needs to be interpreted by receiving clients and does not delete any information on the webservice.
 
-use crate::client::AuthenticatedClient;
+use crate::client::{AuthenticatedClient, DeviceClient};
 use crate::error::Error;
+use crate::profile::Endpoint;
 use chrono::naive::NaiveDateTime;
+use chrono::DateTime;
+use reqwest::StatusCode;
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::ser::{SerializeMap, Serializer};
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::thread;
+use std::time::Duration;
 use url::Url;
 
 /// Type of an [EpisodeAction]
 ///
+/// Unknown action types are kept as [Other](EpisodeActionType::Other), so that a single action of a newer client doesn't prevent reading the whole history.
+///
 /// [gpodder.net API Documentation]: https://gpoddernet.readthedocs.io/en/latest/api/reference/events.html#episode-action-types
-#[serde(rename_all = "lowercase", tag = "action")]
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
 pub enum EpisodeActionType {
     /// download event, so that other clients know where a file has already been downloaded
     Download,
@@ -29,25 +41,42 @@ pub enum EpisodeActionType {
         /// the position (in seconds) at which the client stopped playback
         position: u32,
         /// the position (in seconds) at which the client started playback. Requires total to be set.
-        #[serde(skip_serializing_if = "Option::is_none")]
         started: Option<u32>,
         /// the total length of the file in seconds. Requires started to be set.
-        #[serde(skip_serializing_if = "Option::is_none")]
         total: Option<u32>,
     },
     /// Resets previous events. This state needs to be interpreted by receiving clients and does not delete any information on the webservice.
     New,
     /// (undocumented)
     Flattr,
+    /// action type unknown to this library
+    Other {
+        /// value of the `action` field as sent by the server
+        action: String,
+        /// all fields of the episode action which are not known to this library
+        extra: ExtraFields,
+    },
 }
 
+/// JSON fields which are not known to this library
+///
+/// They are kept to send them back unchanged, e.g. when episode actions of other clients are uploaded again.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct ExtraFields(pub Map<String, Value>);
+
 /// Episode-related event
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
 pub struct EpisodeAction {
     /// feed URL to the podcast feed the episode belongs to
     pub podcast: Url,
     /// media URL of the episode
     pub episode: Url,
+    /// GUID of the episode as found in the podcast feed
+    ///
+    /// Not part of the gpodder.net API, but sent by many clients and servers, because it stays the same when the media URL changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guid: Option<String>,
     /// device ID on which the action has taken place
     #[serde(skip_serializing_if = "Option::is_none")]
     pub device: Option<String>,
@@ -55,8 +84,15 @@ pub struct EpisodeAction {
     #[serde(flatten)]
     pub action: EpisodeActionType,
     /// UTC timestamp when the action took place
+    ///
+    /// Besides the documented ISO 8601 format, RFC 3339 timestamps with offset and UNIX timestamps are accepted on deserialization.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timestamp: Option<NaiveDateTime>,
+    /// fields sent by other clients which are not known to this library
+    ///
+    /// For [Other](EpisodeActionType::Other) actions these are part of the action type instead.
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 // TODO see UploadSubscriptionChangesResponse
@@ -104,9 +140,9 @@ pub trait UploadEpisodeActions {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     ///
-    /// let download = EpisodeAction::new_download(Url::parse("http://example.com/feed1.rss").unwrap(), Url::parse("http://example.com/files/s01e20.mp3").unwrap(), Some(NaiveDate::from_ymd(2009,12,12).and_hms(9,0,0)));
+    /// let download = EpisodeAction::new_download(Url::parse("http://example.com/feed1.rss").unwrap(), Url::parse("http://example.com/files/s01e20.mp3").unwrap(), Some(NaiveDate::from_ymd_opt(2009,12,12).unwrap().and_hms_opt(9,0,0).unwrap()));
     /// let play = EpisodeAction::new_play(Url::parse("http://example.org/podcast2.php").unwrap(), Url::parse("http://ftp.example.org/foo2.ogg").unwrap(), None,120,15,500);
     /// let delete = EpisodeAction::new_delete(Url::parse("http://example.com/feed3.rss").unwrap(), Url::parse("http://example.com/files/s03e20.mp3").unwrap(), None);
     /// let new = EpisodeAction::new_new(Url::parse("http://example.com/feed4.rss").unwrap(), Url::parse("http://example.com/files/s04e20.mp3").unwrap(), None);
@@ -123,6 +159,82 @@ pub trait UploadEpisodeActions {
     ) -> Result<UploadEpisodeActionsResponse, Error>;
 }
 
+/// Options of [upload_episode_actions_in_chunks](UploadEpisodeActionsInChunks::upload_episode_actions_in_chunks)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkedUpload {
+    pub(crate) chunk_size: usize,
+    pub(crate) retries: u8,
+    pub(crate) retry_delay: Duration,
+}
+
+/// Result of [upload_episode_actions_in_chunks](UploadEpisodeActionsInChunks::upload_episode_actions_in_chunks)
+#[derive(Debug, Default)]
+pub struct ChunkedUploadReport {
+    /// timestamp returned for the last uploaded chunk; `None` if no chunk could be uploaded
+    pub timestamp: Option<u64>,
+    /// rewritten URLs of all uploaded chunks, see [UploadEpisodeActionsResponse::update_urls]
+    pub update_urls: Vec<(Url, Url)>,
+    /// indices of the actions which could not be uploaded
+    pub rejected: Vec<usize>,
+    /// last error of each chunk which could not be uploaded
+    pub errors: Vec<Error>,
+}
+
+/// Query of [get_episode_actions](GetEpisodeActions::get_episode_actions)
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::episode::EpisodeActionQuery;
+/// use url::Url;
+///
+/// let query = EpisodeActionQuery::new()
+///     .podcast(Url::parse("http://example.com/feed.rss").unwrap())
+///     .device("phone")
+///     .since(1_262_103_016)
+///     .aggregated(true);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpisodeActionQuery {
+    pub(crate) podcast: Option<Url>,
+    pub(crate) device: Option<String>,
+    pub(crate) since: Option<u64>,
+    pub(crate) aggregated: bool,
+}
+
+/// see [upload_episode_actions_in_chunks](UploadEpisodeActionsInChunks::upload_episode_actions_in_chunks)
+pub trait UploadEpisodeActionsInChunks {
+    /// Upload episode actions in several requests.
+    ///
+    /// Large uploads, e.g. imports of the complete listening history, tend to time out or to be rejected by the server. This splits the actions into chunks and retries chunks which failed because the server couldn't be reached, timed out or responded with a server error (5xx). Chunks rejected as too large (413) are split in halves. Chunks which still fail don't abort the upload; their actions are reported as [rejected](ChunkedUploadReport::rejected).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mygpoclient::client::AuthenticatedClient;
+    /// use mygpoclient::episode::{ChunkedUpload, EpisodeAction, UploadEpisodeActionsInChunks};
+    /// use url::Url;
+    ///
+    /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+    /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+    /// #
+    /// let client = AuthenticatedClient::new(&username, &password)?;
+    ///
+    /// let episode_actions: Vec<EpisodeAction> = (1..=5)
+    ///     .map(|i| EpisodeAction::new_download(Url::parse("http://example.com/feed1.rss").unwrap(), Url::parse(&format!("http://example.com/files/s01e{:02}.mp3", i)).unwrap(), None))
+    ///     .collect();
+    ///
+    /// let report = client.upload_episode_actions_in_chunks(&episode_actions, &ChunkedUpload::new().chunk_size(2));
+    /// assert!(report.rejected.is_empty());
+    /// # Ok::<(), mygpoclient::error::Error>(())
+    /// ```
+    fn upload_episode_actions_in_chunks(
+        &self,
+        actions: &[EpisodeAction],
+        options: &ChunkedUpload,
+    ) -> ChunkedUploadReport;
+}
+
 // TODO use Date(time?) instead of timestamps as integers
 /// see [get_episode_actions](GetEpisodeActions::get_episode_actions)
 pub trait GetEpisodeActions {
@@ -134,36 +246,281 @@ pub trait GetEpisodeActions {
     ///
     /// # Parameters
     ///
-    /// - `podcast`: The URL of a Podcast feed; if set, only actions for episodes of the given podcast are returned
-    /// - `since`: Only episode actions since the given timestamp are returned
-    /// - `aggregated`: If true, only the latest actions is returned for each episode
+    /// - `query`: see [EpisodeActionQuery]
+    ///
+    /// Implementations for [DeviceClient] only return actions of their own device, unless the query sets another [device](EpisodeActionQuery::device).
     ///
     /// # Examples
     ///
     /// ```
     /// use mygpoclient::client::AuthenticatedClient;
-    /// use mygpoclient::episode::GetEpisodeActions;
-    /// use chrono::prelude::*;
+    /// use mygpoclient::episode::{EpisodeActionQuery, GetEpisodeActions};
     /// use url::Url;
     ///
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     ///
-    /// let response = client.get_episode_actions(Some(Url::parse("http://example.com/feed.rss").unwrap()), None, false)?;
+    /// let query = EpisodeActionQuery::new().podcast(Url::parse("http://example.com/feed.rss").unwrap());
+    /// let response = client.get_episode_actions(&query)?;
     /// #
     /// # Ok::<(), mygpoclient::error::Error>(())
     /// ```
     fn get_episode_actions(
         &self,
-        podcast: Option<Url>,
-        since: Option<u64>,
-        aggregated: bool,
+        query: &EpisodeActionQuery,
     ) -> Result<GetEpisodeActionsResponse, Error>;
 }
 
+impl ChunkedUpload {
+    /// Create options with default values: chunks of 500 actions, 2 retries and a retry delay of 1 second
+    pub fn new() -> ChunkedUpload {
+        Default::default()
+    }
+
+    /// Maximum number of actions per request
+    pub fn chunk_size(mut self, chunk_size: usize) -> ChunkedUpload {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Number of additional attempts for a chunk which failed with a transient error
+    pub fn retries(mut self, retries: u8) -> ChunkedUpload {
+        self.retries = retries;
+        self
+    }
+
+    /// Time to wait before retrying a failed chunk
+    pub fn retry_delay(mut self, retry_delay: Duration) -> ChunkedUpload {
+        self.retry_delay = retry_delay;
+        self
+    }
+}
+
+impl EpisodeActionQuery {
+    /// Create query for all episode actions of the user
+    pub fn new() -> EpisodeActionQuery {
+        Default::default()
+    }
+
+    /// Only return actions for episodes of the podcast with the given feed URL
+    pub fn podcast(mut self, podcast: Url) -> EpisodeActionQuery {
+        self.podcast = Some(podcast);
+        self
+    }
+
+    /// Only return actions of the device with the given ID
+    pub fn device<T: Into<String>>(mut self, device: T) -> EpisodeActionQuery {
+        self.device = Some(device.into());
+        self
+    }
+
+    /// Only return actions uploaded since the given timestamp
+    ///
+    /// The timestamp SHOULD be the value returned by the previous episode retrieve request.
+    pub fn since(mut self, since: u64) -> EpisodeActionQuery {
+        self.since = Some(since);
+        self
+    }
+
+    /// If true, only the latest action is returned for each episode
+    pub fn aggregated(mut self, aggregated: bool) -> EpisodeActionQuery {
+        self.aggregated = aggregated;
+        self
+    }
+
+    fn query_parameters(&self) -> Vec<(&'static str, String)> {
+        let mut query_parameters = vec![("aggregated", self.aggregated.to_string())];
+        if let Some(since) = self.since {
+            query_parameters.push(("since", since.to_string()));
+        }
+        if let Some(podcast) = &self.podcast {
+            query_parameters.push(("podcast", podcast.to_string()));
+        }
+        if let Some(device) = &self.device {
+            query_parameters.push(("device", device.clone()));
+        }
+        query_parameters
+    }
+}
+
+/// Reduce pending episode actions before uploading them, similar to the server's aggregation.
+///
+/// - repeats of the latest kept action on the same episode are removed; an action repeated after other actions on the episode is kept, so the final state of the episode doesn't change
+/// - consecutive [Play](EpisodeActionType::Play) actions of the same device on the same episode are collapsed into the latest one. If the latest one [started](EpisodeActionType::Play::started) where the previous one stopped, it keeps `started` of the previous one; plays with a gap in between, e.g. after a seek, are both kept.
+/// - [New](EpisodeActionType::New) actions directly following a [Download](EpisodeActionType::Download) of the same episode are removed
+///
+/// Episodes are identified as in [is_same_episode](EpisodeAction::is_same_episode). The order of the remaining actions is kept; a collapsed play action takes the place of the latest one.
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::episode::{compact_episode_actions, EpisodeAction};
+/// use url::Url;
+///
+/// let podcast = Url::parse("http://example.org/podcast.php").unwrap();
+/// let episode = Url::parse("http://ftp.example.org/foo.ogg").unwrap();
+/// let actions = vec![
+///     EpisodeAction::new_play(podcast.clone(), episode.clone(), None, 30, 0, 500),
+///     EpisodeAction::new_play(podcast.clone(), episode.clone(), None, 60, 30, 500),
+///     EpisodeAction::new_play(podcast.clone(), episode.clone(), None, 90, 60, 500),
+/// ];
+///
+/// let compacted = compact_episode_actions(&actions);
+/// assert_eq!(vec![EpisodeAction::new_play(podcast, episode, None, 90, 0, 500)], compacted);
+/// ```
+pub fn compact_episode_actions(actions: &[EpisodeAction]) -> Vec<EpisodeAction> {
+    let mut compacted: Vec<Option<EpisodeAction>> = Vec::with_capacity(actions.len());
+    let mut latest_by_url: HashMap<&Url, usize> = HashMap::new();
+    let mut latest_by_guid: HashMap<&str, usize> = HashMap::new();
+
+    for action in actions {
+        let previous_index = latest_by_url
+            .get(&action.episode)
+            .into_iter()
+            .chain(
+                action
+                    .guid
+                    .as_deref()
+                    .and_then(|guid| latest_by_guid.get(guid)),
+            )
+            .copied()
+            .filter(|&index| {
+                compacted[index]
+                    .as_ref()
+                    .is_some_and(|previous| previous.is_same_episode(action))
+            })
+            .max();
+
+        let original = action;
+        let mut action = action.clone();
+        if let Some(index) = previous_index {
+            let previous = compacted[index].as_ref().expect("indexed actions are kept");
+            if previous == original {
+                continue;
+            }
+            match (&previous.action, &mut action.action) {
+                (EpisodeActionType::Download, EpisodeActionType::New) => continue,
+                (
+                    EpisodeActionType::Play {
+                        position,
+                        started: Some(first_started),
+                        ..
+                    },
+                    EpisodeActionType::Play {
+                        started: Some(started),
+                        ..
+                    },
+                ) if previous.device == action.device && position == started => {
+                    *started = *first_started;
+                    compacted[index] = None;
+                }
+                // separate plays, e.g. after a seek, are both needed to know what has been listened to
+                (
+                    EpisodeActionType::Play {
+                        started: Some(_), ..
+                    },
+                    EpisodeActionType::Play {
+                        started: Some(_), ..
+                    },
+                ) => {}
+                (EpisodeActionType::Play { .. }, EpisodeActionType::Play { .. })
+                    if previous.device == action.device =>
+                {
+                    compacted[index] = None;
+                }
+                _ => {}
+            }
+        }
+
+        let index = compacted.len();
+        latest_by_url.insert(&original.episode, index);
+        if let Some(guid) = original.guid.as_deref() {
+            latest_by_guid.insert(guid, index);
+        }
+        compacted.push(Some(action));
+    }
+
+    compacted.into_iter().flatten().collect()
+}
+
+impl EpisodeActionType {
+    /// Value of the `action` field, e.g. `play`
+    pub fn name(&self) -> &str {
+        match self {
+            EpisodeActionType::Download => "download",
+            EpisodeActionType::Delete => "delete",
+            EpisodeActionType::Play { .. } => "play",
+            EpisodeActionType::New => "new",
+            EpisodeActionType::Flattr => "flattr",
+            EpisodeActionType::Other { action, .. } => action,
+        }
+    }
+
+    // consumes the fields of the action type; unknown types consume all remaining fields
+    fn from_fields(
+        fields: &mut Map<String, Value>,
+    ) -> Result<EpisodeActionType, serde_json::Error> {
+        let action: String = take_required_field(fields, "action")?;
+        Ok(match action.to_lowercase().as_str() {
+            "download" => EpisodeActionType::Download,
+            "delete" => EpisodeActionType::Delete,
+            "play" => EpisodeActionType::Play {
+                position: take_required_field(fields, "position")?,
+                started: take_field(fields, "started")?,
+                total: take_field(fields, "total")?,
+            },
+            "new" => EpisodeActionType::New,
+            "flattr" => EpisodeActionType::Flattr,
+            _ => EpisodeActionType::Other {
+                action,
+                extra: ExtraFields(std::mem::take(fields)),
+            },
+        })
+    }
+}
+
 impl EpisodeAction {
+    fn from_fields(mut fields: Map<String, Value>) -> Result<EpisodeAction, serde_json::Error> {
+        let podcast = take_required_field(&mut fields, "podcast")?;
+        let episode = take_required_field(&mut fields, "episode")?;
+        let guid = take_field(&mut fields, "guid")?;
+        let device = take_field(&mut fields, "device")?;
+        let timestamp = match fields.remove("timestamp") {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(parse_timestamp(&value).ok_or_else(|| {
+                de::Error::custom(format!("unsupported timestamp format: {}", value))
+            })?),
+        };
+        let action = EpisodeActionType::from_fields(&mut fields)?;
+
+        Ok(EpisodeAction {
+            podcast,
+            episode,
+            guid,
+            device,
+            action,
+            timestamp,
+            extra: ExtraFields(fields),
+        })
+    }
+
+    /// Whether this action refers to the given episode.
+    ///
+    /// The episode is identified by its GUID if both GUIDs are known and by its media URL otherwise.
+    pub fn matches_episode(&self, guid: Option<&str>, episode: &Url) -> bool {
+        match (self.guid.as_deref(), guid) {
+            (Some(own_guid), Some(guid)) => own_guid == guid,
+            _ => &self.episode == episode,
+        }
+    }
+
+    /// Whether both actions refer to the same episode, see [matches_episode](EpisodeAction::matches_episode)
+    pub fn is_same_episode(&self, other: &EpisodeAction) -> bool {
+        self.matches_episode(other.guid.as_deref(), &other.episode)
+    }
+
     fn new(
         podcast: Url,
         episode: Url,
@@ -173,9 +530,11 @@ impl EpisodeAction {
         EpisodeAction {
             podcast,
             episode,
+            guid: None,
             device: None,
             action,
             timestamp,
+            extra: ExtraFields::default(),
         }
     }
 
@@ -209,17 +568,16 @@ impl EpisodeAction {
         timestamp: Option<NaiveDateTime>,
         position: u32,
     ) -> EpisodeAction {
-        EpisodeAction {
+        Self::new(
             podcast,
             episode,
-            device: None,
-            action: EpisodeActionType::Play {
+            timestamp,
+            EpisodeActionType::Play {
                 position,
                 started: None,
                 total: None,
             },
-            timestamp,
-        }
+        )
     }
 
     /// Create new [Play](EpisodeActionType::Play) event with [position](EpisodeActionType::Play::position), [started](EpisodeActionType::Play::started) and [total](EpisodeActionType::Play::total) information (in seconds) so that other clients know where to start playback.
@@ -231,72 +589,755 @@ impl EpisodeAction {
         started: u32,
         total: u32,
     ) -> EpisodeAction {
-        EpisodeAction {
+        Self::new(
             podcast,
             episode,
-            device: None,
-            action: EpisodeActionType::Play {
+            timestamp,
+            EpisodeActionType::Play {
                 position,
                 started: Some(started),
                 total: Some(total),
             },
-            timestamp,
-        }
+        )
     }
 }
 
 impl UploadEpisodeActions for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(actions = actions.len())))]
     fn upload_episode_actions(
         &self,
         actions: &[EpisodeAction],
     ) -> Result<UploadEpisodeActionsResponse, Error> {
         Ok(self
             .post(
-                &format!("https://gpodder.net/api/2/episodes/{}.json", self.username),
-                actions,
+                Endpoint::EpisodeActions,
+                &format!(
+                    "{}/api/2/episodes/{}.json",
+                    self.server(),
+                    self.username.path_segment()
+                ),
+                &self.profile().episode_actions_json(actions)?,
             )?
+            .error_for_status()?
             .json()?)
     }
 }
 
-impl GetEpisodeActions for AuthenticatedClient {
-    fn get_episode_actions(
+impl UploadEpisodeActions for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(actions = actions.len())))]
+    fn upload_episode_actions(
         &self,
-        podcast: Option<Url>,
-        since: Option<u64>,
-        aggregated: bool,
-    ) -> Result<GetEpisodeActionsResponse, Error> {
-        let mut query_parameters: Vec<&(&str, &str)> = Vec::new();
-
-        let aggregated_string = aggregated.to_string();
-        let query_parameter_aggregated = ("aggregated", aggregated_string.as_ref());
-        query_parameters.push(&query_parameter_aggregated);
+        actions: &[EpisodeAction],
+    ) -> Result<UploadEpisodeActionsResponse, Error> {
+        self.as_ref().upload_episode_actions(actions)
+    }
+}
 
-        let since_string = match since {
-            Some(s) => s.to_string(),
-            None => String::new(),
-        };
-        let query_parameter_since: (&str, &str) = ("since", since_string.as_ref());
+impl<T: UploadEpisodeActions + ?Sized> UploadEpisodeActionsInChunks for T {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(actions = actions.len())))]
+    fn upload_episode_actions_in_chunks(
+        &self,
+        actions: &[EpisodeAction],
+        options: &ChunkedUpload,
+    ) -> ChunkedUploadReport {
+        let mut report = ChunkedUploadReport::default();
+        for (chunk_index, chunk) in actions.chunks(options.chunk_size).enumerate() {
+            upload_chunk(
+                self,
+                chunk,
+                chunk_index * options.chunk_size,
+                options,
+                &mut report,
+            );
+        }
+        report
+    }
+}
 
-        if !since_string.is_empty() {
-            query_parameters.push(&query_parameter_since);
+/// Upload `chunk`, which starts at `offset` of all actions, retrying transient errors and splitting the chunk if it is too large
+fn upload_chunk<T: UploadEpisodeActions + ?Sized>(
+    client: &T,
+    chunk: &[EpisodeAction],
+    offset: usize,
+    options: &ChunkedUpload,
+    report: &mut ChunkedUploadReport,
+) {
+    let mut result = client.upload_episode_actions(chunk);
+    for _ in 0..options.retries {
+        match &result {
+            Err(error) if is_transient(error) => {}
+            _ => break,
         }
+        thread::sleep(options.retry_delay);
+        result = client.upload_episode_actions(chunk);
+    }
 
-        let podcast_string = match podcast {
-            Some(p) => p.to_string(),
-            None => String::new(),
-        };
-        let query_parameter_podcast: (&str, &str) = ("podcast", podcast_string.as_ref());
+    match result {
+        Ok(response) => {
+            report.timestamp = Some(response.timestamp);
+            report.update_urls.extend(response.update_urls);
+        }
+        Err(Error::ReqwestError(error))
+            if error.status() == Some(StatusCode::PAYLOAD_TOO_LARGE) && chunk.len() > 1 =>
+        {
+            let (first, second) = chunk.split_at(chunk.len() / 2);
+            upload_chunk(client, first, offset, options, report);
+            upload_chunk(client, second, offset + first.len(), options, report);
+        }
+        Err(error) => {
+            report.rejected.extend(offset..offset + chunk.len());
+            report.errors.push(error);
+        }
+    }
+}
 
-        if !podcast_string.is_empty() {
-            query_parameters.push(&query_parameter_podcast);
+/// Whether retrying the request may succeed: the server couldn't be reached, timed out or had an internal error
+fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::ReqwestError(error) => {
+            error.is_connect()
+                || error.is_timeout()
+                || error
+                    .status()
+                    .is_some_and(|status| status.is_server_error())
         }
+        _ => false,
+    }
+}
 
+impl GetEpisodeActions for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    fn get_episode_actions(
+        &self,
+        query: &EpisodeActionQuery,
+    ) -> Result<GetEpisodeActionsResponse, Error> {
+        let query_parameters = query.query_parameters();
         Ok(self
             .get_with_query(
-                &format!("https://gpodder.net/api/2/episodes/{}.json", self.username),
-                &query_parameters,
+                Endpoint::EpisodeActions,
+                &format!(
+                    "{}/api/2/episodes/{}.json",
+                    self.server(),
+                    self.username.path_segment()
+                ),
+                &query_parameters.iter().collect::<Vec<_>>(),
             )?
             .json()?)
     }
 }
+
+impl GetEpisodeActions for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    fn get_episode_actions(
+        &self,
+        query: &EpisodeActionQuery,
+    ) -> Result<GetEpisodeActionsResponse, Error> {
+        if query.device.is_some() {
+            self.as_ref().get_episode_actions(query)
+        } else {
+            self.as_ref()
+                .get_episode_actions(&query.clone().device(self.device_id.as_str()))
+        }
+    }
+}
+
+impl Serialize for EpisodeActionType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("action", self.name())?;
+        match self {
+            EpisodeActionType::Play {
+                position,
+                started,
+                total,
+            } => {
+                map.serialize_entry("position", position)?;
+                if let Some(started) = started {
+                    map.serialize_entry("started", started)?;
+                }
+                if let Some(total) = total {
+                    map.serialize_entry("total", total)?;
+                }
+            }
+            EpisodeActionType::Other { extra, .. } => {
+                for (key, value) in &extra.0 {
+                    map.serialize_entry(key, value)?;
+                }
+            }
+            _ => {}
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for EpisodeActionType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut fields = Map::deserialize(deserializer)?;
+        EpisodeActionType::from_fields(&mut fields).map_err(de::Error::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for EpisodeAction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        EpisodeAction::from_fields(Map::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+impl Default for ChunkedUpload {
+    fn default() -> Self {
+        ChunkedUpload {
+            chunk_size: 500,
+            retries: 2,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Hash for ExtraFields {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for (key, value) in &self.0 {
+            key.hash(state);
+            value.to_string().hash(state);
+        }
+    }
+}
+
+impl Ord for ExtraFields {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .iter()
+            .map(|(key, value)| (key, value.to_string()))
+            .cmp(other.0.iter().map(|(key, value)| (key, value.to_string())))
+    }
+}
+
+impl PartialOrd for ExtraFields {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn take_field<T: DeserializeOwned>(
+    fields: &mut Map<String, Value>,
+    key: &'static str,
+) -> Result<Option<T>, serde_json::Error> {
+    match fields.remove(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value).map(Some),
+    }
+}
+
+fn take_required_field<T: DeserializeOwned>(
+    fields: &mut Map<String, Value>,
+    key: &'static str,
+) -> Result<T, serde_json::Error> {
+    take_field(fields, key)?.ok_or_else(|| de::Error::missing_field(key))
+}
+
+/// Parse timestamps in the formats used by different clients and servers.
+///
+/// Accepts ISO 8601 without offset (as documented), RFC 3339 with offset, a space instead of `T` and UNIX timestamps in seconds or milliseconds. Timestamps with offset are converted to UTC.
+pub(crate) fn parse_timestamp(value: &Value) -> Option<NaiveDateTime> {
+    match value {
+        Value::Number(number) => number.as_i64().and_then(from_unix_timestamp),
+        Value::String(string) => {
+            let string = string.trim();
+            if let Ok(unix_timestamp) = string.parse::<i64>() {
+                return from_unix_timestamp(unix_timestamp);
+            }
+            if let Ok(date_time) = DateTime::parse_from_rfc3339(string) {
+                return Some(date_time.naive_utc());
+            }
+            if let Ok(date_time) = DateTime::parse_from_str(string, "%Y-%m-%dT%H:%M:%S%.f%z") {
+                return Some(date_time.naive_utc());
+            }
+            [
+                "%Y-%m-%dT%H:%M:%S%.f",
+                "%Y-%m-%d %H:%M:%S%.f",
+                "%Y-%m-%dT%H:%M",
+            ]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(string, format).ok())
+        }
+        _ => None,
+    }
+}
+
+fn from_unix_timestamp(unix_timestamp: i64) -> Option<NaiveDateTime> {
+    // values this large are milliseconds; as seconds they'd be thousands of years in the future
+    let seconds = if unix_timestamp.abs() >= 100_000_000_000 {
+        unix_timestamp / 1000
+    } else {
+        unix_timestamp
+    };
+    DateTime::from_timestamp(seconds, 0).map(|date_time| date_time.naive_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        compact_episode_actions, ChunkedUpload, EpisodeAction, EpisodeActionQuery,
+        EpisodeActionType, ExtraFields, UploadEpisodeActions, UploadEpisodeActionsInChunks,
+        UploadEpisodeActionsResponse,
+    };
+    use crate::error::Error;
+    use crate::test_util::url;
+    use chrono::NaiveDate;
+    use serde_json::json;
+    use std::cell::RefCell;
+    use std::time::Duration;
+    use url::Url;
+
+    struct FlakyServer {
+        attempts: RefCell<Vec<usize>>,
+        failing_attempts: Vec<usize>,
+        status: u16,
+        max_actions: usize,
+    }
+
+    impl FlakyServer {
+        /// Server responding with `status` to the given attempts
+        fn new(failing_attempts: Vec<usize>, status: u16) -> FlakyServer {
+            FlakyServer {
+                attempts: RefCell::new(Vec::new()),
+                failing_attempts,
+                status,
+                max_actions: usize::MAX,
+            }
+        }
+    }
+
+    fn status_error(status: u16) -> Error {
+        let response = http::Response::builder()
+            .status(status)
+            .body(Vec::new())
+            .unwrap();
+        reqwest::blocking::Response::from(response)
+            .error_for_status()
+            .unwrap_err()
+            .into()
+    }
+
+    impl UploadEpisodeActions for FlakyServer {
+        fn upload_episode_actions(
+            &self,
+            actions: &[EpisodeAction],
+        ) -> Result<UploadEpisodeActionsResponse, Error> {
+            let mut attempts = self.attempts.borrow_mut();
+            attempts.push(actions.len());
+            if actions.len() > self.max_actions {
+                return Err(status_error(413));
+            }
+            if self.failing_attempts.contains(&attempts.len()) {
+                return Err(status_error(self.status));
+            }
+            Ok(UploadEpisodeActionsResponse {
+                timestamp: attempts.len() as u64,
+                update_urls: vec![(actions[0].episode.clone(), actions[0].episode.clone())],
+            })
+        }
+    }
+
+    fn downloads(count: usize) -> Vec<EpisodeAction> {
+        (0..count)
+            .map(|i| {
+                EpisodeAction::new_download(
+                    Url::parse("http://example.com/feed.rss").unwrap(),
+                    Url::parse(&format!("http://example.com/{}.mp3", i)).unwrap(),
+                    None,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn upload_in_chunks_retries_failed_chunk() {
+        let server = FlakyServer::new(vec![2], 503);
+        let options = ChunkedUpload::new()
+            .chunk_size(2)
+            .retry_delay(Duration::from_millis(0));
+
+        let report = server.upload_episode_actions_in_chunks(&downloads(5), &options);
+
+        assert_eq!(vec![2, 2, 2, 1], *server.attempts.borrow());
+        assert_eq!(Some(4), report.timestamp);
+        assert_eq!(3, report.update_urls.len());
+        assert!(report.rejected.is_empty());
+        assert!(report.errors.is_empty());
+    }
+
+    #[test]
+    fn upload_in_chunks_reports_rejected_actions() {
+        let server = FlakyServer::new(vec![2, 3], 503);
+        let options = ChunkedUpload::new()
+            .chunk_size(2)
+            .retries(1)
+            .retry_delay(Duration::from_millis(0));
+
+        let report = server.upload_episode_actions_in_chunks(&downloads(5), &options);
+
+        assert_eq!(vec![2, 2, 2, 1], *server.attempts.borrow());
+        assert_eq!(Some(4), report.timestamp);
+        assert_eq!(2, report.update_urls.len());
+        assert_eq!(vec![2, 3], report.rejected);
+        assert_eq!(1, report.errors.len());
+    }
+
+    #[test]
+    fn upload_in_chunks_doesnt_retry_client_errors() {
+        let server = FlakyServer::new(vec![1], 400);
+        let options = ChunkedUpload::new()
+            .chunk_size(2)
+            .retry_delay(Duration::from_millis(0));
+
+        let report = server.upload_episode_actions_in_chunks(&downloads(3), &options);
+
+        assert_eq!(vec![2, 1], *server.attempts.borrow());
+        assert_eq!(vec![0, 1], report.rejected);
+        assert_eq!(1, report.errors.len());
+    }
+
+    #[test]
+    fn upload_in_chunks_splits_too_large_chunk() {
+        let mut server = FlakyServer::new(Vec::new(), 503);
+        server.max_actions = 1;
+        let options = ChunkedUpload::new()
+            .chunk_size(2)
+            .retry_delay(Duration::from_millis(0));
+
+        let report = server.upload_episode_actions_in_chunks(&downloads(3), &options);
+
+        assert_eq!(vec![2, 1, 1, 1], *server.attempts.borrow());
+        assert_eq!(3, report.update_urls.len());
+        assert!(report.rejected.is_empty());
+        assert!(report.errors.is_empty());
+    }
+
+    #[test]
+    fn default_query_parameters() {
+        assert_eq!(
+            vec![("aggregated", String::from("false"))],
+            EpisodeActionQuery::new().query_parameters()
+        );
+    }
+
+    #[test]
+    fn all_query_parameters() {
+        let query = EpisodeActionQuery::new()
+            .podcast(Url::parse("http://example.com/feed.rss").unwrap())
+            .device("phone")
+            .since(12345)
+            .aggregated(true);
+
+        assert_eq!(
+            vec![
+                ("aggregated", String::from("true")),
+                ("since", String::from("12345")),
+                ("podcast", String::from("http://example.com/feed.rss")),
+                ("device", String::from("phone")),
+            ],
+            query.query_parameters()
+        );
+    }
+
+    #[test]
+    fn compact_removes_exact_repeats() {
+        let download = EpisodeAction::new_download(
+            url("http://example.com/feed.rss"),
+            url("http://example.com/1.mp3"),
+            None,
+        );
+        let delete = EpisodeAction::new_delete(
+            url("http://example.com/feed.rss"),
+            url("http://example.com/1.mp3"),
+            None,
+        );
+
+        assert_eq!(
+            vec![download.clone()],
+            compact_episode_actions(&[download.clone(), download.clone()])
+        );
+        assert_eq!(
+            vec![download.clone(), delete.clone(), download.clone()],
+            compact_episode_actions(&[download.clone(), delete.clone(), download.clone()])
+        );
+        assert_eq!(
+            vec![download.clone(), delete.clone()],
+            compact_episode_actions(&[download.clone(), delete.clone(), delete])
+        );
+    }
+
+    #[test]
+    fn compact_collapses_consecutive_plays() {
+        let podcast = url("http://example.com/feed.rss");
+        let mut other_device = EpisodeAction::new_play(
+            podcast.clone(),
+            url("http://example.com/1.mp3"),
+            None,
+            10,
+            0,
+            500,
+        );
+        other_device.device = Some(String::from("laptop"));
+        let actions = vec![
+            EpisodeAction::new_play(
+                podcast.clone(),
+                url("http://example.com/1.mp3"),
+                None,
+                30,
+                0,
+                500,
+            ),
+            EpisodeAction::new_download(podcast.clone(), url("http://example.com/2.mp3"), None),
+            EpisodeAction::new_play(
+                podcast.clone(),
+                url("http://example.com/1.mp3"),
+                None,
+                60,
+                30,
+                500,
+            ),
+            other_device.clone(),
+            EpisodeAction::new_play_stop(
+                podcast.clone(),
+                url("http://example.com/1.mp3"),
+                None,
+                90,
+            ),
+        ];
+
+        assert_eq!(
+            vec![
+                EpisodeAction::new_download(podcast.clone(), url("http://example.com/2.mp3"), None),
+                EpisodeAction::new_play(
+                    podcast.clone(),
+                    url("http://example.com/1.mp3"),
+                    None,
+                    60,
+                    0,
+                    500
+                ),
+                other_device,
+                EpisodeAction::new_play_stop(podcast, url("http://example.com/1.mp3"), None, 90),
+            ],
+            compact_episode_actions(&actions)
+        );
+    }
+
+    #[test]
+    fn compact_keeps_plays_after_seek() {
+        let podcast = url("http://example.com/feed.rss");
+        let episode = url("http://example.com/1.mp3");
+        let actions = vec![
+            EpisodeAction::new_play(podcast.clone(), episode.clone(), None, 100, 0, 900),
+            EpisodeAction::new_play(podcast.clone(), episode.clone(), None, 600, 500, 900),
+            EpisodeAction::new_play(podcast.clone(), episode.clone(), None, 700, 600, 900),
+        ];
+
+        assert_eq!(
+            vec![
+                EpisodeAction::new_play(podcast.clone(), episode.clone(), None, 100, 0, 900),
+                EpisodeAction::new_play(podcast, episode, None, 700, 500, 900),
+            ],
+            compact_episode_actions(&actions)
+        );
+    }
+
+    #[test]
+    fn compact_keeps_plays_separated_by_other_action() {
+        let podcast = url("http://example.com/feed.rss");
+        let actions = vec![
+            EpisodeAction::new_play_stop(
+                podcast.clone(),
+                url("http://example.com/1.mp3"),
+                None,
+                30,
+            ),
+            EpisodeAction::new_new(podcast.clone(), url("http://example.com/1.mp3"), None),
+            EpisodeAction::new_play_stop(podcast, url("http://example.com/1.mp3"), None, 10),
+        ];
+
+        assert_eq!(actions, compact_episode_actions(&actions));
+    }
+
+    #[test]
+    fn compact_drops_new_after_download() {
+        let podcast = url("http://example.com/feed.rss");
+        let mut download =
+            EpisodeAction::new_download(podcast.clone(), url("http://example.com/1.mp3"), None);
+        download.guid = Some(String::from("episode-1"));
+        let mut new_moved =
+            EpisodeAction::new_new(podcast.clone(), url("https://cdn.example.com/1.mp3"), None);
+        new_moved.guid = Some(String::from("episode-1"));
+        let delete =
+            EpisodeAction::new_delete(podcast.clone(), url("http://example.com/2.mp3"), None);
+        let new_after_delete =
+            EpisodeAction::new_new(podcast, url("http://example.com/2.mp3"), None);
+
+        assert_eq!(
+            vec![download.clone(), delete.clone(), new_after_delete.clone()],
+            compact_episode_actions(&[download, new_moved, delete, new_after_delete])
+        );
+    }
+
+    #[test]
+    fn deserialize_play_action() {
+        let action: EpisodeAction = serde_json::from_value(json!({
+            "podcast": "http://example.org/podcast.php",
+            "episode": "http://ftp.example.org/foo.ogg",
+            "device": "abcdef",
+            "action": "play",
+            "started": 15,
+            "position": 120,
+            "total": 500,
+            "timestamp": "2009-12-12T09:00:00"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            EpisodeAction {
+                podcast: Url::parse("http://example.org/podcast.php").unwrap(),
+                episode: Url::parse("http://ftp.example.org/foo.ogg").unwrap(),
+                guid: None,
+                device: Some(String::from("abcdef")),
+                action: EpisodeActionType::Play {
+                    position: 120,
+                    started: Some(15),
+                    total: Some(500),
+                },
+                timestamp: Some(
+                    NaiveDate::from_ymd_opt(2009, 12, 12)
+                        .unwrap()
+                        .and_hms_opt(9, 0, 0)
+                        .unwrap()
+                ),
+                extra: ExtraFields::default(),
+            },
+            action
+        );
+    }
+
+    #[test]
+    fn unknown_action_type_round_trip() {
+        let json = json!({
+            "podcast": "http://example.org/podcast.php",
+            "episode": "http://ftp.example.org/foo.ogg",
+            "action": "star",
+            "rating": 5
+        });
+        let action: EpisodeAction = serde_json::from_value(json.clone()).unwrap();
+
+        match &action.action {
+            EpisodeActionType::Other { action, extra } => {
+                assert_eq!("star", action);
+                assert_eq!(Some(&json!(5)), extra.0.get("rating"));
+            }
+            other => panic!("unexpected action type {:?}", other),
+        }
+        assert!(action.extra.0.is_empty());
+        assert_eq!(json, serde_json::to_value(&action).unwrap());
+    }
+
+    #[test]
+    fn unknown_fields_round_trip() {
+        let json = json!({
+            "podcast": "http://example.org/podcast.php",
+            "episode": "http://ftp.example.org/foo.ogg",
+            "action": "download",
+            "client": "SomeOtherClient/1.0"
+        });
+        let action: EpisodeAction = serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(EpisodeActionType::Download, action.action);
+        assert_eq!(
+            Some(&json!("SomeOtherClient/1.0")),
+            action.extra.0.get("client")
+        );
+        assert_eq!(json, serde_json::to_value(&action).unwrap());
+    }
+
+    #[test]
+    fn guid_round_trip() {
+        let json = json!({
+            "podcast": "http://example.org/podcast.php",
+            "episode": "http://ftp.example.org/foo.ogg",
+            "guid": "urn:uuid:3e0b2a4b-1a7e-4f6c-9f5d-2f1c1d1c1d1c",
+            "action": "delete"
+        });
+        let action: EpisodeAction = serde_json::from_value(json.clone()).unwrap();
+
+        assert_eq!(
+            Some("urn:uuid:3e0b2a4b-1a7e-4f6c-9f5d-2f1c1d1c1d1c"),
+            action.guid.as_deref()
+        );
+        assert!(action.extra.0.is_empty());
+        assert_eq!(json, serde_json::to_value(&action).unwrap());
+    }
+
+    #[test]
+    fn same_episode_by_guid_then_url() {
+        let podcast = Url::parse("http://example.org/podcast.php").unwrap();
+        let old_url = Url::parse("http://ftp.example.org/foo.ogg").unwrap();
+        let new_url = Url::parse("https://cdn.example.org/foo.ogg").unwrap();
+
+        let mut old = EpisodeAction::new_download(podcast.clone(), old_url.clone(), None);
+        let mut moved = EpisodeAction::new_delete(podcast.clone(), new_url.clone(), None);
+        assert!(!old.is_same_episode(&moved));
+
+        old.guid = Some(String::from("foo"));
+        assert!(!old.is_same_episode(&moved));
+
+        moved.guid = Some(String::from("foo"));
+        assert!(old.is_same_episode(&moved));
+
+        moved.guid = Some(String::from("bar"));
+        assert!(!old.is_same_episode(&moved));
+
+        let without_guid = EpisodeAction::new_new(podcast, old_url.clone(), None);
+        assert!(old.is_same_episode(&without_guid));
+        assert!(old.matches_episode(None, &old_url));
+        assert!(!old.matches_episode(Some("bar"), &old_url));
+    }
+
+    #[test]
+    fn lenient_timestamps() {
+        let expected = NaiveDate::from_ymd_opt(2009, 12, 12)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        let timestamps = vec![
+            json!("2009-12-12T09:00:00"),
+            json!("2009-12-12T09:00:00.000"),
+            json!("2009-12-12 09:00:00"),
+            json!("2009-12-12T09:00:00Z"),
+            json!("2009-12-12T10:00:00+01:00"),
+            json!("2009-12-12T10:00:00+0100"),
+            json!(1_260_608_400),
+            json!("1260608400"),
+            json!(1_260_608_400_000_i64),
+        ];
+
+        for timestamp in timestamps {
+            let action: EpisodeAction = serde_json::from_value(json!({
+                "podcast": "http://example.org/podcast.php",
+                "episode": "http://ftp.example.org/foo.ogg",
+                "action": "new",
+                "timestamp": timestamp
+            }))
+            .unwrap();
+            assert_eq!(Some(expected), action.timestamp, "{}", timestamp);
+        }
+    }
+
+    #[test]
+    fn invalid_timestamp_is_error() {
+        let result = serde_json::from_value::<EpisodeAction>(json!({
+            "podcast": "http://example.org/podcast.php",
+            "episode": "http://ftp.example.org/foo.ogg",
+            "action": "new",
+            "timestamp": "yesterday"
+        }));
+
+        assert!(result.is_err());
+    }
+}
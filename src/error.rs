@@ -7,6 +7,26 @@ use std::fmt;
 pub enum Error {
     /// Error originating from reqwest crate
     ReqwestError(reqwest::Error),
+    /// Error reading or writing local files
+    IoError(std::io::Error),
+    /// Error (de)serializing local JSON data
+    JsonError(serde_json::Error),
+    /// Error parsing a TOML configuration file
+    TomlError(toml::de::Error),
+    /// Device with the given ID doesn't exist
+    UnknownDevice(String),
+    /// Podcast with the given feed URL is unknown
+    UnknownPodcast(url::Url),
+    /// Request without matching interaction in a replayed [Cassette](crate::cassette::Cassette)
+    UnmatchedRequest(String),
+    /// Endpoint isn't supported by the [profile](crate::profile::ServerProfile) of the server
+    Unsupported(crate::profile::Endpoint),
+    /// User with the given name doesn't exist
+    UnknownUser(String),
+    /// Username or device ID contains characters which aren't allowed
+    InvalidIdentifier(String),
+    /// Configuration value which can't be used, e.g. a limit of zero concurrent requests
+    InvalidConfig(String),
 }
 
 impl From<reqwest::Error> for Error {
@@ -15,10 +35,40 @@ impl From<reqwest::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::IoError(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::JsonError(error)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(error: toml::de::Error) -> Self {
+        Error::TomlError(error)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self {
             Error::ReqwestError(reqwest_error) => reqwest_error.fmt(f),
+            Error::IoError(io_error) => io_error.fmt(f),
+            Error::JsonError(json_error) => json_error.fmt(f),
+            Error::TomlError(toml_error) => toml_error.fmt(f),
+            Error::UnknownDevice(device_id) => write!(f, "unknown device {}", device_id),
+            Error::UnknownPodcast(url) => write!(f, "unknown podcast {}", url),
+            Error::Unsupported(endpoint) => write!(f, "{} not supported by server", endpoint),
+            Error::UnknownUser(username) => write!(f, "unknown user {}", username),
+            Error::InvalidIdentifier(id) => write!(f, "invalid identifier {:?}", id),
+            Error::InvalidConfig(message) => write!(f, "invalid configuration: {}", message),
+            Error::UnmatchedRequest(request) => {
+                write!(f, "no recorded interaction for request {}", request)
+            }
         }
     }
 }
@@ -4,6 +4,7 @@ use crate::client::AuthenticatedClient;
 use crate::client::DeviceClient;
 use crate::directory::Episode;
 use crate::error::Error;
+use crate::profile::Endpoint;
 
 /// see [get_favorite_episodes](GetFavoriteEpisodes::get_favorite_episodes)
 pub trait GetFavoriteEpisodes {
@@ -18,7 +19,7 @@ pub trait GetFavoriteEpisodes {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     ///
     /// client.get_favorite_episodes()?;
     /// # Ok::<(), mygpoclient::error::Error>(())
@@ -31,17 +32,23 @@ pub trait GetFavoriteEpisodes {
 }
 
 impl GetFavoriteEpisodes for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_favorite_episodes(&self) -> Result<Vec<Episode>, Error> {
         Ok(self
-            .get(&format!(
-                "https://gpodder.net/api/2/favorites/{}.json",
-                self.username
-            ))?
+            .get(
+                Endpoint::Favorites,
+                &format!(
+                    "{}/api/2/favorites/{}.json",
+                    self.server(),
+                    self.username.path_segment()
+                ),
+            )?
             .json()?)
     }
 }
 
 impl GetFavoriteEpisodes for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_favorite_episodes(&self) -> Result<Vec<Episode>, Error> {
         self.authenticated_client.get_favorite_episodes()
     }
@@ -0,0 +1,292 @@
+//! Local journal of subscription changes
+//!
+//! gpodder.net only returns the net subscription changes since a timestamp. A [SubscriptionHistory] records the changes of repeated [pulls](SubscriptionHistory::pull), so it can answer which subscriptions a device had at an earlier time and when a feed was added or removed. Changes are recorded with the timestamp of the pull which first returned them, so the history is as precise as the pulls are frequent.
+
+use crate::client::DeviceClient;
+use crate::error::Error;
+use crate::outbox::write_atomically;
+use crate::subscription::{
+    subscription_changes, DeviceSubscriptionChanges, GetSubscriptionChangesResponse,
+    SubscriptionChanges, UploadSubscriptionChangesResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use url::Url;
+
+/// Kind of a [SubscriptionEvent]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SubscriptionEventType {
+    /// feed was subscribed
+    Add,
+    /// feed was unsubscribed
+    Remove,
+}
+
+/// Single change in a [SubscriptionHistory]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubscriptionEvent {
+    /// device ID
+    pub device: String,
+    /// feed URL
+    pub url: Url,
+    /// whether the feed was added or removed
+    pub event_type: SubscriptionEventType,
+    /// server timestamp of the pull which returned this change
+    pub timestamp: u64,
+}
+
+/// Journal of the subscription changes of devices
+///
+/// # Examples
+///
+/// ```no_run
+/// use mygpoclient::client::DeviceClient;
+/// use mygpoclient::history::SubscriptionHistory;
+///
+/// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+/// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+/// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
+/// #
+/// let client = DeviceClient::new(&username, &password, &deviceid)?;
+///
+/// let mut history = SubscriptionHistory::load("history.json")?;
+/// history.pull(&client)?;
+/// history.save("history.json")?;
+///
+/// let yesterday = history.last_pull(&deviceid).unwrap_or_default().saturating_sub(86400);
+/// println!("{:?}", history.subscriptions_at(&deviceid, yesterday));
+/// #
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubscriptionHistory {
+    /// all recorded changes in chronological order
+    pub events: Vec<SubscriptionEvent>,
+    /// server timestamp of the last pull per device ID
+    pub timestamps: BTreeMap<String, u64>,
+}
+
+impl SubscriptionHistory {
+    /// Create empty history
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Read history from JSON file, or create empty history if the file doesn't exist
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        match File::open(path) {
+            Ok(file) => Ok(serde_json::from_reader(file)?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Write history as JSON file
+    ///
+    /// The file is replaced atomically, so a crash while saving keeps the previous history.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        write_atomically(path.as_ref(), &serde_json::to_vec(self)?)
+    }
+
+    /// Server timestamp of the last pull of the given device
+    pub fn last_pull(&self, device: &str) -> Option<u64> {
+        self.timestamps.get(device).copied()
+    }
+
+    /// Get the subscription changes of the client's device since the last pull and record them.
+    ///
+    /// Returns the number of recorded events.
+    pub fn pull(&mut self, client: &DeviceClient) -> Result<usize, Error> {
+        let since = self.last_pull(&client.device_id).unwrap_or_default();
+        let response = client.get_subscription_changes(since)?;
+        Ok(self.record(&client.device_id, &response))
+    }
+
+    /// Record a response of [get_subscription_changes](SubscriptionChanges::get_subscription_changes) for the given device.
+    ///
+    /// Changes which don't change the recorded subscriptions are skipped. Returns the number of recorded events.
+    pub fn record(&mut self, device: &str, response: &GetSubscriptionChangesResponse) -> usize {
+        let mut subscriptions = self.subscriptions_at(device, u64::MAX);
+        let recorded = self.events.len();
+
+        for (urls, event_type) in [
+            (&response.remove, SubscriptionEventType::Remove),
+            (&response.add, SubscriptionEventType::Add),
+        ] {
+            for url in urls {
+                let subscribed = subscriptions.contains(url);
+                match event_type {
+                    SubscriptionEventType::Add if !subscribed => subscriptions.push(url.clone()),
+                    SubscriptionEventType::Remove if subscribed => {
+                        subscriptions.retain(|subscription| subscription != url)
+                    }
+                    _ => continue,
+                }
+                self.events.push(SubscriptionEvent {
+                    device: device.to_owned(),
+                    url: url.clone(),
+                    event_type,
+                    timestamp: response.timestamp,
+                });
+            }
+        }
+
+        let last_pull = self.timestamps.entry(device.to_owned()).or_default();
+        *last_pull = response.timestamp.max(*last_pull);
+        self.events.len() - recorded
+    }
+
+    /// Subscriptions of the given device at the given server timestamp, in the order they were added
+    pub fn subscriptions_at(&self, device: &str, timestamp: u64) -> Vec<Url> {
+        let mut subscriptions: Vec<Url> = Vec::new();
+        for event in self
+            .events
+            .iter()
+            .filter(|event| event.device == device && event.timestamp <= timestamp)
+        {
+            match event.event_type {
+                SubscriptionEventType::Add => subscriptions.push(event.url.clone()),
+                SubscriptionEventType::Remove => {
+                    subscriptions.retain(|subscription| subscription != &event.url)
+                }
+            }
+        }
+        subscriptions
+    }
+
+    /// All events of the given feed on any device, i.e. when it was added or removed
+    pub fn events_of_feed(&self, url: &Url) -> Vec<&SubscriptionEvent> {
+        self.events
+            .iter()
+            .filter(|event| &event.url == url)
+            .collect()
+    }
+
+    /// Changes which revert the subscriptions of the given device to the state at the given server timestamp
+    pub fn undo_changes(&self, device: &str, timestamp: u64) -> DeviceSubscriptionChanges {
+        subscription_changes(
+            device,
+            &self.subscriptions_at(device, u64::MAX),
+            &self.subscriptions_at(device, timestamp),
+        )
+    }
+
+    /// Revert the subscriptions of the client's device to the state at the given server timestamp by uploading the inverse changes.
+    ///
+    /// The upload itself is recorded by the next [pull](SubscriptionHistory::pull). Returns `None` if there is nothing to revert.
+    pub fn undo(
+        &self,
+        client: &DeviceClient,
+        timestamp: u64,
+    ) -> Result<Option<UploadSubscriptionChangesResponse>, Error> {
+        let changes = self.undo_changes(&client.device_id, timestamp);
+        if changes.add.is_empty() && changes.remove.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(client.upload_subscription_changes(
+            &changes.add,
+            &changes.remove,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SubscriptionEventType, SubscriptionHistory};
+    use crate::subscription::GetSubscriptionChangesResponse;
+    use crate::test_util::url;
+
+    fn response(timestamp: u64, add: &[&str], remove: &[&str]) -> GetSubscriptionChangesResponse {
+        GetSubscriptionChangesResponse {
+            timestamp,
+            add: add.iter().map(|u| url(u)).collect(),
+            remove: remove.iter().map(|u| url(u)).collect(),
+        }
+    }
+
+    fn history() -> SubscriptionHistory {
+        let mut history = SubscriptionHistory::new();
+        history.record(
+            "phone",
+            &response(
+                10,
+                &["http://example.com/1.rss", "http://example.com/2.rss"],
+                &[],
+            ),
+        );
+        history.record(
+            "phone",
+            &response(
+                20,
+                &["http://example.com/3.rss"],
+                &["http://example.com/1.rss"],
+            ),
+        );
+        history.record("laptop", &response(25, &["http://example.com/1.rss"], &[]));
+        history
+    }
+
+    #[test]
+    fn subscriptions_at_timestamp() {
+        let history = history();
+
+        assert!(history.subscriptions_at("phone", 5).is_empty());
+        assert_eq!(
+            vec![
+                url("http://example.com/1.rss"),
+                url("http://example.com/2.rss")
+            ],
+            history.subscriptions_at("phone", 15)
+        );
+        assert_eq!(
+            vec![
+                url("http://example.com/2.rss"),
+                url("http://example.com/3.rss")
+            ],
+            history.subscriptions_at("phone", 20)
+        );
+        assert_eq!(Some(20), history.last_pull("phone"));
+    }
+
+    #[test]
+    fn redundant_changes_are_skipped() {
+        let mut history = history();
+
+        assert_eq!(
+            0,
+            history.record("phone", &response(30, &["http://example.com/2.rss"], &[]))
+        );
+        assert_eq!(Some(30), history.last_pull("phone"));
+    }
+
+    #[test]
+    fn events_of_feed() {
+        let history = history();
+        let events = history.events_of_feed(&url("http://example.com/1.rss"));
+
+        let summary: Vec<(&str, SubscriptionEventType, u64)> = events
+            .iter()
+            .map(|event| (event.device.as_str(), event.event_type, event.timestamp))
+            .collect();
+        assert_eq!(
+            vec![
+                ("phone", SubscriptionEventType::Add, 10),
+                ("phone", SubscriptionEventType::Remove, 20),
+                ("laptop", SubscriptionEventType::Add, 25),
+            ],
+            summary
+        );
+    }
+
+    #[test]
+    fn undo_changes_are_inverse() {
+        let changes = history().undo_changes("phone", 15);
+
+        assert_eq!(vec![url("http://example.com/1.rss")], changes.add);
+        assert_eq!(vec![url("http://example.com/3.rss")], changes.remove);
+    }
+}
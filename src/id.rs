@@ -0,0 +1,262 @@
+//! Validated identifiers
+//!
+//! Usernames and device IDs are part of URL paths. [Username] and [DeviceId] only accept the characters allowed by gpodder.net and are percent-encoded when inserted into a path, so an identifier can't address another endpoint.
+
+use crate::error::Error;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// characters which are encoded in a URL path segment, i.e. all except unreserved characters, `@` and `+`
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'@')
+    .remove(b'+');
+
+/// Username of a gpodder.net account
+///
+/// Usernames consist of letters, digits and the characters `_`, `.`, `@`, `+` and `-`. Usernames made only of dots are rejected, because `.` and `..` are resolved as relative paths in URLs.
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::id::Username;
+///
+/// assert!(Username::new("alice@example.com").is_ok());
+/// assert!(Username::new("alice/../bob").is_err());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Username(String);
+
+/// ID of a device
+///
+/// A device ID can be any string matching the regular expression `[\w.-]+`, i.e. letters, digits and the characters `_`, `.` and `-`, except for IDs made only of dots.
+///
+/// Deserialization is less strict: servers other than gpodder.net may return device IDs with other characters, so any ID which isn't empty and doesn't consist only of dots is accepted. Such IDs are percent-encoded in URL paths.
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::id::DeviceId;
+///
+/// assert!(DeviceId::new("gpodder-laptop").is_ok());
+/// assert!(DeviceId::new("laptop?since=0").is_err());
+/// ```
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[serde(into = "String")]
+pub struct DeviceId(String);
+
+/// Whether the identifier is a single path segment which isn't resolved as `.` or `..`
+fn is_path_segment(value: &str) -> bool {
+    !value.is_empty() && !value.chars().all(|c| c == '.')
+}
+
+fn validate(value: &str, special: &[char]) -> Result<String, Error> {
+    if is_path_segment(value)
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || special.contains(&c))
+    {
+        Ok(value.to_owned())
+    } else {
+        Err(Error::InvalidIdentifier(value.to_owned()))
+    }
+}
+
+fn path_segment(value: &str) -> String {
+    utf8_percent_encode(value, PATH_SEGMENT).to_string()
+}
+
+impl Username {
+    /// Validate username
+    pub fn new(username: &str) -> Result<Username, Error> {
+        validate(username, &['.', '@', '+', '-']).map(Username)
+    }
+
+    /// Username for services which don't use it in URLs, e.g. Nextcloud
+    pub(crate) fn unchecked(username: &str) -> Username {
+        Username(username.to_owned())
+    }
+
+    /// Username as string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Percent-encoded username for URL paths
+    pub(crate) fn path_segment(&self) -> String {
+        path_segment(&self.0)
+    }
+}
+
+impl DeviceId {
+    /// Validate device ID
+    pub fn new(device_id: &str) -> Result<DeviceId, Error> {
+        validate(device_id, &['.', '-']).map(DeviceId)
+    }
+
+    /// Device ID as string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Percent-encoded device ID for URL paths
+    pub(crate) fn path_segment(&self) -> String {
+        path_segment(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for DeviceId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let device_id = String::deserialize(deserializer)?;
+        if is_path_segment(&device_id) {
+            Ok(DeviceId(device_id))
+        } else {
+            Err(de::Error::custom(Error::InvalidIdentifier(device_id)))
+        }
+    }
+}
+
+impl Deref for Username {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for DeviceId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Username {
+    type Error = Error;
+
+    fn try_from(username: String) -> Result<Self, Self::Error> {
+        Username::new(&username)
+    }
+}
+
+impl TryFrom<String> for DeviceId {
+    type Error = Error;
+
+    fn try_from(device_id: String) -> Result<Self, Self::Error> {
+        DeviceId::new(&device_id)
+    }
+}
+
+impl FromStr for Username {
+    type Err = Error;
+
+    fn from_str(username: &str) -> Result<Self, Self::Err> {
+        Username::new(username)
+    }
+}
+
+impl FromStr for DeviceId {
+    type Err = Error;
+
+    fn from_str(device_id: &str) -> Result<Self, Self::Err> {
+        DeviceId::new(device_id)
+    }
+}
+
+impl From<Username> for String {
+    fn from(username: Username) -> Self {
+        username.0
+    }
+}
+
+impl From<DeviceId> for String {
+    fn from(device_id: DeviceId) -> Self {
+        device_id.0
+    }
+}
+
+impl PartialEq<str> for Username {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Username {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<str> for DeviceId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for DeviceId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for Username {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeviceId, Username};
+    use crate::error::Error;
+
+    #[test]
+    fn validate_identifiers() {
+        assert!(DeviceId::new("gPodder_on-my.Lappy").is_ok());
+        assert!(DeviceId::new("mööp").is_ok());
+        assert!(DeviceId::new("...a").is_ok());
+        for invalid in &["", ".", "..", "...", "a/b", "a?b", "a#b", "a b", "a@b"] {
+            assert!(matches!(
+                DeviceId::new(invalid),
+                Err(Error::InvalidIdentifier(_))
+            ));
+        }
+        assert!(Username::new("alice+podcasts@example.com").is_ok());
+        assert!(Username::new("alice%2F").is_err());
+        assert!(Username::new("..").is_err());
+        assert!(serde_json::from_str::<Username>(r#""a/b""#).is_err());
+    }
+
+    #[test]
+    fn deserialize_device_ids_of_other_servers() {
+        let device_id: DeviceId = serde_json::from_str(r#""my phone/1""#).unwrap();
+        assert_eq!("my%20phone%2F1", device_id.path_segment());
+        assert!(serde_json::from_str::<DeviceId>(r#""..""#).is_err());
+        assert!(serde_json::from_str::<DeviceId>(r#""""#).is_err());
+    }
+
+    #[test]
+    fn percent_encode_path_segments() {
+        assert_eq!(
+            "m%C3%B6%C3%B6p",
+            DeviceId::new("mööp").unwrap().path_segment()
+        );
+        assert_eq!("a%2Fb%3Fc", Username::unchecked("a/b?c").path_segment());
+    }
+}
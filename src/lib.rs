@@ -15,7 +15,7 @@
     nonstandard_style,
     rust_2018_compatibility,
     rust_2018_idioms,
-    rustdoc,
+    rustdoc::all,
     unused,
     macro_use_extern_crate,
     missing_docs,
@@ -29,14 +29,36 @@
     unused_lifetimes,
     unused_qualifications
 )]
-#![allow(missing_doc_code_examples)]
 
+pub mod account;
+pub mod analytics;
+pub mod backup;
+pub mod cassette;
 pub mod client;
+pub mod credentials;
 pub mod device;
 pub mod directory;
 pub mod episode;
 pub mod error;
 pub mod favorite;
+pub mod history;
+pub mod id;
+pub mod manager;
+pub mod migration;
+pub mod nextcloud;
+pub mod normalize;
+pub mod outbox;
+pub mod profile;
+pub mod resolver;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod settings;
+pub mod snapshot;
 pub mod subscription;
 pub mod suggestion;
+#[cfg(test)]
+pub(crate) mod test_util;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "tracing")]
+mod trace;
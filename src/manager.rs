@@ -0,0 +1,310 @@
+//! Management of many accounts
+//!
+//! An [AccountManager] holds [AuthenticatedClient]s keyed by name, which share one connection pool. Each account can be limited to a number of concurrent requests. Accounts are usually loaded from a TOML configuration file:
+//!
+//! ```toml
+//! # default for accounts without limit
+//! max_concurrent_requests = 4
+//!
+//! [accounts.alice]
+//! username = "alice"
+//! credentials = { env = "ALICE_PASSWORD" }
+//! device = "sync-server"
+//!
+//! [accounts.bob]
+//! server = "https://gpodder.example.com"
+//! username = "bob"
+//! credentials = { file = "/run/secrets/bob" }
+//! max_concurrent_requests = 1
+//! ```
+
+use crate::client::{AuthenticatedClient, DeviceClient, DEFAULT_SERVER};
+use crate::credentials::{
+    CredentialProvider, EnvCredentials, FileCredentials, NetrcCredentials, Secret,
+};
+use crate::error::Error;
+use crate::id::{DeviceId, Username};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+/// Where the password of an account comes from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialSource {
+    /// password in the configuration file
+    Password(Secret),
+    /// environment variable with the given name, see [EnvCredentials]
+    Env(String),
+    /// first line of the file at the given path, see [FileCredentials]
+    File(PathBuf),
+    /// netrc file at the given path, see [NetrcCredentials]
+    Netrc(PathBuf),
+}
+
+/// Configuration of an account
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccountConfig {
+    /// URL of a gpodder.net compatible server, defaults to gpodder.net
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// username
+    pub username: Username,
+    /// source of the password
+    pub credentials: CredentialSource,
+    /// default device, see [AccountManager::device]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<DeviceId>,
+    /// maximum number of concurrent requests, overrides [ManagerConfig::max_concurrent_requests]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_requests: Option<usize>,
+}
+
+/// Configuration of an [AccountManager], see [module documentation](self)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManagerConfig {
+    /// maximum number of concurrent requests per account; unlimited if `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_requests: Option<usize>,
+    /// accounts by name
+    #[serde(default)]
+    pub accounts: BTreeMap<String, AccountConfig>,
+}
+
+impl ManagerConfig {
+    /// Read configuration from the TOML file at `path`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ManagerConfig, Error> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+impl CredentialProvider for CredentialSource {
+    fn password(&self, server: &str, username: &str) -> Result<Secret, Error> {
+        match self {
+            CredentialSource::Password(password) => Ok(password.clone()),
+            CredentialSource::Env(variable) => {
+                EnvCredentials::new(variable).password(server, username)
+            }
+            CredentialSource::File(path) => FileCredentials::new(path).password(server, username),
+            CredentialSource::Netrc(path) => {
+                NetrcCredentials::with_path(path).password(server, username)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ManagedAccount {
+    client: AuthenticatedClient,
+    device: Option<DeviceId>,
+}
+
+/// Accounts sharing one connection pool, see [module documentation](self)
+///
+/// # Examples
+///
+/// ```no_run
+/// use mygpoclient::device::ListDevices;
+/// use mygpoclient::manager::AccountManager;
+///
+/// let manager = AccountManager::load("accounts.toml")?;
+///
+/// for (name, client) in manager.accounts() {
+///     println!("{}: {} devices", name, client.list_devices()?.len());
+/// }
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct AccountManager {
+    transport: Client,
+    accounts: BTreeMap<String, ManagedAccount>,
+}
+
+impl AccountManager {
+    /// Create manager without accounts
+    pub fn new() -> AccountManager {
+        Default::default()
+    }
+
+    /// Create manager with the accounts of the given configuration
+    ///
+    /// Passwords are read from their [sources](CredentialSource) when they are needed. Fails with [InvalidConfig](Error::InvalidConfig) if a limit of concurrent requests is zero.
+    pub fn from_config(config: &ManagerConfig) -> Result<AccountManager, Error> {
+        let mut manager = AccountManager::new();
+        for (name, account) in &config.accounts {
+            let mut client = AuthenticatedClient::with_username(
+                account.server.as_deref().unwrap_or(DEFAULT_SERVER),
+                account.username.clone(),
+                account.credentials.clone(),
+            );
+            if let Some(limit) = account
+                .max_concurrent_requests
+                .or(config.max_concurrent_requests)
+            {
+                let limit = NonZeroUsize::new(limit).ok_or_else(|| {
+                    Error::InvalidConfig(format!(
+                        "max_concurrent_requests of account {} is 0",
+                        name
+                    ))
+                })?;
+                client = client.with_concurrency_limit(limit);
+            }
+            manager.add(name, client);
+            if let Some(device) = &account.device {
+                manager.set_device(name, device.clone());
+            }
+        }
+        Ok(manager)
+    }
+
+    /// Create manager with the accounts of the TOML configuration file at `path`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<AccountManager, Error> {
+        AccountManager::from_config(&ManagerConfig::load(path)?)
+    }
+
+    /// Add or replace account `name`; the client is switched to the shared connection pool
+    pub fn add(&mut self, name: &str, client: AuthenticatedClient) -> &AuthenticatedClient {
+        let client = client.with_http_client(self.transport.clone());
+        let account = ManagedAccount {
+            client,
+            device: None,
+        };
+        self.accounts.insert(name.to_owned(), account);
+        &self.accounts[name].client
+    }
+
+    /// Remove account `name`
+    pub fn remove(&mut self, name: &str) -> Option<AuthenticatedClient> {
+        self.accounts.remove(name).map(|account| account.client)
+    }
+
+    /// Set default device of account `name`; returns `false` if there is no such account
+    pub fn set_device(&mut self, name: &str, device_id: DeviceId) -> bool {
+        match self.accounts.get_mut(name) {
+            Some(account) => {
+                account.device = Some(device_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Client of the account with the given name or, if there is none, username
+    pub fn account(&self, key: &str) -> Option<&AuthenticatedClient> {
+        self.managed_account(key).map(|account| &account.client)
+    }
+
+    /// Client for the default device of the account with the given name or username
+    pub fn device(&self, key: &str) -> Option<DeviceClient> {
+        let account = self.managed_account(key)?;
+        account
+            .device
+            .clone()
+            .map(|device_id| account.client.device_client(device_id))
+    }
+
+    /// All accounts ordered by name
+    pub fn accounts(&self) -> impl Iterator<Item = (&str, &AuthenticatedClient)> {
+        self.accounts
+            .iter()
+            .map(|(name, account)| (name.as_str(), &account.client))
+    }
+
+    fn managed_account(&self, key: &str) -> Option<&ManagedAccount> {
+        self.accounts.get(key).or_else(|| {
+            self.accounts
+                .values()
+                .find(|account| account.client.username == key)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountManager, CredentialSource, ManagerConfig};
+    use crate::credentials::{CredentialProvider, Secret};
+    use crate::error::Error;
+
+    const CONFIG: &str = r#"
+        max_concurrent_requests = 4
+
+        [accounts.alice]
+        username = "alice"
+        credentials = { password = "secret" }
+        device = "phone"
+
+        [accounts.work]
+        server = "https://gpodder.example.com/"
+        username = "bob"
+        credentials = { env = "MYGPOCLIENT_TEST_UNSET_VARIABLE" }
+        max_concurrent_requests = 1
+    "#;
+
+    #[test]
+    fn parse_config() {
+        let config: ManagerConfig = toml::from_str(CONFIG).unwrap();
+
+        assert_eq!(Some(4), config.max_concurrent_requests);
+        assert_eq!(
+            CredentialSource::Password(Secret::from("secret")),
+            config.accounts["alice"].credentials
+        );
+        assert_eq!(Some(1), config.accounts["work"].max_concurrent_requests);
+        assert!(config.accounts["work"]
+            .credentials
+            .password("https://gpodder.example.com", "bob")
+            .is_err());
+    }
+
+    #[test]
+    fn lookup_by_name_or_username() {
+        let config: ManagerConfig = toml::from_str(CONFIG).unwrap();
+        let manager = AccountManager::from_config(&config).unwrap();
+
+        assert_eq!("bob", manager.account("work").unwrap().username.as_str());
+        assert_eq!("bob", manager.account("bob").unwrap().username.as_str());
+        assert_eq!(
+            "https://gpodder.example.com",
+            manager.account("bob").unwrap().server()
+        );
+        assert_eq!(
+            "phone",
+            manager.device("alice").unwrap().device_id().as_str()
+        );
+        assert!(manager.device("bob").is_none());
+        assert_eq!(
+            Some(4),
+            manager
+                .account("alice")
+                .unwrap()
+                .public_client
+                .concurrency_limit()
+        );
+        assert_eq!(
+            Some(1),
+            manager
+                .account("work")
+                .unwrap()
+                .public_client
+                .concurrency_limit()
+        );
+        assert_eq!(
+            vec!["alice", "work"],
+            manager.accounts().map(|(name, _)| name).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn reject_zero_concurrent_requests() {
+        let mut config: ManagerConfig = toml::from_str(CONFIG).unwrap();
+        config.max_concurrent_requests = Some(0);
+
+        assert!(matches!(
+            AccountManager::from_config(&config),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,496 @@
+//! Migration of an account to another account or gpodder.net compatible server
+//!
+//! The migration copies devices with caption and type, the subscriptions of each device, the complete episode action history, account, device and podcast settings and the settings of episodes, which include favorites. Episode settings are copied for the episodes in the episode action history and the favorite episodes, settings of other episodes are left behind. Settings are skipped if either server doesn't support them, like oPodSync and micro-gpodder. Its progress is recorded in a [MigrationCheckpoint], so a failed migration can be resumed without uploading everything again.
+
+use crate::client::AuthenticatedClient;
+use crate::device::{ListDevices, UpdateDeviceData};
+use crate::episode::{
+    ChunkedUpload, EpisodeActionQuery, GetEpisodeActions, UploadEpisodeActionsInChunks,
+};
+use crate::error::Error;
+use crate::favorite::GetFavoriteEpisodes;
+use crate::outbox::write_atomically;
+use crate::profile::Endpoint;
+use crate::settings::{
+    GetAccountSettings, GetDeviceSettings, GetEpisodeSettings, GetPodcastSettings,
+    SaveAccountSettings, SaveDeviceSettings, SaveEpisodeSettings, SavePodcastSettings,
+};
+use crate::subscription::SubscriptionsOfDevice;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use url::Url;
+
+/// Progress of a [Migration]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationCheckpoint {
+    /// IDs of devices which have been created with their subscriptions and settings
+    pub devices: BTreeSet<String>,
+    /// timestamp of the source history up to which all episode actions have been uploaded
+    pub episode_actions_since: u64,
+    /// number of episode actions after [episode_actions_since](MigrationCheckpoint::episode_actions_since) which have been uploaded
+    pub episode_actions: usize,
+    /// whether account settings have been copied
+    pub account_settings: bool,
+    /// podcasts whose settings have been copied
+    pub podcast_settings: BTreeSet<Url>,
+    /// episodes whose settings have been copied, as pairs of podcast and episode URL
+    pub episode_settings: BTreeSet<(Url, Url)>,
+}
+
+/// Migration from one account to another
+///
+/// # Examples
+///
+/// ```no_run
+/// use mygpoclient::client::AuthenticatedClient;
+/// use mygpoclient::migration::Migration;
+///
+/// let source = AuthenticatedClient::new("alice", "secret")?;
+/// let target = AuthenticatedClient::with_server("https://gpodder.example.com", "alice", "secret")?;
+///
+/// // run again after a failure to resume
+/// Migration::new(&source, &target).run_with_checkpoint_file("migration.json")?;
+/// #
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Migration<'a> {
+    source: &'a AuthenticatedClient,
+    target: &'a AuthenticatedClient,
+    chunk_size: usize,
+}
+
+impl<'a> Migration<'a> {
+    /// Create migration from `source` to `target`
+    pub fn new(source: &'a AuthenticatedClient, target: &'a AuthenticatedClient) -> Self {
+        Migration {
+            source,
+            target,
+            chunk_size: 500,
+        }
+    }
+
+    /// Maximum number of episode actions per upload request, defaults to 500
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Run migration, skipping the steps already recorded in `checkpoint`.
+    ///
+    /// `checkpoint` is updated after every step, so it describes the progress even if an error is returned.
+    /// Settings are only copied if both servers support [Settings](Endpoint::Settings).
+    pub fn run(&self, checkpoint: &mut MigrationCheckpoint) -> Result<(), Error> {
+        self.run_with(checkpoint, &mut |_| Ok(()))
+    }
+
+    /// Run migration and store the checkpoint as JSON file at `path` after every device, chunk of episode actions and batch of settings.
+    ///
+    /// If the file exists, the migration is resumed from the stored checkpoint.
+    pub fn run_with_checkpoint_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let mut checkpoint = match File::open(path) {
+            Ok(file) => serde_json::from_reader(file)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Default::default(),
+            Err(error) => return Err(error.into()),
+        };
+        self.run_with(&mut checkpoint, &mut |checkpoint| {
+            write_atomically(path, &serde_json::to_vec(checkpoint)?)
+        })
+    }
+
+    fn run_with(
+        &self,
+        checkpoint: &mut MigrationCheckpoint,
+        save: &mut dyn FnMut(&MigrationCheckpoint) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let settings_supported = self.source.profile().supports(Endpoint::Settings)
+            && self.target.profile().supports(Endpoint::Settings);
+
+        let mut podcasts = BTreeSet::new();
+        for device in self.source.list_devices()? {
+            let source_device = self.source.device_client(device.id.clone());
+            let subscriptions = source_device.get_subscriptions_of_device()?;
+
+            if !checkpoint.devices.contains(device.id.as_str()) {
+                let target_device = self.target.device_client(device.id.clone());
+                target_device.update_device_data(device.caption.clone(), device.device_type)?;
+                target_device.upload_subscriptions_of_device(&subscriptions)?;
+                if settings_supported {
+                    let settings = source_device.get_device_settings()?;
+                    if !settings.is_empty() {
+                        target_device.save_device_settings(settings, Vec::new())?;
+                    }
+                }
+                checkpoint.devices.insert(device.id.to_string());
+                save(checkpoint)?;
+            }
+
+            podcasts.extend(subscriptions);
+        }
+
+        // actions are appended to the history, so the actions since the checkpoint keep their positions between runs
+        let response = self.source.get_episode_actions(
+            &EpisodeActionQuery::new().since(checkpoint.episode_actions_since),
+        )?;
+        let actions = response.actions;
+        let options = ChunkedUpload::new().chunk_size(self.chunk_size);
+        while checkpoint.episode_actions < actions.len() {
+            let end = (checkpoint.episode_actions + self.chunk_size).min(actions.len());
+            let mut report = self.target.upload_episode_actions_in_chunks(
+                &actions[checkpoint.episode_actions..end],
+                &options,
+            );
+            if !report.errors.is_empty() {
+                return Err(report.errors.remove(0));
+            }
+            checkpoint.episode_actions = end;
+            save(checkpoint)?;
+        }
+        if checkpoint.episode_actions_since != response.timestamp {
+            checkpoint.episode_actions_since = response.timestamp;
+            checkpoint.episode_actions = 0;
+            save(checkpoint)?;
+        }
+
+        if !settings_supported {
+            return Ok(());
+        }
+
+        if !checkpoint.account_settings {
+            let settings = self.source.get_account_settings()?;
+            if !settings.is_empty() {
+                self.target.save_account_settings(settings, Vec::new())?;
+            }
+            checkpoint.account_settings = true;
+            save(checkpoint)?;
+        }
+
+        // the checkpoint is saved once per batch of settings, also if copying fails halfway
+        let copied = self.copy_podcast_settings(podcasts, checkpoint);
+        save(checkpoint)?;
+        copied?;
+
+        let copied = self.copy_episode_settings(checkpoint);
+        save(checkpoint)?;
+        copied
+    }
+
+    fn copy_podcast_settings(
+        &self,
+        podcasts: BTreeSet<Url>,
+        checkpoint: &mut MigrationCheckpoint,
+    ) -> Result<(), Error> {
+        for podcast in podcasts {
+            if !checkpoint.podcast_settings.contains(&podcast) {
+                let settings = self.source.get_podcast_settings(podcast.clone())?;
+                if !settings.is_empty() {
+                    self.target
+                        .save_podcast_settings(settings, Vec::new(), podcast.clone())?;
+                }
+                checkpoint.podcast_settings.insert(podcast);
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_episode_settings(&self, checkpoint: &mut MigrationCheckpoint) -> Result<(), Error> {
+        let mut episodes: BTreeSet<(Url, Url)> = self
+            .source
+            .get_episode_actions(&EpisodeActionQuery::new().aggregated(true))?
+            .actions
+            .into_iter()
+            .map(|action| (action.podcast, action.episode))
+            .collect();
+        if self.source.profile().supports(Endpoint::Favorites) {
+            episodes.extend(
+                self.source
+                    .get_favorite_episodes()?
+                    .into_iter()
+                    .map(|episode| (episode.podcast_url, episode.url)),
+            );
+        }
+        for (podcast, episode) in episodes {
+            if !checkpoint
+                .episode_settings
+                .contains(&(podcast.clone(), episode.clone()))
+            {
+                let settings = self
+                    .source
+                    .get_episode_settings(podcast.clone(), episode.clone())?;
+                if !settings.is_empty() {
+                    self.target.save_episode_settings(
+                        settings,
+                        Vec::new(),
+                        podcast.clone(),
+                        episode.clone(),
+                    )?;
+                }
+                checkpoint.episode_settings.insert((podcast, episode));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Migration, MigrationCheckpoint};
+    use crate::cassette::{Cassette, Interaction};
+    use crate::client::AuthenticatedClient;
+    use crate::profile::ServerProfile;
+    use crate::test_util::{interaction, login, url};
+    use std::fs;
+
+    const SOURCE: &str = "https://source.example.com";
+    const TARGET: &str = "https://target.example.com";
+    const FEED: &str = "http://example.com/feed.rss";
+    const DOWNLOAD_1: &str = r#"{"podcast": "http://example.com/feed.rss", "episode": "http://example.com/1.mp3", "action": "download"}"#;
+    const DOWNLOAD_2: &str = r#"{"podcast": "http://example.com/feed.rss", "episode": "http://example.com/2.mp3", "action": "download"}"#;
+
+    fn source_devices() -> Vec<Interaction> {
+        vec![
+            interaction(
+                "GET",
+                &format!("{}/api/2/devices/alice.json", SOURCE),
+                None,
+                r#"[{"id": "phone", "caption": "Phone", "type": "mobile", "subscriptions": 1}]"#,
+            ),
+            interaction(
+                "GET",
+                &format!("{}/subscriptions/alice/phone.json", SOURCE),
+                None,
+                &format!(r#"["{}"]"#, FEED),
+            ),
+        ]
+    }
+
+    fn client(server: &str, interactions: Vec<Interaction>) -> (AuthenticatedClient, Cassette) {
+        let cassette = Cassette::from_interactions(interactions);
+        let client = AuthenticatedClient::with_server(server, "alice", "secret")
+            .unwrap()
+            .with_cassette(cassette.clone());
+        (client, cassette)
+    }
+
+    #[test]
+    fn resume_from_checkpoint_file() {
+        let path =
+            std::env::temp_dir().join(format!("mygpoclient-migration-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut interactions = source_devices();
+        interactions.extend(vec![
+            interaction(
+                "GET",
+                &format!("{}/api/2/settings/alice/device.json?device=phone", SOURCE),
+                None,
+                "{}",
+            ),
+            interaction(
+                "GET",
+                &format!(
+                    "{}/api/2/episodes/alice.json?aggregated=false&since=0",
+                    SOURCE
+                ),
+                None,
+                &format!(r#"{{"actions": [{}], "timestamp": 10}}"#, DOWNLOAD_1),
+            ),
+            interaction(
+                "GET",
+                &format!("{}/api/2/settings/alice/account.json", SOURCE),
+                None,
+                r#"{"public": "false"}"#,
+            ),
+        ]);
+        let (source, _) = client(SOURCE, interactions);
+        let (target, target_cassette) = client(
+            TARGET,
+            vec![
+                interaction(
+                    "POST",
+                    &format!("{}/api/2/devices/alice/phone.json", TARGET),
+                    Some(r#"{"caption": "Phone", "type": "mobile"}"#),
+                    "",
+                ),
+                interaction(
+                    "PUT",
+                    &format!("{}/subscriptions/alice/phone.json", TARGET),
+                    Some(&format!(r#"["{}"]"#, FEED)),
+                    "",
+                ),
+                interaction(
+                    "POST",
+                    &format!("{}/api/2/episodes/alice.json", TARGET),
+                    Some(&format!("[{}]", DOWNLOAD_1)),
+                    r#"{"timestamp": 20, "update_urls": []}"#,
+                ),
+            ],
+        );
+
+        // saving account settings isn't recorded, so the first run fails
+        assert!(Migration::new(&source, &target)
+            .run_with_checkpoint_file(&path)
+            .is_err());
+        assert!(target_cassette.interactions().is_empty());
+        let checkpoint: MigrationCheckpoint =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(10, checkpoint.episode_actions_since);
+        assert!(checkpoint.devices.contains("phone"));
+        assert!(!checkpoint.account_settings);
+
+        // another action was added to the source history in the meantime
+        let mut interactions = source_devices();
+        interactions.extend(vec![
+            interaction(
+                "GET",
+                &format!("{}/api/2/episodes/alice.json?aggregated=false&since=10", SOURCE),
+                None,
+                &format!(r#"{{"actions": [{}], "timestamp": 11}}"#, DOWNLOAD_2),
+            ),
+            interaction(
+                "GET",
+                &format!("{}/api/2/settings/alice/account.json", SOURCE),
+                None,
+                r#"{"public": "false"}"#,
+            ),
+            interaction(
+                "GET",
+                &format!(
+                    "{}/api/2/settings/alice/podcast.json?podcast=http%3A%2F%2Fexample.com%2Ffeed.rss",
+                    SOURCE
+                ),
+                None,
+                "{}",
+            ),
+            interaction(
+                "GET",
+                &format!("{}/api/2/episodes/alice.json?aggregated=true", SOURCE),
+                None,
+                &format!(
+                    r#"{{"actions": [{}, {}], "timestamp": 11}}"#,
+                    DOWNLOAD_1, DOWNLOAD_2
+                ),
+            ),
+            interaction(
+                "GET",
+                &format!("{}/api/2/favorites/alice.json", SOURCE),
+                None,
+                "[]",
+            ),
+            interaction(
+                "GET",
+                &format!(
+                    "{}/api/2/settings/alice/episode.json?podcast=http%3A%2F%2Fexample.com%2Ffeed.rss&episode=http%3A%2F%2Fexample.com%2F1.mp3",
+                    SOURCE
+                ),
+                None,
+                r#"{"is_favorite": "true"}"#,
+            ),
+            interaction(
+                "GET",
+                &format!(
+                    "{}/api/2/settings/alice/episode.json?podcast=http%3A%2F%2Fexample.com%2Ffeed.rss&episode=http%3A%2F%2Fexample.com%2F2.mp3",
+                    SOURCE
+                ),
+                None,
+                "{}",
+            ),
+        ]);
+        let (source, source_cassette) = client(SOURCE, interactions);
+        let (target, target_cassette) = client(
+            TARGET,
+            vec![
+                interaction(
+                    "POST",
+                    &format!("{}/api/2/episodes/alice.json", TARGET),
+                    Some(&format!("[{}]", DOWNLOAD_2)),
+                    r#"{"timestamp": 21, "update_urls": []}"#,
+                ),
+                interaction(
+                    "POST",
+                    &format!("{}/api/2/settings/alice/account.json", TARGET),
+                    Some(r#"{"set": {"public": "false"}, "remove": []}"#),
+                    r#"{"public": "false"}"#,
+                ),
+                interaction(
+                    "POST",
+                    &format!(
+                        "{}/api/2/settings/alice/episode.json?podcast=http%3A%2F%2Fexample.com%2Ffeed.rss&episode=http%3A%2F%2Fexample.com%2F1.mp3",
+                        TARGET
+                    ),
+                    Some(r#"{"set": {"is_favorite": "true"}, "remove": []}"#),
+                    r#"{"is_favorite": "true"}"#,
+                ),
+            ],
+        );
+
+        Migration::new(&source, &target)
+            .run_with_checkpoint_file(&path)
+            .unwrap();
+        assert!(source_cassette.interactions().is_empty());
+        assert!(target_cassette.interactions().is_empty());
+        let checkpoint: MigrationCheckpoint =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(11, checkpoint.episode_actions_since);
+        assert_eq!(0, checkpoint.episode_actions);
+        assert!(checkpoint.account_settings);
+        assert!(checkpoint.podcast_settings.contains(&url(FEED)));
+        assert_eq!(2, checkpoint.episode_settings.len());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn skip_settings_unsupported_by_target() {
+        let mut interactions = source_devices();
+        interactions.push(interaction(
+            "GET",
+            &format!(
+                "{}/api/2/episodes/alice.json?aggregated=false&since=0",
+                SOURCE
+            ),
+            None,
+            &format!(r#"{{"actions": [{}], "timestamp": 10}}"#, DOWNLOAD_1),
+        ));
+        let (source, source_cassette) = client(SOURCE, interactions);
+        let (target, target_cassette) = client(
+            TARGET,
+            vec![
+                login(&format!("{}/api/2/auth/alice/login.json", TARGET), "abc"),
+                interaction(
+                    "POST",
+                    &format!("{}/api/2/devices/alice/phone.json", TARGET),
+                    Some(r#"{"caption": "Phone", "type": "mobile"}"#),
+                    "",
+                ),
+                interaction(
+                    "PUT",
+                    &format!("{}/subscriptions/alice/phone.json", TARGET),
+                    Some(&format!(r#"["{}"]"#, FEED)),
+                    "",
+                ),
+                interaction(
+                    "POST",
+                    &format!("{}/api/2/episodes/alice.json", TARGET),
+                    Some(&format!("[{}]", DOWNLOAD_1)),
+                    r#"{"timestamp": 20, "update_urls": []}"#,
+                ),
+            ],
+        );
+        let target = target.with_profile(ServerProfile::opodsync());
+        let mut checkpoint = MigrationCheckpoint::default();
+
+        Migration::new(&source, &target)
+            .run(&mut checkpoint)
+            .unwrap();
+
+        assert!(source_cassette.interactions().is_empty());
+        assert!(target_cassette.interactions().is_empty());
+        assert!(checkpoint.devices.contains("phone"));
+        assert_eq!(10, checkpoint.episode_actions_since);
+        assert!(!checkpoint.account_settings);
+    }
+}
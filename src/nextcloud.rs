@@ -0,0 +1,304 @@
+//! Client for the [Nextcloud gpoddersync app](https://github.com/thrillfall/nextcloud-gpodder)
+//!
+//! The app provides a subset of the gpodder.net API below `/index.php/apps/gpoddersync`. Subscriptions aren't device-specific and episode actions are stored without device. [NextcloudClient] implements [SubscriptionChanges], [UploadEpisodeActions] and [GetEpisodeActions], so code based on these traits works with both services.
+//!
+//! Use a Nextcloud app password instead of the account password.
+
+use crate::cassette::Cassette;
+use crate::client::AuthenticatedClient;
+use crate::credentials::Secret;
+use crate::episode::{
+    EpisodeAction, EpisodeActionQuery, GetEpisodeActions, GetEpisodeActionsResponse,
+    UploadEpisodeActions, UploadEpisodeActionsResponse,
+};
+use crate::error::Error;
+use crate::id::Username;
+use crate::profile::Endpoint;
+use crate::subscription::{
+    GetSubscriptionChangesResponse, SubscriptionChanges, UploadSubscriptionChangesRequest,
+    UploadSubscriptionChangesResponse,
+};
+use chrono::{Timelike, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use url::Url;
+
+const API_PATH: &str = "index.php/apps/gpoddersync";
+
+/// Client for a Nextcloud server with gpoddersync app
+///
+/// # Examples
+///
+/// ```no_run
+/// use mygpoclient::nextcloud::NextcloudClient;
+/// use mygpoclient::subscription::SubscriptionChanges;
+///
+/// let client = NextcloudClient::new("https://cloud.example.com", "alice", "app-password");
+///
+/// let changes = client.get_subscription_changes(0)?;
+/// #
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct NextcloudClient {
+    pub(crate) authenticated_client: AuthenticatedClient,
+}
+
+/// Upload responses of gpoddersync only contain the timestamp
+#[derive(Deserialize)]
+struct TimestampResponse {
+    timestamp: u64,
+}
+
+impl NextcloudClient {
+    /// Create client for the Nextcloud server at `server`, e.g. `https://cloud.example.com`, authenticated with an app password
+    pub fn new(server: &str, username: &str, app_password: &str) -> NextcloudClient {
+        NextcloudClient {
+            authenticated_client: AuthenticatedClient::with_username(
+                server,
+                Username::unchecked(username),
+                Secret::from(app_password),
+            ),
+        }
+    }
+
+    /// Record requests into or replay responses from the given [Cassette]
+    pub fn with_cassette(mut self, cassette: Cassette) -> NextcloudClient {
+        self.authenticated_client = self.authenticated_client.with_cassette(cassette);
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            self.authenticated_client.server(),
+            API_PATH,
+            path
+        )
+    }
+}
+
+impl SubscriptionChanges for NextcloudClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(add = add.len(), remove = remove.len())))]
+    fn upload_subscription_changes(
+        &self,
+        add: &[Url],
+        remove: &[Url],
+    ) -> Result<UploadSubscriptionChangesResponse, Error> {
+        let input = UploadSubscriptionChangesRequest {
+            add: add.to_owned(),
+            remove: remove.to_owned(),
+        };
+        let response: TimestampResponse = self
+            .authenticated_client
+            .post(
+                Endpoint::SubscriptionChanges,
+                &self.url("subscription_change/create"),
+                &input,
+            )?
+            .json()?;
+        Ok(UploadSubscriptionChangesResponse {
+            timestamp: response.timestamp,
+            update_urls: Vec::new(),
+        })
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(since = timestamp)))]
+    fn get_subscription_changes(
+        &self,
+        timestamp: u64,
+    ) -> Result<GetSubscriptionChangesResponse, Error> {
+        Ok(self
+            .authenticated_client
+            .get_with_query(
+                Endpoint::SubscriptionChanges,
+                &self.url("subscriptions"),
+                &[&("since", timestamp)],
+            )?
+            .json()?)
+    }
+}
+
+impl UploadEpisodeActions for NextcloudClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(actions = actions.len())))]
+    fn upload_episode_actions(
+        &self,
+        actions: &[EpisodeAction],
+    ) -> Result<UploadEpisodeActionsResponse, Error> {
+        // gpoddersync requires timestamps with second precision
+        let now = Utc::now().naive_utc();
+        let actions: Vec<EpisodeAction> = actions
+            .iter()
+            .map(|action| {
+                let mut action = action.clone();
+                action.timestamp = action.timestamp.unwrap_or(now).with_nanosecond(0);
+                action
+            })
+            .collect();
+        let response: TimestampResponse = self
+            .authenticated_client
+            .post(
+                Endpoint::EpisodeActions,
+                &self.url("episode_action/create"),
+                &actions,
+            )?
+            .error_for_status()?
+            .json()?;
+        Ok(UploadEpisodeActionsResponse {
+            timestamp: response.timestamp,
+            update_urls: Vec::new(),
+        })
+    }
+}
+
+impl GetEpisodeActions for NextcloudClient {
+    /// Only `since` is supported by gpoddersync, the other parameters of the query are applied locally. Episode actions of gpoddersync have no device, so filtering by device returns no actions.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    fn get_episode_actions(
+        &self,
+        query: &EpisodeActionQuery,
+    ) -> Result<GetEpisodeActionsResponse, Error> {
+        let mut response: GetEpisodeActionsResponse = self
+            .authenticated_client
+            .get_with_query(
+                Endpoint::EpisodeActions,
+                &self.url("episode_action"),
+                &[&("since", query.since.unwrap_or_default())],
+            )?
+            .json()?;
+
+        response.actions.retain(|action| {
+            query
+                .podcast
+                .as_ref()
+                .is_none_or(|podcast| &action.podcast == podcast)
+                && query
+                    .device
+                    .as_ref()
+                    .is_none_or(|device| action.device.as_ref() == Some(device))
+        });
+
+        if query.aggregated {
+            // indices of the latest action of each episode, looked up like in compact_episode_actions
+            let mut kept: Vec<usize> = Vec::new();
+            let mut by_url: HashMap<(&Url, &Url), usize> = HashMap::new();
+            let mut by_guid: HashMap<(&Url, &str), usize> = HashMap::new();
+            for (index, action) in response.actions.iter().enumerate() {
+                let same = by_url
+                    .get(&(&action.podcast, &action.episode))
+                    .into_iter()
+                    .chain(
+                        action
+                            .guid
+                            .as_deref()
+                            .and_then(|guid| by_guid.get(&(&action.podcast, guid))),
+                    )
+                    .copied()
+                    .find(|&episode| response.actions[kept[episode]].is_same_episode(action));
+                let episode = match same {
+                    Some(episode) => {
+                        if response.actions[kept[episode]].timestamp <= action.timestamp {
+                            kept[episode] = index;
+                        }
+                        episode
+                    }
+                    None => {
+                        kept.push(index);
+                        kept.len() - 1
+                    }
+                };
+                by_url.insert((&action.podcast, &action.episode), episode);
+                if let Some(guid) = action.guid.as_deref() {
+                    by_guid.insert((&action.podcast, guid), episode);
+                }
+            }
+            kept.sort_unstable();
+            response.actions = kept
+                .into_iter()
+                .map(|index| response.actions[index].clone())
+                .collect();
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NextcloudClient;
+    use crate::cassette::{Cassette, Interaction};
+    use crate::episode::{EpisodeActionQuery, EpisodeActionType, GetEpisodeActions};
+    use crate::subscription::SubscriptionChanges;
+    use crate::test_util::{interaction, url};
+
+    fn client(interactions: Vec<Interaction>) -> NextcloudClient {
+        NextcloudClient::new("https://cloud.example.com/", "alice", "app-password")
+            .with_cassette(Cassette::from_interactions(interactions))
+    }
+
+    #[test]
+    fn get_subscription_changes() {
+        let client = client(vec![interaction(
+            "GET",
+            "https://cloud.example.com/index.php/apps/gpoddersync/subscriptions?since=0",
+            None,
+            r#"{"add": ["http://example.com/feed.rss"], "remove": [], "timestamp": 1629640000}"#,
+        )]);
+
+        let changes = client.get_subscription_changes(0).unwrap();
+
+        assert_eq!(1629640000, changes.timestamp);
+        assert_eq!(vec![url("http://example.com/feed.rss")], changes.add);
+    }
+
+    #[test]
+    fn get_aggregated_episode_actions() {
+        let client = client(vec![interaction(
+            "GET",
+            "https://cloud.example.com/index.php/apps/gpoddersync/episode_action?since=0",
+            None,
+            r#"{"actions": [
+                {"podcast": "http://example.com/feed.rss", "episode": "http://example.com/1.mp3", "guid": "1", "action": "DOWNLOAD", "timestamp": "2021-08-22T15:00:00", "started": -1, "position": -1, "total": -1},
+                {"podcast": "http://example.com/feed.rss", "episode": "http://example.com/1.mp3", "guid": "1", "action": "PLAY", "timestamp": "2021-08-22T15:58:13", "started": 0, "position": 120, "total": 500},
+                {"podcast": "http://example.org/feed.rss", "episode": "http://example.org/2.mp3", "action": "NEW", "timestamp": "2021-08-22T16:00:00"}
+            ], "timestamp": 1629640000}"#,
+        )]);
+        let query = EpisodeActionQuery::new()
+            .podcast(url("http://example.com/feed.rss"))
+            .aggregated(true);
+
+        let response = client.get_episode_actions(&query).unwrap();
+
+        assert_eq!(1, response.actions.len());
+        assert_eq!(Some("1"), response.actions[0].guid.as_deref());
+        assert_eq!(
+            EpisodeActionType::Play {
+                position: 120,
+                started: Some(0),
+                total: Some(500),
+            },
+            response.actions[0].action
+        );
+    }
+
+    #[test]
+    fn aggregate_episode_actions_by_guid() {
+        let client = client(vec![interaction(
+            "GET",
+            "https://cloud.example.com/index.php/apps/gpoddersync/episode_action?since=0",
+            None,
+            r#"{"actions": [
+                {"podcast": "http://example.com/feed.rss", "episode": "http://example.com/1.mp3", "guid": "1", "action": "DOWNLOAD", "timestamp": "2021-08-22T15:00:00"},
+                {"podcast": "http://example.com/feed.rss", "episode": "http://cdn.example.com/1.mp3", "guid": "1", "action": "DELETE", "timestamp": "2021-08-22T15:30:00"},
+                {"podcast": "http://example.com/feed.rss", "episode": "http://example.com/1.mp3", "guid": "2", "action": "NEW", "timestamp": "2021-08-22T16:00:00"}
+            ], "timestamp": 1629640000}"#,
+        )]);
+        let query = EpisodeActionQuery::new().aggregated(true);
+
+        let response = client.get_episode_actions(&query).unwrap();
+
+        assert_eq!(2, response.actions.len());
+        assert_eq!(EpisodeActionType::Delete, response.actions[0].action);
+        assert_eq!(EpisodeActionType::New, response.actions[1].action);
+    }
+}
@@ -0,0 +1,251 @@
+//! Normalization of feed URLs and detection of duplicate subscriptions
+//!
+//! gpodder.net sanitizes uploaded URLs and reports the rewrites as `update_urls`. [sanitize_url] applies the same kind of rules locally, so that clients can avoid the rewrites. [find_duplicates] additionally treats URLs as equal which only differ in scheme, trailing slash or a `format=xml` query parameter.
+
+use crate::subscription::Podcast;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use url::Url;
+
+/// URL schemes which podcast applications use as alias for HTTP
+const FEED_SCHEMES: [&str; 4] = ["feed", "itpc", "pcast", "podcast"];
+
+const FEEDBURNER_HOSTS: [&str; 3] = [
+    "feeds.feedburner.com",
+    "feeds2.feedburner.com",
+    "feedproxy.google.com",
+];
+
+/// Podcasts of [get_all_subscriptions](crate::subscription::GetAllSubscriptions::get_all_subscriptions) which are probably the same feed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateCluster {
+    /// proposed URL to keep
+    pub canonical: Url,
+    /// all podcasts of the cluster
+    pub podcasts: Vec<Podcast>,
+}
+
+/// Sanitize a feed URL like gpodder.net does.
+///
+/// - `feed:`, `itpc:`, `pcast:` and `podcast:` URLs are converted to HTTP
+/// - fragments are removed
+/// - `feeds2.feedburner.com` and `feedproxy.google.com` are replaced by `feeds.feedburner.com`, and the `format=xml` query parameter is removed from FeedBurner URLs
+///
+/// Returns `None` if the URL is not allowed by gpodder.net, i.e. it is neither HTTP nor HTTPS.
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::normalize::sanitize_url;
+/// use url::Url;
+///
+/// let url = Url::parse("http://feeds2.feedburner.com/LinuxOutlaws?format=xml").unwrap();
+/// assert_eq!(
+///     Some(Url::parse("http://feeds.feedburner.com/LinuxOutlaws").unwrap()),
+///     sanitize_url(&url)
+/// );
+/// ```
+pub fn sanitize_url(url: &Url) -> Option<Url> {
+    let mut url = if FEED_SCHEMES.contains(&url.scheme()) {
+        // both feed://example.com/rss and feed:http://example.com/rss are in use
+        let rest = &url.as_str()[url.scheme().len() + 1..];
+        if rest.starts_with("http://") || rest.starts_with("https://") {
+            Url::parse(rest).ok()?
+        } else {
+            Url::parse(&format!("http:{}", rest)).ok()?
+        }
+    } else {
+        url.clone()
+    };
+
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return None;
+    }
+
+    url.set_fragment(None);
+
+    if url
+        .host_str()
+        .is_some_and(|host| FEEDBURNER_HOSTS.contains(&host))
+    {
+        url.set_host(Some(FEEDBURNER_HOSTS[0])).ok()?;
+        remove_format_xml(&mut url);
+    }
+
+    Some(url)
+}
+
+/// Sanitize URLs like gpodder.net does on upload, dropping rejected URLs and collecting rewrites as `update_urls`
+#[cfg(feature = "server")]
+pub(crate) fn sanitize_urls(urls: &[Url], update_urls: &mut Vec<(Url, Url)>) -> Vec<Url> {
+    urls.iter()
+        .filter_map(|url| {
+            let sanitized = sanitize_url(url)?;
+            if &sanitized != url && !update_urls.iter().any(|(old, _)| old == url) {
+                update_urls.push((url.clone(), sanitized.clone()));
+            }
+            Some(sanitized)
+        })
+        .collect()
+}
+
+/// Group podcasts whose feed URLs are probably the same feed.
+///
+/// Besides the rules of [sanitize_url], URLs are considered equal if they only differ in scheme (`http` or `https`), a trailing slash or a `format=xml` query parameter. Only clusters with at least two podcasts are returned.
+///
+/// The proposed [canonical](DuplicateCluster::canonical) URL is the sanitized URL of the podcast which uses HTTPS, has the most subscribers and the shortest URL, in this order of precedence.
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::client::AuthenticatedClient;
+/// use mygpoclient::normalize::find_duplicates;
+/// use mygpoclient::subscription::GetAllSubscriptions;
+///
+/// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+/// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+/// #
+/// let client = AuthenticatedClient::new(&username, &password)?;
+///
+/// for cluster in find_duplicates(&client.get_all_subscriptions()?) {
+///     println!("{} duplicates of {}", cluster.podcasts.len(), cluster.canonical);
+/// }
+/// #
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+pub fn find_duplicates(podcasts: &[Podcast]) -> Vec<DuplicateCluster> {
+    let mut clusters: Vec<Vec<&Podcast>> = Vec::new();
+    let mut cluster_by_key: HashMap<String, usize> = HashMap::new();
+
+    for podcast in podcasts {
+        let key = duplicate_key(&podcast.url);
+        let index = *cluster_by_key.entry(key).or_insert_with(|| {
+            clusters.push(Vec::new());
+            clusters.len() - 1
+        });
+        clusters[index].push(podcast);
+    }
+
+    clusters
+        .into_iter()
+        .filter(|cluster| cluster.len() > 1)
+        .map(|cluster| {
+            let best = cluster
+                .iter()
+                .min_by(|a, b| {
+                    (b.url.scheme() == "https")
+                        .cmp(&(a.url.scheme() == "https"))
+                        .then_with(|| b.subscribers.cmp(&a.subscribers))
+                        .then_with(|| a.url.as_str().len().cmp(&b.url.as_str().len()))
+                })
+                .expect("clusters are not empty");
+            DuplicateCluster {
+                canonical: sanitize_url(&best.url).unwrap_or_else(|| best.url.clone()),
+                podcasts: cluster.into_iter().cloned().collect(),
+            }
+        })
+        .collect()
+}
+
+fn duplicate_key(url: &Url) -> String {
+    let mut url = sanitize_url(url).unwrap_or_else(|| url.clone());
+    remove_format_xml(&mut url);
+    let without_scheme = &url.as_str()[url.scheme().len()..];
+    without_scheme.trim_end_matches('/').to_owned()
+}
+
+fn remove_format_xml(url: &mut Url) {
+    let query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, value)| !(key == "format" && value == "xml"))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_duplicates, sanitize_url};
+    use crate::subscription::Podcast;
+    use crate::test_util::url;
+
+    fn podcast(feed: &str, subscribers: u16) -> Podcast {
+        Podcast {
+            url: url(feed),
+            title: String::from("Linux Outlaws"),
+            author: None,
+            description: String::new(),
+            subscribers,
+            subscribers_last_week: subscribers,
+            logo_url: None,
+            scaled_logo_url: None,
+            website: None,
+            mygpo_link: url("http://gpodder.net/podcast/11171"),
+        }
+    }
+
+    #[test]
+    fn sanitize_feed_schemes() {
+        assert_eq!(
+            Some(url("http://example.com/feed.rss")),
+            sanitize_url(&url("feed://example.com/feed.rss"))
+        );
+        assert_eq!(
+            Some(url("https://example.com/feed.rss")),
+            sanitize_url(&url("feed:https://example.com/feed.rss"))
+        );
+        assert_eq!(
+            Some(url("http://example.com/feed.rss")),
+            sanitize_url(&url("itpc://example.com/feed.rss"))
+        );
+        assert_eq!(None, sanitize_url(&url("ftp://example.com/feed.rss")));
+    }
+
+    #[test]
+    fn sanitize_feedburner() {
+        assert_eq!(
+            Some(url("http://feeds.feedburner.com/LinuxOutlaws")),
+            sanitize_url(&url("http://feeds2.feedburner.com/LinuxOutlaws?format=xml"))
+        );
+        assert_eq!(
+            Some(url("http://feeds.feedburner.com/LinuxOutlaws?fmt=rss")),
+            sanitize_url(&url(
+                "http://feedproxy.google.com/LinuxOutlaws?format=xml&fmt=rss#top"
+            ))
+        );
+        assert_eq!(
+            Some(url("http://feeds.wnyc.org/onthemedia?format=xml")),
+            sanitize_url(&url("http://feeds.wnyc.org/onthemedia?format=xml"))
+        );
+    }
+
+    #[test]
+    fn find_duplicate_clusters() {
+        let podcasts = vec![
+            podcast("http://feeds2.feedburner.com/LinuxOutlaws?format=xml", 10),
+            podcast("http://example.com/feed.rss", 1),
+            podcast("https://feeds.feedburner.com/LinuxOutlaws/", 3),
+            podcast("http://feeds.feedburner.com/LinuxOutlaws", 20),
+            podcast("http://feeds.wnyc.org/onthemedia?format=xml", 5),
+            podcast("https://feeds.wnyc.org/onthemedia", 7),
+        ];
+
+        let clusters = find_duplicates(&podcasts);
+
+        assert_eq!(2, clusters.len());
+        assert_eq!(3, clusters[0].podcasts.len());
+        assert_eq!(
+            url("https://feeds.feedburner.com/LinuxOutlaws/"),
+            clusters[0].canonical
+        );
+        assert_eq!(2, clusters[1].podcasts.len());
+        assert_eq!(
+            url("https://feeds.wnyc.org/onthemedia"),
+            clusters[1].canonical
+        );
+    }
+}
@@ -0,0 +1,780 @@
+//! Offline queue for write requests
+//!
+//! [OfflineClient] wraps a [DeviceClient] and implements the write traits. If a write fails because the server can't be reached, it is stored in a durable [Outbox] and replayed in order by [flush](OfflineClient::flush). Redundant operations are coalesced while they are queued.
+
+use crate::client::DeviceClient;
+use crate::device::{DeviceType, UpdateDeviceData};
+use crate::episode::{
+    EpisodeAction, EpisodeActionType, UploadEpisodeActions, UploadEpisodeActionsResponse,
+};
+use crate::error::Error;
+use crate::settings::{
+    SaveAccountSettings, SaveDeviceSettings, SaveEpisodeSettings, SavePodcastSettings,
+    SettingsTarget,
+};
+use crate::subscription::{
+    GetSubscriptionChangesResponse, SubscriptionChanges, UploadSubscriptionChangesResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use url::Url;
+
+/// Queued write request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// [upload_subscription_changes](SubscriptionChanges::upload_subscription_changes)
+    SubscriptionChanges {
+        /// device ID
+        device: String,
+        /// subscriptions to add
+        add: Vec<Url>,
+        /// subscriptions to remove
+        remove: Vec<Url>,
+    },
+    /// [upload_episode_actions](UploadEpisodeActions::upload_episode_actions)
+    EpisodeActions(Vec<EpisodeAction>),
+    /// [update_device_data](UpdateDeviceData::update_device_data)
+    DeviceData {
+        /// device ID
+        device: String,
+        /// new caption, if changed
+        caption: Option<String>,
+        /// new type, if changed
+        device_type: Option<DeviceType>,
+    },
+    /// save settings
+    Settings {
+        /// scope of the settings
+        target: SettingsTarget,
+        /// settings to set
+        set: HashMap<String, String>,
+        /// settings to remove
+        remove: Vec<String>,
+    },
+}
+
+/// Queue of write requests, which coalesces redundant operations
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Outbox {
+    operations: Vec<Operation>,
+}
+
+/// Client which queues write requests while the server can't be reached
+///
+/// Writes are sent immediately if the outbox is empty. If the server can't be reached, the write is queued and a placeholder result is returned: an empty response with timestamp 0 for uploads and the saved values for settings. While the outbox isn't empty, all writes are queued to keep their order. Reads are always sent to the server.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mygpoclient::client::DeviceClient;
+/// use mygpoclient::outbox::OfflineClient;
+/// use mygpoclient::subscription::SubscriptionChanges;
+/// use url::Url;
+///
+/// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+/// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+/// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
+/// #
+/// let client = OfflineClient::open(DeviceClient::new(&username, &password, &deviceid)?, "outbox.json")?;
+///
+/// let feed = Url::parse("http://example.com/feed.rss").unwrap();
+/// client.upload_subscription_changes(&[feed], &[])?;
+///
+/// // later, when the network is back
+/// client.flush()?;
+/// #
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct OfflineClient {
+    client: DeviceClient,
+    path: PathBuf,
+    outbox: Mutex<Outbox>,
+}
+
+impl Outbox {
+    /// Create empty outbox
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Queued operations in the order they are replayed
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Whether no operations are queued
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Remove the first operation, e.g. after the server rejected it
+    pub fn discard_first(&mut self) -> Option<Operation> {
+        if self.operations.is_empty() {
+            None
+        } else {
+            Some(self.operations.remove(0))
+        }
+    }
+
+    /// Queue an operation.
+    ///
+    /// It is merged into a queued operation of the same kind and target, if there is one:
+    ///
+    /// - subscription changes of the same device are combined, the later change of a feed wins, e.g. an add followed by a remove becomes a remove
+    /// - episode actions are appended to episode actions queued last, dropping repeats of the latest action on an episode and merging a [Play](EpisodeActionType::Play) into the previous one of the same device if it starts where that one stopped; otherwise they are queued as a new operation
+    /// - device data of the same device is combined, later values win
+    /// - settings of the same target are combined, later values win
+    pub fn push(&mut self, operation: Operation) {
+        if let Operation::EpisodeActions(new_actions) = &operation {
+            match self.operations.last_mut() {
+                Some(Operation::EpisodeActions(actions)) => {
+                    append_episode_actions(actions, new_actions)
+                }
+                _ => {
+                    let mut actions = Vec::with_capacity(new_actions.len());
+                    append_episode_actions(&mut actions, new_actions);
+                    if !actions.is_empty() {
+                        self.operations.push(Operation::EpisodeActions(actions));
+                    }
+                }
+            }
+            return;
+        }
+        for queued in &mut self.operations {
+            if merge(queued, &operation) {
+                self.operations.retain(|operation| !is_noop(operation));
+                return;
+            }
+        }
+        if !is_noop(&operation) {
+            self.operations.push(operation);
+        }
+    }
+}
+
+fn merge(queued: &mut Operation, operation: &Operation) -> bool {
+    match (queued, operation) {
+        (
+            Operation::SubscriptionChanges {
+                device,
+                add,
+                remove,
+            },
+            Operation::SubscriptionChanges {
+                device: new_device,
+                add: new_add,
+                remove: new_remove,
+            },
+        ) if device == new_device => {
+            for url in new_add {
+                remove.retain(|removed| removed != url);
+                if !add.contains(url) {
+                    add.push(url.clone());
+                }
+            }
+            for url in new_remove {
+                add.retain(|added| added != url);
+                if !remove.contains(url) {
+                    remove.push(url.clone());
+                }
+            }
+            true
+        }
+        (
+            Operation::DeviceData {
+                device,
+                caption,
+                device_type,
+            },
+            Operation::DeviceData {
+                device: new_device,
+                caption: new_caption,
+                device_type: new_device_type,
+            },
+        ) if device == new_device => {
+            if new_caption.is_some() {
+                caption.clone_from(new_caption);
+            }
+            if new_device_type.is_some() {
+                *device_type = *new_device_type;
+            }
+            true
+        }
+        (
+            Operation::Settings {
+                target,
+                set,
+                remove,
+            },
+            Operation::Settings {
+                target: new_target,
+                set: new_set,
+                remove: new_remove,
+            },
+        ) if target == new_target => {
+            for (key, value) in new_set {
+                remove.retain(|removed| removed != key);
+                set.insert(key.clone(), value.clone());
+            }
+            for key in new_remove {
+                set.remove(key);
+                if !remove.contains(key) {
+                    remove.push(key.clone());
+                }
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Append actions, so that the recorded play history doesn't change
+fn append_episode_actions(actions: &mut Vec<EpisodeAction>, new_actions: &[EpisodeAction]) {
+    for action in new_actions {
+        let previous = actions.iter_mut().rev().find(|previous| {
+            previous.podcast == action.podcast && previous.is_same_episode(action)
+        });
+        if let Some(previous) = previous {
+            if previous == action {
+                continue;
+            }
+            if let (
+                EpisodeActionType::Play {
+                    position,
+                    started: Some(first_started),
+                    ..
+                },
+                EpisodeActionType::Play {
+                    started: Some(started),
+                    ..
+                },
+            ) = (&previous.action, &action.action)
+            {
+                if previous.device == action.device && position == started {
+                    let first_started = *first_started;
+                    *previous = action.clone();
+                    if let EpisodeActionType::Play { started, .. } = &mut previous.action {
+                        *started = Some(first_started);
+                    }
+                    continue;
+                }
+            }
+        }
+        actions.push(action.clone());
+    }
+}
+
+fn is_noop(operation: &Operation) -> bool {
+    match operation {
+        Operation::SubscriptionChanges { add, remove, .. } => add.is_empty() && remove.is_empty(),
+        Operation::EpisodeActions(actions) => actions.is_empty(),
+        Operation::DeviceData {
+            caption,
+            device_type,
+            ..
+        } => caption.is_none() && device_type.is_none(),
+        Operation::Settings { set, remove, .. } => set.is_empty() && remove.is_empty(),
+    }
+}
+
+/// Whether the error indicates that the server can't be reached, so the request should be retried later
+fn is_offline(error: &Error) -> bool {
+    match error {
+        Error::ReqwestError(error) => error.is_connect() || error.is_timeout(),
+        _ => false,
+    }
+}
+
+/// Replace the file at `path`, so that it has either the old or the new content after a crash
+pub(crate) fn write_atomically(path: &Path, content: &[u8]) -> Result<(), Error> {
+    let mut temporary_name = path.file_name().unwrap_or_default().to_os_string();
+    temporary_name.push(".tmp");
+    let temporary_path = path.with_file_name(temporary_name);
+    let mut file = File::create(&temporary_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&temporary_path, path)?;
+    Ok(())
+}
+
+impl OfflineClient {
+    /// Wrap client and use the JSON file at `path` as outbox.
+    ///
+    /// Operations queued in an existing file are kept.
+    pub fn open<P: AsRef<Path>>(client: DeviceClient, path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let outbox = match File::open(&path) {
+            Ok(file) => serde_json::from_reader(file)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Outbox::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(OfflineClient {
+            client,
+            path,
+            outbox: Mutex::new(outbox),
+        })
+    }
+
+    /// Wrapped client
+    pub fn client(&self) -> &DeviceClient {
+        &self.client
+    }
+
+    /// Copy of the queued operations
+    pub fn outbox(&self) -> Outbox {
+        self.outbox.lock().unwrap().clone()
+    }
+
+    /// Replay queued operations in order.
+    ///
+    /// The outbox is saved after every replayed operation. Stops at the first failure and keeps the failed and all following operations queued. If the server rejects an operation, e.g. because of invalid data, it blocks the outbox until it is removed with [discard_first](OfflineClient::discard_first). Returns the number of replayed operations.
+    pub fn flush(&self) -> Result<usize, Error> {
+        let mut outbox = self.outbox.lock().unwrap();
+        let mut flushed = 0;
+        while let Some(operation) = outbox.operations.first() {
+            self.send(operation)?;
+            outbox.operations.remove(0);
+            self.save(&outbox)?;
+            flushed += 1;
+        }
+        Ok(flushed)
+    }
+
+    /// Remove the first queued operation without sending it and return it, e.g. to dead-letter an operation the server rejects
+    pub fn discard_first(&self) -> Result<Option<Operation>, Error> {
+        let mut outbox = self.outbox.lock().unwrap();
+        let operation = outbox.discard_first();
+        self.save(&outbox)?;
+        Ok(operation)
+    }
+
+    fn send(&self, operation: &Operation) -> Result<(), Error> {
+        match operation {
+            Operation::SubscriptionChanges {
+                device,
+                add,
+                remove,
+            } => {
+                self.device_client(device)?
+                    .upload_subscription_changes(add, remove)?;
+            }
+            Operation::EpisodeActions(actions) => {
+                self.client
+                    .authenticated_client
+                    .upload_episode_actions(actions)?;
+            }
+            Operation::DeviceData {
+                device,
+                caption,
+                device_type,
+            } => {
+                self.device_client(device)?
+                    .update_device_data(caption.clone(), *device_type)?;
+            }
+            Operation::Settings {
+                target,
+                set,
+                remove,
+            } => {
+                let (set, remove) = (set.clone(), remove.clone());
+                match target {
+                    SettingsTarget::Account => {
+                        self.client.save_account_settings(set, remove)?;
+                    }
+                    SettingsTarget::Device(device) => {
+                        self.device_client(device)?
+                            .save_device_settings(set, remove)?;
+                    }
+                    SettingsTarget::Podcast(podcast) => {
+                        self.client
+                            .save_podcast_settings(set, remove, podcast.clone())?;
+                    }
+                    SettingsTarget::Episode { podcast, episode } => {
+                        self.client.save_episode_settings(
+                            set,
+                            remove,
+                            podcast.clone(),
+                            episode.clone(),
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn device_client(&self, device: &str) -> Result<DeviceClient, Error> {
+        self.client.authenticated_client.device(device)
+    }
+
+    fn save(&self, outbox: &Outbox) -> Result<(), Error> {
+        write_atomically(&self.path, &serde_json::to_vec(outbox)?)
+    }
+
+    /// Send `operation` with `send` if nothing is queued, otherwise or if the server can't be reached queue it.
+    ///
+    /// Returns `None` if the operation was queued.
+    fn send_or_queue<T>(
+        &self,
+        operation: Operation,
+        send: impl FnOnce() -> Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        let mut outbox = self.outbox.lock().unwrap();
+        if outbox.is_empty() {
+            match send() {
+                Err(error) if is_offline(&error) => {}
+                result => return result.map(Some),
+            }
+        }
+        outbox.push(operation);
+        self.save(&outbox)?;
+        Ok(None)
+    }
+
+    fn save_settings(
+        &self,
+        target: SettingsTarget,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+        send: impl FnOnce(
+            HashMap<String, String>,
+            Vec<String>,
+        ) -> Result<HashMap<String, String>, Error>,
+    ) -> Result<HashMap<String, String>, Error> {
+        let operation = Operation::Settings {
+            target,
+            set: set.clone(),
+            remove: remove.clone(),
+        };
+        Ok(self
+            .send_or_queue(operation, || send(set.clone(), remove))?
+            .unwrap_or(set))
+    }
+}
+
+impl SubscriptionChanges for OfflineClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(add = add.len(), remove = remove.len())))]
+    fn upload_subscription_changes(
+        &self,
+        add: &[Url],
+        remove: &[Url],
+    ) -> Result<UploadSubscriptionChangesResponse, Error> {
+        let operation = Operation::SubscriptionChanges {
+            device: self.client.device_id.to_string(),
+            add: add.to_owned(),
+            remove: remove.to_owned(),
+        };
+        Ok(self
+            .send_or_queue(operation, || {
+                self.client.upload_subscription_changes(add, remove)
+            })?
+            .unwrap_or(UploadSubscriptionChangesResponse {
+                timestamp: 0,
+                update_urls: Vec::new(),
+            }))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(since = timestamp)))]
+    fn get_subscription_changes(
+        &self,
+        timestamp: u64,
+    ) -> Result<GetSubscriptionChangesResponse, Error> {
+        self.client.get_subscription_changes(timestamp)
+    }
+}
+
+impl UploadEpisodeActions for OfflineClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(actions = actions.len())))]
+    fn upload_episode_actions(
+        &self,
+        actions: &[EpisodeAction],
+    ) -> Result<UploadEpisodeActionsResponse, Error> {
+        let operation = Operation::EpisodeActions(actions.to_owned());
+        Ok(self
+            .send_or_queue(operation, || {
+                self.client
+                    .authenticated_client
+                    .upload_episode_actions(actions)
+            })?
+            .unwrap_or(UploadEpisodeActionsResponse {
+                timestamp: 0,
+                update_urls: Vec::new(),
+            }))
+    }
+}
+
+impl UpdateDeviceData for OfflineClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    fn update_device_data<T: Into<Option<String>>, U: Into<Option<DeviceType>>>(
+        &self,
+        caption: T,
+        device_type: U,
+    ) -> Result<(), Error> {
+        let (caption, device_type) = (caption.into(), device_type.into());
+        let operation = Operation::DeviceData {
+            device: self.client.device_id.to_string(),
+            caption: caption.clone(),
+            device_type,
+        };
+        self.send_or_queue(operation, || {
+            self.client.update_device_data(caption, device_type)
+        })?;
+        Ok(())
+    }
+}
+
+impl SaveAccountSettings for OfflineClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
+    fn save_account_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error> {
+        self.save_settings(SettingsTarget::Account, set, remove, |set, remove| {
+            self.client.save_account_settings(set, remove)
+        })
+    }
+}
+
+impl SaveDeviceSettings for OfflineClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
+    fn save_device_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error> {
+        let target = SettingsTarget::Device(self.client.device_id.to_string());
+        self.save_settings(target, set, remove, |set, remove| {
+            self.client.save_device_settings(set, remove)
+        })
+    }
+}
+
+impl SavePodcastSettings for OfflineClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
+    fn save_podcast_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+        podcast: Url,
+    ) -> Result<HashMap<String, String>, Error> {
+        let target = SettingsTarget::Podcast(podcast.clone());
+        self.save_settings(target, set, remove, |set, remove| {
+            self.client.save_podcast_settings(set, remove, podcast)
+        })
+    }
+}
+
+impl SaveEpisodeSettings for OfflineClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
+    fn save_episode_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+        podcast: Url,
+        episode: Url,
+    ) -> Result<HashMap<String, String>, Error> {
+        let target = SettingsTarget::Episode {
+            podcast: podcast.clone(),
+            episode: episode.clone(),
+        };
+        self.save_settings(target, set, remove, |set, remove| {
+            self.client
+                .save_episode_settings(set, remove, podcast, episode)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Operation, Outbox};
+    use crate::cassette::Cassette;
+    use crate::client::DeviceClient;
+    use crate::device::DeviceType;
+    use crate::episode::EpisodeAction;
+    use crate::outbox::OfflineClient;
+    use crate::settings::SettingsTarget;
+    use crate::subscription::SubscriptionChanges;
+    use crate::test_util::{interaction, url};
+    use std::collections::HashMap;
+
+    fn subscription_changes(add: &[&str], remove: &[&str]) -> Operation {
+        Operation::SubscriptionChanges {
+            device: String::from("phone"),
+            add: add.iter().map(|u| url(u)).collect(),
+            remove: remove.iter().map(|u| url(u)).collect(),
+        }
+    }
+
+    #[test]
+    fn coalesce_subscription_changes() {
+        let mut outbox = Outbox::new();
+        outbox.push(subscription_changes(
+            &["http://example.com/1.rss", "http://example.com/2.rss"],
+            &[],
+        ));
+        outbox.push(Operation::DeviceData {
+            device: String::from("phone"),
+            caption: Some(String::from("My Phone")),
+            device_type: None,
+        });
+        outbox.push(subscription_changes(&[], &["http://example.com/1.rss"]));
+        outbox.push(Operation::DeviceData {
+            device: String::from("phone"),
+            caption: None,
+            device_type: Some(DeviceType::Mobile),
+        });
+
+        assert_eq!(
+            &[
+                subscription_changes(&["http://example.com/2.rss"], &["http://example.com/1.rss"]),
+                Operation::DeviceData {
+                    device: String::from("phone"),
+                    caption: Some(String::from("My Phone")),
+                    device_type: Some(DeviceType::Mobile),
+                },
+            ],
+            outbox.operations()
+        );
+    }
+
+    #[test]
+    fn coalesce_settings_and_episode_actions() {
+        let mut outbox = Outbox::new();
+        let mut set = HashMap::new();
+        set.insert(String::from("theme"), String::from("dark"));
+        outbox.push(Operation::Settings {
+            target: SettingsTarget::Account,
+            set,
+            remove: Vec::new(),
+        });
+        outbox.push(Operation::Settings {
+            target: SettingsTarget::Account,
+            set: HashMap::new(),
+            remove: vec![String::from("theme")],
+        });
+        let download = EpisodeAction::new_download(
+            url("http://example.com/feed.rss"),
+            url("http://example.com/1.mp3"),
+            None,
+        );
+        outbox.push(Operation::EpisodeActions(vec![download.clone()]));
+        outbox.push(Operation::EpisodeActions(vec![download.clone()]));
+
+        assert_eq!(
+            &[
+                Operation::Settings {
+                    target: SettingsTarget::Account,
+                    set: HashMap::new(),
+                    remove: vec![String::from("theme")],
+                },
+                Operation::EpisodeActions(vec![download]),
+            ],
+            outbox.operations()
+        );
+    }
+
+    #[test]
+    fn append_episode_actions_in_order() {
+        let podcast = url("http://example.com/feed.rss");
+        let episode = url("http://example.com/1.mp3");
+        let play = |position, started| {
+            EpisodeAction::new_play(
+                podcast.clone(),
+                episode.clone(),
+                None,
+                position,
+                started,
+                900,
+            )
+        };
+        let mut outbox = Outbox::new();
+        outbox.push(Operation::EpisodeActions(vec![play(100, 0)]));
+        // continues the first play
+        outbox.push(Operation::EpisodeActions(vec![play(200, 100)]));
+        // after a seek
+        outbox.push(Operation::EpisodeActions(vec![play(600, 500)]));
+        outbox.push(subscription_changes(&["http://example.com/feed.rss"], &[]));
+        outbox.push(Operation::EpisodeActions(vec![play(700, 600)]));
+
+        assert_eq!(
+            &[
+                Operation::EpisodeActions(vec![play(200, 0), play(600, 500)]),
+                subscription_changes(&["http://example.com/feed.rss"], &[]),
+                Operation::EpisodeActions(vec![play(700, 600)]),
+            ],
+            outbox.operations()
+        );
+    }
+
+    #[test]
+    fn queue_while_offline() {
+        let path =
+            std::env::temp_dir().join(format!("mygpoclient-outbox-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let device_client =
+            DeviceClient::with_server("http://127.0.0.1:1", "alice", "secret", "phone").unwrap();
+        let client = OfflineClient::open(device_client.clone(), &path).unwrap();
+
+        let response = client
+            .upload_subscription_changes(&[url("http://example.com/1.rss")], &[])
+            .unwrap();
+        assert_eq!(0, response.timestamp);
+        assert!(client.flush().is_err());
+
+        let reopened = OfflineClient::open(device_client, &path).unwrap();
+        assert_eq!(
+            &[subscription_changes(&["http://example.com/1.rss"], &[])],
+            reopened.outbox().operations()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn flush_saves_progress_and_discards_rejected_operations() {
+        let path = std::env::temp_dir().join(format!(
+            "mygpoclient-outbox-flush-{}.json",
+            std::process::id()
+        ));
+        let mut outbox = Outbox::new();
+        outbox.push(subscription_changes(&["http://example.com/1.rss"], &[]));
+        outbox.push(Operation::DeviceData {
+            device: String::from("phone"),
+            caption: Some(String::from("My Phone")),
+            device_type: None,
+        });
+        std::fs::write(&path, serde_json::to_vec(&outbox).unwrap()).unwrap();
+        let cassette = Cassette::from_interactions(vec![interaction(
+            "POST",
+            "https://gpodder.example.com/api/2/subscriptions/alice/phone.json",
+            Some(r#"{"add": ["http://example.com/1.rss"], "remove": []}"#),
+            r#"{"timestamp": 12347, "update_urls": []}"#,
+        )]);
+        let device_client =
+            DeviceClient::with_server("https://gpodder.example.com", "alice", "secret", "phone")
+                .unwrap()
+                .with_cassette(cassette);
+        let client = OfflineClient::open(device_client.clone(), &path).unwrap();
+
+        assert!(client.flush().is_err());
+        let reopened = OfflineClient::open(device_client.clone(), &path).unwrap();
+        assert_eq!(1, reopened.outbox().operations().len());
+
+        assert!(matches!(
+            reopened.discard_first().unwrap(),
+            Some(Operation::DeviceData { .. })
+        ));
+        assert_eq!(0, reopened.flush().unwrap());
+        assert!(OfflineClient::open(device_client, &path)
+            .unwrap()
+            .outbox()
+            .is_empty());
+        assert!(!path.with_extension("json.tmp").exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,374 @@
+//! Profiles of gpodder.net compatible servers
+//!
+//! Servers like [oPodSync](https://github.com/kd2org/opodsync) and micro-gpodder implement a subset of the gpodder.net API with some quirks. A [ServerProfile] describes which [endpoints](Endpoint) a server supports, how clients authenticate and how timestamps are sent. Requests to unsupported endpoints fail with [Unsupported](crate::error::Error::Unsupported) without contacting the server.
+
+use crate::episode::EpisodeAction;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Group of API endpoints
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Endpoint {
+    /// podcast directory: tags, podcast and episode data, toplist and search
+    Directory,
+    /// podcast suggestions
+    Suggestions,
+    /// favorite episodes
+    Favorites,
+    /// account, device, podcast and episode settings
+    Settings,
+    /// device list and device data
+    Devices,
+    /// device updates
+    DeviceUpdates,
+    /// subscriptions of the simple API
+    Subscriptions,
+    /// subscription changes of API version 2
+    SubscriptionChanges,
+    /// episode actions
+    EpisodeActions,
+}
+
+/// How clients authenticate
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    /// HTTP basic authentication with every request
+    Basic,
+    /// login once with HTTP basic authentication and send the session cookie afterwards
+    Session,
+}
+
+/// How timestamps of episode actions are uploaded
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFormat {
+    /// ISO 8601 with fractional seconds if present, e.g. `2009-12-12T09:00:00.5`
+    Iso8601,
+    /// ISO 8601 truncated to seconds, e.g. `2009-12-12T09:00:00`
+    Iso8601Seconds,
+    /// UNIX timestamp in seconds
+    Unix,
+}
+
+/// Capabilities and quirks of a server
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::client::AuthenticatedClient;
+/// use mygpoclient::profile::{Endpoint, ServerProfile};
+///
+/// let client = AuthenticatedClient::with_server("https://opodsync.example.com", "alice", "secret")?
+///     .with_profile(ServerProfile::opodsync());
+///
+/// assert!(!client.profile().supports(Endpoint::Directory));
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ServerProfile {
+    /// name of the server software
+    pub name: String,
+    /// authentication mode
+    pub auth: AuthMode,
+    /// format of uploaded timestamps
+    pub timestamp_format: TimestampFormat,
+    /// supported endpoints
+    pub endpoints: BTreeSet<Endpoint>,
+}
+
+const ALL_ENDPOINTS: [Endpoint; 9] = [
+    Endpoint::Directory,
+    Endpoint::Suggestions,
+    Endpoint::Favorites,
+    Endpoint::Settings,
+    Endpoint::Devices,
+    Endpoint::DeviceUpdates,
+    Endpoint::Subscriptions,
+    Endpoint::SubscriptionChanges,
+    Endpoint::EpisodeActions,
+];
+
+/// endpoints of the gpodder.net API which are needed for synchronization
+const SYNC_ENDPOINTS: [Endpoint; 4] = [
+    Endpoint::Devices,
+    Endpoint::Subscriptions,
+    Endpoint::SubscriptionChanges,
+    Endpoint::EpisodeActions,
+];
+
+impl ServerProfile {
+    /// gpodder.net and other instances of mygpo, which support the complete API
+    pub fn gpodder_net() -> ServerProfile {
+        ServerProfile {
+            name: String::from("mygpo"),
+            auth: AuthMode::Basic,
+            timestamp_format: TimestampFormat::Iso8601,
+            endpoints: ALL_ENDPOINTS.iter().copied().collect(),
+        }
+    }
+
+    /// oPodSync, which only supports synchronization and session authentication
+    pub fn opodsync() -> ServerProfile {
+        ServerProfile {
+            name: String::from("oPodSync"),
+            auth: AuthMode::Session,
+            timestamp_format: TimestampFormat::Iso8601Seconds,
+            endpoints: SYNC_ENDPOINTS.iter().copied().collect(),
+        }
+    }
+
+    /// micro-gpodder, the predecessor of oPodSync
+    pub fn micro_gpodder() -> ServerProfile {
+        ServerProfile {
+            name: String::from("micro-gpodder"),
+            auth: AuthMode::Session,
+            timestamp_format: TimestampFormat::Unix,
+            endpoints: SYNC_ENDPOINTS.iter().copied().collect(),
+        }
+    }
+
+    /// Whether the server supports the given endpoint
+    pub fn supports(&self, endpoint: Endpoint) -> bool {
+        self.endpoints.contains(&endpoint)
+    }
+
+    /// Mark endpoint as supported or unsupported
+    pub fn endpoint(mut self, endpoint: Endpoint, supported: bool) -> ServerProfile {
+        if supported {
+            self.endpoints.insert(endpoint);
+        } else {
+            self.endpoints.remove(&endpoint);
+        }
+        self
+    }
+
+    /// Set authentication mode
+    pub fn auth(mut self, auth: AuthMode) -> ServerProfile {
+        self.auth = auth;
+        self
+    }
+
+    /// Set format of uploaded timestamps
+    pub fn timestamp_format(mut self, timestamp_format: TimestampFormat) -> ServerProfile {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    /// Fail with [Unsupported](Error::Unsupported) if the endpoint isn't supported
+    pub(crate) fn check(&self, endpoint: Endpoint) -> Result<(), Error> {
+        if self.supports(endpoint) {
+            Ok(())
+        } else {
+            Err(Error::Unsupported(endpoint))
+        }
+    }
+
+    /// Episode actions as JSON with timestamps in the format of this profile
+    pub(crate) fn episode_actions_json(&self, actions: &[EpisodeAction]) -> Result<Value, Error> {
+        let mut json = serde_json::to_value(actions)?;
+        if self.timestamp_format == TimestampFormat::Iso8601 {
+            return Ok(json);
+        }
+        if let Value::Array(json_actions) = &mut json {
+            for (json_action, action) in json_actions.iter_mut().zip(actions) {
+                if let (Value::Object(fields), Some(timestamp)) = (json_action, action.timestamp) {
+                    let timestamp = match self.timestamp_format {
+                        TimestampFormat::Unix => Value::from(timestamp.and_utc().timestamp()),
+                        _ => Value::from(timestamp.format("%Y-%m-%dT%H:%M:%S").to_string()),
+                    };
+                    fields.insert(String::from("timestamp"), timestamp);
+                }
+            }
+        }
+        Ok(json)
+    }
+}
+
+impl Default for ServerProfile {
+    fn default() -> Self {
+        ServerProfile::gpodder_net()
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Endpoint::Directory => "directory",
+            Endpoint::Suggestions => "suggestions",
+            Endpoint::Favorites => "favorites",
+            Endpoint::Settings => "settings",
+            Endpoint::Devices => "devices",
+            Endpoint::DeviceUpdates => "device updates",
+            Endpoint::Subscriptions => "subscriptions",
+            Endpoint::SubscriptionChanges => "subscription changes",
+            Endpoint::EpisodeActions => "episode actions",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Session ID shared by clones of a client
+#[derive(Clone, Default)]
+pub(crate) struct Session(Arc<Mutex<Option<String>>>);
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Session(..)")
+    }
+}
+
+impl Session {
+    /// Current session ID, if logged in
+    pub(crate) fn get(&self) -> Option<String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set(&self, session_id: String) {
+        *self.0.lock().unwrap() = Some(session_id);
+    }
+
+    /// Forget the session unless another request already replaced it
+    pub(crate) fn expire(&self, session_id: &str) {
+        let mut session = self.0.lock().unwrap();
+        if session.as_deref() == Some(session_id) {
+            *session = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Endpoint, ServerProfile, TimestampFormat};
+    use crate::cassette::Cassette;
+    use crate::client::AuthenticatedClient;
+    use crate::device::ListDevices;
+    use crate::directory::RetrieveTopTags;
+    use crate::episode::EpisodeAction;
+    use crate::error::Error;
+    use crate::test_util::{interaction, login, url};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn session_authentication() {
+        let cassette = Cassette::from_interactions(vec![
+            login(
+                "https://opodsync.example.com/api/2/auth/alice/login.json",
+                "abc",
+            ),
+            interaction(
+                "GET",
+                "https://opodsync.example.com/api/2/devices/alice.json",
+                None,
+                "[]",
+            ),
+            interaction(
+                "GET",
+                "https://opodsync.example.com/api/2/devices/alice.json",
+                None,
+                "[]",
+            ),
+        ]);
+        let client =
+            AuthenticatedClient::with_server("https://opodsync.example.com", "alice", "secret")
+                .unwrap()
+                .with_profile(ServerProfile::opodsync())
+                .with_cassette(cassette.clone());
+
+        assert!(client.list_devices().unwrap().is_empty());
+        assert!(client.list_devices().unwrap().is_empty());
+        assert!(cassette.interactions().is_empty());
+        assert!(matches!(
+            client.retrieve_top_tags(10),
+            Err(Error::Unsupported(Endpoint::Directory))
+        ));
+    }
+
+    #[test]
+    fn unsupported_endpoints_are_rejected() {
+        let profile = ServerProfile::opodsync();
+
+        assert!(matches!(
+            profile.check(Endpoint::Directory),
+            Err(Error::Unsupported(Endpoint::Directory))
+        ));
+        assert!(matches!(
+            profile.check(Endpoint::Settings),
+            Err(Error::Unsupported(Endpoint::Settings))
+        ));
+        assert!(profile.check(Endpoint::EpisodeActions).is_ok());
+        assert!(ServerProfile::gpodder_net()
+            .check(Endpoint::Directory)
+            .is_ok());
+    }
+
+    #[test]
+    fn expired_session_is_renewed() {
+        let login_url = "https://opodsync.example.com/api/2/auth/alice/login.json";
+        let mut expired = interaction(
+            "GET",
+            "https://opodsync.example.com/api/2/devices/alice.json",
+            None,
+            "",
+        );
+        expired.response.status = 401;
+        let cassette = Cassette::from_interactions(vec![
+            login(login_url, "abc"),
+            expired,
+            login(login_url, "def"),
+            interaction(
+                "GET",
+                "https://opodsync.example.com/api/2/devices/alice.json",
+                None,
+                "[]",
+            ),
+        ]);
+        let client =
+            AuthenticatedClient::with_server("https://opodsync.example.com", "alice", "secret")
+                .unwrap()
+                .with_profile(ServerProfile::opodsync())
+                .with_cassette(cassette.clone());
+
+        assert!(client.list_devices().unwrap().is_empty());
+        assert!(cassette.interactions().is_empty());
+        assert_eq!(Some(String::from("def")), client.session.get());
+    }
+
+    #[test]
+    fn timestamp_formats() {
+        let mut action = EpisodeAction::new_download(
+            url("http://example.com/feed.rss"),
+            url("http://example.com/1.mp3"),
+            None,
+        );
+        action.timestamp = Some(
+            NaiveDate::from_ymd_opt(2009, 12, 12)
+                .unwrap()
+                .and_hms_milli_opt(9, 0, 0, 500)
+                .unwrap(),
+        );
+        let actions = [action];
+        let timestamp = |format| {
+            ServerProfile::gpodder_net()
+                .timestamp_format(format)
+                .episode_actions_json(&actions)
+                .unwrap()[0]["timestamp"]
+                .clone()
+        };
+
+        assert_eq!(
+            "2009-12-12T09:00:00.500",
+            timestamp(TimestampFormat::Iso8601)
+        );
+        assert_eq!(
+            "2009-12-12T09:00:00",
+            timestamp(TimestampFormat::Iso8601Seconds)
+        );
+        assert_eq!(1260608400, timestamp(TimestampFormat::Unix));
+    }
+}
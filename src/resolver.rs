@@ -0,0 +1,239 @@
+//! Bulk resolution of feed URLs into [Podcast] data
+//!
+//! [get_subscriptions_of_device](crate::subscription::SubscriptionsOfDevice::get_subscriptions_of_device) only returns feed URLs. [PodcastResolver] looks up the podcast data of many URLs with a bounded number of parallel requests and caches the results.
+
+use crate::directory::RetrievePodcastData;
+use crate::error::Error;
+use crate::normalize::sanitize_url;
+use crate::subscription::{GetAllSubscriptions, Podcast};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use url::Url;
+
+/// Resolver of feed URLs into [Podcast] data with cache
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::client::DeviceClient;
+/// use mygpoclient::resolver::PodcastResolver;
+/// use mygpoclient::subscription::SubscriptionsOfDevice;
+///
+/// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+/// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+/// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
+/// #
+/// let client = DeviceClient::new(&username, &password, &deviceid)?;
+/// let resolver = PodcastResolver::new(&client).concurrency(8);
+/// resolver.load_subscriptions(&client)?;
+///
+/// let urls = client.get_subscriptions_of_device()?;
+/// for (url, podcast) in urls.iter().zip(resolver.resolve(&urls)) {
+///     match podcast {
+///         Ok(podcast) => println!("{}", podcast.title),
+///         Err(_) => println!("{}", url),
+///     }
+/// }
+/// #
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct PodcastResolver<'a, C: ?Sized> {
+    client: &'a C,
+    concurrency: usize,
+    cache: Mutex<HashMap<Url, Podcast>>,
+}
+
+impl<'a, C: RetrievePodcastData + Sync + ?Sized> PodcastResolver<'a, C> {
+    /// Create resolver which retrieves podcast data with `client`
+    pub fn new(client: &'a C) -> Self {
+        PodcastResolver {
+            client,
+            concurrency: 4,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Maximum number of parallel requests, defaults to 4
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Add podcasts to the cache, so their URLs are resolved without request
+    pub fn add_podcasts<I: IntoIterator<Item = Podcast>>(&self, podcasts: I) {
+        let mut cache = self.cache.lock().unwrap();
+        for podcast in podcasts {
+            cache.insert(podcast.url.clone(), podcast);
+        }
+    }
+
+    /// Add all subscriptions of the user to the cache with a single request.
+    ///
+    /// This is usually much faster than resolving the subscriptions of a device one by one.
+    pub fn load_subscriptions<G: GetAllSubscriptions + ?Sized>(
+        &self,
+        client: &G,
+    ) -> Result<(), Error> {
+        self.add_podcasts(client.get_all_subscriptions()?);
+        Ok(())
+    }
+
+    /// Returns the podcast data of each URL, in the order of `urls`.
+    ///
+    /// Cached podcasts are returned without request, also if the URL only differs from the cached one by [sanitization](crate::normalize::sanitize_url). Other URLs are retrieved with up to [concurrency](PodcastResolver::concurrency) parallel requests. Successful results are cached, failures only affect the result of the respective URL.
+    pub fn resolve(&self, urls: &[Url]) -> Vec<Result<Podcast, Error>> {
+        let missing: Vec<&Url> = {
+            let cache = self.cache.lock().unwrap();
+            let mut missing: Vec<&Url> = Vec::new();
+            for url in urls {
+                if lookup(&cache, url).is_none() && !missing.contains(&url) {
+                    missing.push(url);
+                }
+            }
+            missing
+        };
+
+        let mut errors: HashMap<&Url, Error> = HashMap::new();
+        if !missing.is_empty() {
+            let next = AtomicUsize::new(0);
+            let failures = Mutex::new(Vec::new());
+            thread::scope(|scope| {
+                for _ in 0..self.concurrency.min(missing.len()) {
+                    scope.spawn(|| loop {
+                        let index = next.fetch_add(1, Ordering::SeqCst);
+                        let url = match missing.get(index) {
+                            Some(url) => *url,
+                            None => break,
+                        };
+                        match self.client.retrieve_podcast_data(url.clone()) {
+                            Ok(podcast) => {
+                                let mut cache = self.cache.lock().unwrap();
+                                if &podcast.url != url {
+                                    cache.insert(url.clone(), podcast.clone());
+                                }
+                                cache.insert(podcast.url.clone(), podcast);
+                            }
+                            Err(error) => failures.lock().unwrap().push((url, error)),
+                        }
+                    });
+                }
+            });
+            errors.extend(failures.into_inner().unwrap());
+        }
+
+        let cache = self.cache.lock().unwrap();
+        urls.iter()
+            .map(|url| match lookup(&cache, url) {
+                Some(podcast) => Ok(podcast.clone()),
+                None => Err(errors
+                    .remove(url)
+                    .unwrap_or_else(|| Error::UnknownPodcast(url.clone()))),
+            })
+            .collect()
+    }
+}
+
+fn lookup<'c>(cache: &'c HashMap<Url, Podcast>, url: &Url) -> Option<&'c Podcast> {
+    cache.get(url).or_else(|| {
+        sanitize_url(url)
+            .as_ref()
+            .and_then(|sanitized| cache.get(sanitized))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PodcastResolver;
+    use crate::directory::RetrievePodcastData;
+    use crate::error::Error;
+    use crate::subscription::Podcast;
+    use std::sync::Mutex;
+    use url::Url;
+
+    struct Directory {
+        requests: Mutex<Vec<Url>>,
+    }
+
+    impl RetrievePodcastData for Directory {
+        fn retrieve_podcast_data(&self, url: Url) -> Result<Podcast, Error> {
+            self.requests.lock().unwrap().push(url.clone());
+            if url.path().contains("unknown") {
+                return Err(Error::UnknownPodcast(url));
+            }
+            Ok(podcast(url.as_str()))
+        }
+    }
+
+    fn podcast(feed: &str) -> Podcast {
+        Podcast {
+            url: Url::parse(feed).unwrap(),
+            title: feed.to_owned(),
+            author: None,
+            description: String::new(),
+            subscribers: 1,
+            subscribers_last_week: 1,
+            logo_url: None,
+            scaled_logo_url: None,
+            website: None,
+            mygpo_link: Url::parse("http://gpodder.net/podcast/1").unwrap(),
+        }
+    }
+
+    fn urls(urls: &[&str]) -> Vec<Url> {
+        urls.iter().map(|url| Url::parse(url).unwrap()).collect()
+    }
+
+    #[test]
+    fn resolve_aligned_with_input() {
+        let directory = Directory {
+            requests: Mutex::new(Vec::new()),
+        };
+        let resolver = PodcastResolver::new(&directory).concurrency(2);
+        resolver.add_podcasts(vec![podcast("http://example.com/subscribed.rss")]);
+
+        let input = urls(&[
+            "http://example.com/a.rss",
+            "http://example.com/unknown.rss",
+            "feed://example.com/subscribed.rss",
+            "http://example.com/b.rss",
+            "http://example.com/a.rss",
+        ]);
+        let results = resolver.resolve(&input);
+
+        assert_eq!(5, results.len());
+        assert_eq!(
+            "http://example.com/a.rss",
+            results[0].as_ref().unwrap().title
+        );
+        assert!(matches!(results[1], Err(Error::UnknownPodcast(_))));
+        assert_eq!(
+            "http://example.com/subscribed.rss",
+            results[2].as_ref().unwrap().title
+        );
+        assert_eq!(
+            "http://example.com/b.rss",
+            results[3].as_ref().unwrap().title
+        );
+        assert!(results[4].is_ok());
+        assert_eq!(3, directory.requests.lock().unwrap().len());
+    }
+
+    #[test]
+    fn resolve_uses_cache() {
+        let directory = Directory {
+            requests: Mutex::new(Vec::new()),
+        };
+        let resolver = PodcastResolver::new(&directory);
+        let input = urls(&["http://example.com/a.rss", "http://example.com/unknown.rss"]);
+
+        resolver.resolve(&input);
+        let results = resolver.resolve(&input);
+
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert_eq!(3, directory.requests.lock().unwrap().len());
+    }
+}
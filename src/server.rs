@@ -0,0 +1,1170 @@
+//! gpodder.net compatible sync server
+//!
+//! [SyncServer] handles requests of the authentication, device, subscription (simple API and API version 2), episode action, settings, device update and favorites endpoints. Requests and responses are types of the [http](https://docs.rs/http) crate, so the server can be embedded into any HTTP server framework. Data is stored by an implementation of [Storage], e.g. [MemoryStorage].
+//!
+//! Requires the `server` feature.
+
+use crate::backup::FAVORITE_SETTING;
+use crate::device::{Device, DeviceData, DeviceType, DeviceUpdates, EpisodeUpdate};
+use crate::directory::Episode;
+use crate::episode::{
+    EpisodeAction, EpisodeActionQuery, GetEpisodeActionsResponse, UploadEpisodeActionsResponse,
+};
+use crate::error::Error;
+use crate::id::DeviceId;
+use crate::normalize::sanitize_urls;
+use crate::settings::{SaveSettingsRequest, SettingsTarget};
+use crate::subscription::{
+    subscription_changes, GetSubscriptionChangesResponse, Podcast,
+    UploadSubscriptionChangesRequest, UploadSubscriptionChangesResponse,
+};
+use chrono::{DateTime, Utc};
+use http::{header, Method, Request, Response, StatusCode};
+use percent_encoding::percent_decode_str;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use url::form_urlencoded;
+use url::Url;
+
+/// Data store of a [SyncServer]
+///
+/// Timestamps are assigned by the storage. They must increase with every change, changes are returned if their timestamp is greater than the requested `since` timestamp.
+pub trait Storage {
+    /// Whether the user exists and the password is correct
+    fn authenticate(&self, username: &str, password: &str) -> Result<bool, Error>;
+
+    /// Devices of the user
+    fn devices(&self, username: &str) -> Result<Vec<Device>, Error>;
+
+    /// Create or update a device, `None` values are left unchanged
+    fn update_device(
+        &self,
+        username: &str,
+        device: &str,
+        caption: Option<String>,
+        device_type: Option<DeviceType>,
+    ) -> Result<(), Error>;
+
+    /// Current subscriptions of a device
+    fn subscriptions(&self, username: &str, device: &str) -> Result<Vec<Url>, Error>;
+
+    /// Subscription changes of a device since the given timestamp
+    fn subscription_changes(
+        &self,
+        username: &str,
+        device: &str,
+        since: u64,
+    ) -> Result<GetSubscriptionChangesResponse, Error>;
+
+    /// Apply subscription changes to a device, creating the device if necessary, and return the new timestamp
+    fn apply_subscription_changes(
+        &self,
+        username: &str,
+        device: &str,
+        add: &[Url],
+        remove: &[Url],
+    ) -> Result<u64, Error>;
+
+    /// Episode actions matching the query
+    fn episode_actions(
+        &self,
+        username: &str,
+        query: &EpisodeActionQuery,
+    ) -> Result<GetEpisodeActionsResponse, Error>;
+
+    /// Store episode actions and return the new timestamp
+    fn add_episode_actions(&self, username: &str, actions: &[EpisodeAction]) -> Result<u64, Error>;
+
+    /// Settings of the given scope
+    fn settings(
+        &self,
+        username: &str,
+        target: &SettingsTarget,
+    ) -> Result<HashMap<String, String>, Error>;
+
+    /// Change settings of the given scope and return all settings of the scope
+    fn save_settings(
+        &self,
+        username: &str,
+        target: &SettingsTarget,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error>;
+
+    /// Favorite episodes of the user
+    fn favorites(&self, username: &str) -> Result<Vec<Episode>, Error>;
+
+    /// Podcast data for the simple API and device updates
+    ///
+    /// The default implementation only contains the feed URL.
+    fn podcast(&self, url: &Url) -> Result<Podcast, Error> {
+        Ok(minimal_podcast(url))
+    }
+
+    /// Episode data for favorites and device updates
+    ///
+    /// The default implementation only contains the URLs.
+    fn episode(&self, podcast: &Url, episode: &Url) -> Result<Episode, Error> {
+        Ok(minimal_episode(podcast, episode))
+    }
+}
+
+fn minimal_podcast(url: &Url) -> Podcast {
+    Podcast {
+        url: url.clone(),
+        title: url.to_string(),
+        author: None,
+        description: String::new(),
+        subscribers: 0,
+        subscribers_last_week: 0,
+        logo_url: None,
+        scaled_logo_url: None,
+        website: None,
+        mygpo_link: url.clone(),
+    }
+}
+
+fn minimal_episode(podcast: &Url, episode: &Url) -> Episode {
+    Episode {
+        title: episode.to_string(),
+        url: episode.clone(),
+        podcast_title: podcast.to_string(),
+        podcast_url: podcast.clone(),
+        description: String::new(),
+        website: None,
+        mygpo_link: episode.clone(),
+        released: DateTime::<Utc>::UNIX_EPOCH.naive_utc(),
+    }
+}
+
+/// Request handler of a gpodder.net compatible server
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::server::{MemoryStorage, SyncServer};
+///
+/// let storage = MemoryStorage::new();
+/// storage.add_user("alice", "secret");
+/// let server = SyncServer::new(storage);
+///
+/// let request = http::Request::get("/api/2/devices/alice.json")
+///     .header("Authorization", "Basic YWxpY2U6c2VjcmV0")
+///     .body(Vec::new())
+///     .unwrap();
+/// let response = server.handle(request);
+///
+/// assert_eq!(200, response.status());
+/// assert_eq!(b"[]", response.body().as_slice());
+/// ```
+#[derive(Debug)]
+pub struct SyncServer<S> {
+    storage: S,
+    sessions: Mutex<HashMap<String, Session>>,
+    session_lifetime: Duration,
+}
+
+/// Lifetime of sessions if not set by [SyncServer::with_session_lifetime]
+const DEFAULT_SESSION_LIFETIME: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+/// Logged in user
+#[derive(Debug)]
+struct Session {
+    username: String,
+    expires: Instant,
+}
+
+/// Error response
+struct Failure(StatusCode, String);
+
+impl<E: Into<Error>> From<E> for Failure {
+    fn from(error: E) -> Self {
+        let error = error.into();
+        let status = match error {
+            Error::InvalidIdentifier(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Failure(status, error.to_string())
+    }
+}
+
+type Handled = Result<Response<Vec<u8>>, Failure>;
+
+impl<S: Storage> SyncServer<S> {
+    /// Create server with the given storage
+    pub fn new(storage: S) -> Self {
+        SyncServer {
+            storage,
+            sessions: Default::default(),
+            session_lifetime: DEFAULT_SESSION_LIFETIME,
+        }
+    }
+
+    /// Set how long sessions are valid after login, two weeks by default
+    pub fn with_session_lifetime(mut self, lifetime: Duration) -> Self {
+        self.session_lifetime = lifetime;
+        self
+    }
+
+    /// Storage of the server
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Handle a request.
+    ///
+    /// Paths are expected relative to the server root, e.g. `/api/2/devices/alice.json`.
+    pub fn handle(&self, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+        match self.route(&request) {
+            Ok(response) => response,
+            Err(Failure(status, message)) => Response::builder()
+                .status(status)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(message.into_bytes())
+                .unwrap(),
+        }
+    }
+
+    fn route(&self, request: &Request<Vec<u8>>) -> Handled {
+        let path = request.uri().path();
+        let query: HashMap<String, String> =
+            form_urlencoded::parse(request.uri().query().unwrap_or_default().as_bytes())
+                .into_owned()
+                .collect();
+        let not_found = || Failure(StatusCode::NOT_FOUND, format!("unknown endpoint {}", path));
+        let method_not_allowed = || {
+            Failure(
+                StatusCode::METHOD_NOT_ALLOWED,
+                format!("method {} not allowed for {}", request.method(), path),
+            )
+        };
+        let segments: Vec<String> = path
+            .trim_start_matches('/')
+            .trim_end_matches(".json")
+            .split('/')
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+            .collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        let method = request.method();
+
+        match segments.as_slice() {
+            ["api", "2", "auth", username, "login"] if method == Method::POST => {
+                self.login(request, username)
+            }
+            ["api", "2", "auth", username, "logout"] if method == Method::POST => {
+                self.authorize(request, username)?;
+                self.logout(request);
+                empty()
+            }
+            ["api", "2", "devices", username] if method == Method::GET => {
+                self.authorize(request, username)?;
+                json(&self.storage.devices(username)?)
+            }
+            ["api", "2", "devices", username, device] if method == Method::POST => {
+                self.authorize(request, username)?;
+                let data: DeviceData = parse(request)?;
+                self.storage
+                    .update_device(username, device, data.caption, data.device_type)?;
+                empty()
+            }
+            ["subscriptions", username] if method == Method::GET => {
+                self.authorize(request, username)?;
+                let mut urls: Vec<Url> = Vec::new();
+                for device in self.storage.devices(username)? {
+                    for url in self.storage.subscriptions(username, &device.id)? {
+                        if !urls.contains(&url) {
+                            urls.push(url);
+                        }
+                    }
+                }
+                let podcasts = urls
+                    .iter()
+                    .map(|url| self.storage.podcast(url))
+                    .collect::<Result<Vec<Podcast>, Error>>()?;
+                json(&podcasts)
+            }
+            ["subscriptions", username, device] if method == Method::GET => {
+                self.authorize(request, username)?;
+                json(&self.storage.subscriptions(username, device)?)
+            }
+            ["subscriptions", username, device] if method == Method::PUT => {
+                self.authorize(request, username)?;
+                let wanted = sanitize_urls(&parse::<Vec<Url>>(request)?, &mut Vec::new());
+                let current = self.storage.subscriptions(username, device)?;
+                let changes = subscription_changes(device, &current, &wanted);
+                self.storage.apply_subscription_changes(
+                    username,
+                    device,
+                    &changes.add,
+                    &changes.remove,
+                )?;
+                empty()
+            }
+            ["api", "2", "subscriptions", username, device] if method == Method::GET => {
+                self.authorize(request, username)?;
+                let since = number(&query, "since")?.unwrap_or_default();
+                json(&self.storage.subscription_changes(username, device, since)?)
+            }
+            ["api", "2", "subscriptions", username, device] if method == Method::POST => {
+                self.authorize(request, username)?;
+                let changes: UploadSubscriptionChangesRequest = parse(request)?;
+                if changes.add.iter().any(|url| changes.remove.contains(url)) {
+                    return Err(Failure(
+                        StatusCode::BAD_REQUEST,
+                        String::from("the same feed is added and removed"),
+                    ));
+                }
+                let mut update_urls = Vec::new();
+                let add = sanitize_urls(&changes.add, &mut update_urls);
+                let remove = sanitize_urls(&changes.remove, &mut update_urls);
+                let timestamp = self
+                    .storage
+                    .apply_subscription_changes(username, device, &add, &remove)?;
+                json(&UploadSubscriptionChangesResponse {
+                    timestamp,
+                    update_urls,
+                })
+            }
+            ["api", "2", "episodes", username] if method == Method::GET => {
+                self.authorize(request, username)?;
+                let mut episode_query = EpisodeActionQuery::new();
+                if let Some(podcast) = query.get("podcast") {
+                    episode_query = episode_query
+                        .podcast(Url::parse(podcast).map_err(|error| {
+                            Failure(StatusCode::BAD_REQUEST, error.to_string())
+                        })?);
+                }
+                if let Some(device) = query.get("device") {
+                    episode_query = episode_query.device(device.as_str());
+                }
+                if let Some(since) = number(&query, "since")? {
+                    episode_query = episode_query.since(since);
+                }
+                episode_query = episode_query
+                    .aggregated(query.get("aggregated").map(String::as_str) == Some("true"));
+                json(&self.storage.episode_actions(username, &episode_query)?)
+            }
+            ["api", "2", "episodes", username] if method == Method::POST => {
+                self.authorize(request, username)?;
+                let actions: Vec<EpisodeAction> = parse(request)?;
+                let timestamp = self.storage.add_episode_actions(username, &actions)?;
+                json(&UploadEpisodeActionsResponse {
+                    timestamp,
+                    update_urls: Vec::new(),
+                })
+            }
+            ["api", "2", "settings", username, scope] => {
+                self.authorize(request, username)?;
+                let url = |key: &str| -> Result<Url, Failure> {
+                    query
+                        .get(key)
+                        .and_then(|url| Url::parse(url).ok())
+                        .ok_or_else(|| {
+                            Failure(
+                                StatusCode::BAD_REQUEST,
+                                format!("missing parameter {}", key),
+                            )
+                        })
+                };
+                let target = match *scope {
+                    "account" => SettingsTarget::Account,
+                    "device" => {
+                        SettingsTarget::Device(query.get("device").cloned().ok_or_else(|| {
+                            Failure(
+                                StatusCode::BAD_REQUEST,
+                                String::from("missing parameter device"),
+                            )
+                        })?)
+                    }
+                    "podcast" => SettingsTarget::Podcast(url("podcast")?),
+                    "episode" => SettingsTarget::Episode {
+                        podcast: url("podcast")?,
+                        episode: url("episode")?,
+                    },
+                    _ => return Err(not_found()),
+                };
+                match *method {
+                    Method::GET => json(&self.storage.settings(username, &target)?),
+                    Method::POST => {
+                        let changes: SaveSettingsRequest = parse(request)?;
+                        json(&self.storage.save_settings(
+                            username,
+                            &target,
+                            changes.set,
+                            changes.remove,
+                        )?)
+                    }
+                    _ => Err(method_not_allowed()),
+                }
+            }
+            ["api", "2", "updates", username, device] if method == Method::GET => {
+                self.authorize(request, username)?;
+                let since = number(&query, "since")?.unwrap_or_default();
+                let include_actions =
+                    query.get("include_actions").map(String::as_str) == Some("true");
+                json(&device_updates(
+                    &self.storage,
+                    username,
+                    device,
+                    since,
+                    include_actions,
+                )?)
+            }
+            ["api", "2", "favorites", username] if method == Method::GET => {
+                self.authorize(request, username)?;
+                json(&self.storage.favorites(username)?)
+            }
+            segments if is_endpoint(segments) => Err(method_not_allowed()),
+            _ => Err(not_found()),
+        }
+    }
+
+    fn login(&self, request: &Request<Vec<u8>>, username: &str) -> Handled {
+        match basic_credentials(request) {
+            Some((user, password))
+                if user == username && self.storage.authenticate(&user, &password)? => {}
+            _ => return Err(unauthorized()),
+        }
+        let mut bytes = [0_u8; 16];
+        getrandom::getrandom(&mut bytes).map_err(io::Error::from)?;
+        let session_id: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.expires > now);
+        sessions.insert(
+            session_id.clone(),
+            Session {
+                username: username.to_owned(),
+                expires: now + self.session_lifetime,
+            },
+        );
+        drop(sessions);
+        Ok(Response::builder()
+            .header(
+                header::SET_COOKIE,
+                format!("sessionid={}; Path=/; HttpOnly", session_id),
+            )
+            .body(Vec::new())
+            .unwrap())
+    }
+
+    fn logout(&self, request: &Request<Vec<u8>>) {
+        if let Some(session_id) = session_id(request) {
+            self.sessions.lock().unwrap().remove(&session_id);
+        }
+    }
+
+    /// Require basic authentication or a session of the user of the requested path
+    fn authorize(&self, request: &Request<Vec<u8>>, username: &str) -> Result<(), Failure> {
+        if let Some(session_id) = session_id(request) {
+            let mut sessions = self.sessions.lock().unwrap();
+            match sessions.get(&session_id) {
+                Some(session) if session.expires <= Instant::now() => {
+                    sessions.remove(&session_id);
+                }
+                Some(session) if session.username == username => return Ok(()),
+                _ => {}
+            }
+        }
+        match basic_credentials(request) {
+            Some((user, password))
+                if user == username && self.storage.authenticate(&user, &password)? =>
+            {
+                Ok(())
+            }
+            _ => Err(unauthorized()),
+        }
+    }
+}
+
+/// Whether the path segments address a known endpoint, regardless of the method
+fn is_endpoint(segments: &[&str]) -> bool {
+    matches!(
+        segments,
+        ["api", "2", "auth", _, "login"]
+            | ["api", "2", "auth", _, "logout"]
+            | ["api", "2", "devices", _]
+            | ["api", "2", "devices", _, _]
+            | ["subscriptions", _]
+            | ["subscriptions", _, _]
+            | ["api", "2", "subscriptions", _, _]
+            | ["api", "2", "episodes", _]
+            | ["api", "2", "updates", _, _]
+            | ["api", "2", "favorites", _]
+    )
+}
+
+/// Device updates as returned by the updates endpoint
+pub(crate) fn device_updates<S: Storage + ?Sized>(
+    storage: &S,
+    username: &str,
+    device: &str,
+    since: u64,
+    include_actions: bool,
+) -> Result<DeviceUpdates, Error> {
+    let changes = storage.subscription_changes(username, device, since)?;
+    let mut updates = Vec::new();
+    if include_actions {
+        let actions = storage.episode_actions(
+            username,
+            &EpisodeActionQuery::new().since(since).aggregated(true),
+        )?;
+        for action in actions.actions {
+            updates.push(EpisodeUpdate {
+                episode: storage.episode(&action.podcast, &action.episode)?,
+                status: Some(action.action),
+            });
+        }
+    }
+    Ok(DeviceUpdates {
+        add: changes
+            .add
+            .iter()
+            .map(|url| storage.podcast(url))
+            .collect::<Result<Vec<Podcast>, Error>>()?,
+        rem: changes.remove,
+        updates,
+        timestamp: changes.timestamp,
+    })
+}
+
+fn unauthorized() -> Failure {
+    Failure(StatusCode::UNAUTHORIZED, String::from("unauthorized"))
+}
+
+fn basic_credentials(request: &Request<Vec<u8>>) -> Option<(String, String)> {
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let encoded = authorization.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(base64::decode(encoded.trim()).ok()?).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_owned(), password.to_owned()))
+}
+
+fn session_id(request: &Request<Vec<u8>>) -> Option<String> {
+    request
+        .headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|cookies| cookies.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|cookie| cookie.trim().strip_prefix("sessionid="))
+        .map(str::to_owned)
+}
+
+fn parse<T: DeserializeOwned>(request: &Request<Vec<u8>>) -> Result<T, Failure> {
+    serde_json::from_slice(request.body())
+        .map_err(|error| Failure(StatusCode::BAD_REQUEST, error.to_string()))
+}
+
+fn number(query: &HashMap<String, String>, key: &str) -> Result<Option<u64>, Failure> {
+    query
+        .get(key)
+        .map(|value| {
+            value.parse().map_err(|_| {
+                Failure(
+                    StatusCode::BAD_REQUEST,
+                    format!("invalid parameter {}", key),
+                )
+            })
+        })
+        .transpose()
+}
+
+fn json<T: Serialize>(value: &T) -> Handled {
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(value)?)
+        .unwrap())
+}
+
+fn empty() -> Handled {
+    Ok(Response::builder().body(Vec::new()).unwrap())
+}
+
+/// [Storage] which keeps all data in memory
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    timestamp: u64,
+    users: HashMap<String, MemoryUser>,
+    podcasts: BTreeMap<Url, Podcast>,
+    episodes: HashMap<(Url, Url), Episode>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryUser {
+    password: String,
+    devices: BTreeMap<String, Device>,
+    /// device, feed, whether it was added, timestamp
+    subscription_events: Vec<(String, Url, bool, u64)>,
+    episode_actions: Vec<(EpisodeAction, u64)>,
+    settings: HashMap<SettingsTarget, HashMap<String, String>>,
+}
+
+impl MemoryStorage {
+    /// Create storage without users
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add user or change the password of an existing user
+    pub fn add_user(&self, username: &str, password: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.users.entry(username.to_owned()).or_default().password = password.to_owned();
+    }
+
+    /// Add podcast data, which is returned instead of minimal data
+    pub fn add_podcast(&self, podcast: Podcast) {
+        let mut state = self.state.lock().unwrap();
+        state.podcasts.insert(podcast.url.clone(), podcast);
+    }
+
+    /// Add episode data, which is returned instead of minimal data
+    pub fn add_episode(&self, episode: Episode) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .episodes
+            .insert((episode.podcast_url.clone(), episode.url.clone()), episode);
+    }
+
+    /// Added podcasts ordered by feed URL
+    pub fn podcasts(&self) -> Vec<Podcast> {
+        let state = self.state.lock().unwrap();
+        state.podcasts.values().cloned().collect()
+    }
+
+    /// Read data of a user with the timestamp of the latest change
+    fn with_user<T>(
+        &self,
+        username: &str,
+        f: impl FnOnce(&MemoryUser, u64) -> T,
+    ) -> Result<T, Error> {
+        let state = self.state.lock().unwrap();
+        let user = state
+            .users
+            .get(username)
+            .ok_or_else(|| Error::UnknownUser(username.to_owned()))?;
+        Ok(f(user, state.timestamp))
+    }
+
+    /// Change data of a user with a new timestamp
+    fn update_user<T>(
+        &self,
+        username: &str,
+        f: impl FnOnce(&mut MemoryUser, u64) -> T,
+    ) -> Result<T, Error> {
+        let mut state = self.state.lock().unwrap();
+        if !state.users.contains_key(username) {
+            return Err(Error::UnknownUser(username.to_owned()));
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        state.timestamp = now.max(state.timestamp + 1);
+        let timestamp = state.timestamp;
+        let user = state
+            .users
+            .get_mut(username)
+            .ok_or_else(|| Error::UnknownUser(username.to_owned()))?;
+        Ok(f(user, timestamp))
+    }
+}
+
+impl MemoryUser {
+    fn device(&mut self, device: &DeviceId) -> &mut Device {
+        self.devices
+            .entry(device.to_string())
+            .or_insert_with(|| Device {
+                id: device.clone(),
+                caption: String::new(),
+                device_type: DeviceType::Other,
+                subscriptions: 0,
+            })
+    }
+
+    fn subscriptions(&self, device: &str, until: u64) -> Vec<Url> {
+        let mut subscriptions: Vec<Url> = Vec::new();
+        for (_, url, added, _) in self
+            .subscription_events
+            .iter()
+            .filter(|(event_device, _, _, timestamp)| event_device == device && *timestamp <= until)
+        {
+            subscriptions.retain(|subscription| subscription != url);
+            if *added {
+                subscriptions.push(url.clone());
+            }
+        }
+        subscriptions
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn authenticate(&self, username: &str, password: &str) -> Result<bool, Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .users
+            .get(username)
+            .is_some_and(|user| user.password == password))
+    }
+
+    fn devices(&self, username: &str) -> Result<Vec<Device>, Error> {
+        self.with_user(username, |user, timestamp| {
+            let mut devices: Vec<Device> = user.devices.values().cloned().collect();
+            for device in &mut devices {
+                device.subscriptions = user.subscriptions(&device.id, timestamp).len() as u16;
+            }
+            devices
+        })
+    }
+
+    fn update_device(
+        &self,
+        username: &str,
+        device: &str,
+        caption: Option<String>,
+        device_type: Option<DeviceType>,
+    ) -> Result<(), Error> {
+        let device = DeviceId::new(device)?;
+        self.update_user(username, |user, _| {
+            let device = user.device(&device);
+            if let Some(caption) = caption {
+                device.caption = caption;
+            }
+            if let Some(device_type) = device_type {
+                device.device_type = device_type;
+            }
+        })
+    }
+
+    fn subscriptions(&self, username: &str, device: &str) -> Result<Vec<Url>, Error> {
+        self.with_user(username, |user, timestamp| {
+            user.subscriptions(device, timestamp)
+        })
+    }
+
+    fn subscription_changes(
+        &self,
+        username: &str,
+        device: &str,
+        since: u64,
+    ) -> Result<GetSubscriptionChangesResponse, Error> {
+        self.with_user(username, |user, timestamp| {
+            let before = user.subscriptions(device, since);
+            let now = user.subscriptions(device, timestamp);
+            let changes = subscription_changes(device, &before, &now);
+            GetSubscriptionChangesResponse {
+                timestamp,
+                add: changes.add,
+                remove: changes.remove,
+            }
+        })
+    }
+
+    fn apply_subscription_changes(
+        &self,
+        username: &str,
+        device: &str,
+        add: &[Url],
+        remove: &[Url],
+    ) -> Result<u64, Error> {
+        let device_id = DeviceId::new(device)?;
+        self.update_user(username, |user, timestamp| {
+            user.device(&device_id);
+            for (urls, added) in [(remove, false), (add, true)] {
+                for url in urls {
+                    user.subscription_events.push((
+                        device.to_owned(),
+                        url.clone(),
+                        added,
+                        timestamp,
+                    ));
+                }
+            }
+            timestamp
+        })
+    }
+
+    fn episode_actions(
+        &self,
+        username: &str,
+        query: &EpisodeActionQuery,
+    ) -> Result<GetEpisodeActionsResponse, Error> {
+        self.with_user(username, |user, timestamp| {
+            let mut actions: Vec<EpisodeAction> = Vec::new();
+            for (action, action_timestamp) in &user.episode_actions {
+                if query.since.is_some_and(|since| *action_timestamp <= since)
+                    || query
+                        .podcast
+                        .as_ref()
+                        .is_some_and(|podcast| &action.podcast != podcast)
+                    || query
+                        .device
+                        .as_ref()
+                        .is_some_and(|device| action.device.as_ref() != Some(device))
+                {
+                    continue;
+                }
+                if query.aggregated {
+                    actions.retain(|aggregated| !aggregated.is_same_episode(action));
+                }
+                actions.push(action.clone());
+            }
+            GetEpisodeActionsResponse { actions, timestamp }
+        })
+    }
+
+    fn add_episode_actions(&self, username: &str, actions: &[EpisodeAction]) -> Result<u64, Error> {
+        let devices = actions
+            .iter()
+            .filter_map(|action| action.device.as_deref())
+            .map(DeviceId::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.update_user(username, |user, timestamp| {
+            for device in &devices {
+                user.device(device);
+            }
+            for action in actions {
+                user.episode_actions.push((action.clone(), timestamp));
+            }
+            timestamp
+        })
+    }
+
+    fn settings(
+        &self,
+        username: &str,
+        target: &SettingsTarget,
+    ) -> Result<HashMap<String, String>, Error> {
+        self.with_user(username, |user, _| {
+            user.settings.get(target).cloned().unwrap_or_default()
+        })
+    }
+
+    fn save_settings(
+        &self,
+        username: &str,
+        target: &SettingsTarget,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error> {
+        self.update_user(username, |user, _| {
+            let settings = user.settings.entry(target.clone()).or_default();
+            for key in remove {
+                settings.remove(&key);
+            }
+            settings.extend(set);
+            settings.clone()
+        })
+    }
+
+    fn favorites(&self, username: &str) -> Result<Vec<Episode>, Error> {
+        let favorites: Vec<(Url, Url)> = self.with_user(username, |user, _| {
+            user.settings
+                .iter()
+                .filter(|(_, settings)| {
+                    settings.get(FAVORITE_SETTING).map(String::as_str) == Some("true")
+                })
+                .filter_map(|(target, _)| match target {
+                    SettingsTarget::Episode { podcast, episode } => {
+                        Some((podcast.clone(), episode.clone()))
+                    }
+                    _ => None,
+                })
+                .collect()
+        })?;
+        favorites
+            .iter()
+            .map(|(podcast, episode)| self.episode(podcast, episode))
+            .collect()
+    }
+
+    fn podcast(&self, url: &Url) -> Result<Podcast, Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .podcasts
+            .get(url)
+            .cloned()
+            .unwrap_or_else(|| minimal_podcast(url)))
+    }
+
+    fn episode(&self, podcast: &Url, episode: &Url) -> Result<Episode, Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .episodes
+            .get(&(podcast.clone(), episode.clone()))
+            .cloned()
+            .unwrap_or_else(|| minimal_episode(podcast, episode)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryStorage, SyncServer};
+    use http::{Request, Response};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    const ALICE: &str = "Basic YWxpY2U6c2VjcmV0";
+
+    fn server() -> SyncServer<MemoryStorage> {
+        let storage = MemoryStorage::new();
+        storage.add_user("alice", "secret");
+        storage.add_user("bob", "secret");
+        SyncServer::new(storage)
+    }
+
+    fn request(method: &str, uri: &str, authorization: &str, body: Value) -> Request<Vec<u8>> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", authorization)
+            .body(if body.is_null() {
+                Vec::new()
+            } else {
+                serde_json::to_vec(&body).unwrap()
+            })
+            .unwrap()
+    }
+
+    fn body(response: Response<Vec<u8>>) -> Value {
+        assert_eq!(200, response.status(), "{:?}", response);
+        serde_json::from_slice(response.body()).unwrap()
+    }
+
+    #[test]
+    fn authentication() {
+        let server = server();
+
+        let wrong_password = server.handle(request(
+            "GET",
+            "/api/2/devices/alice.json",
+            "Basic YWxpY2U6d3Jvbmc=",
+            Value::Null,
+        ));
+        assert_eq!(401, wrong_password.status());
+        let other_user = server.handle(request(
+            "GET",
+            "/api/2/devices/bob.json",
+            ALICE,
+            Value::Null,
+        ));
+        assert_eq!(401, other_user.status());
+
+        let login = server.handle(request(
+            "POST",
+            "/api/2/auth/alice/login.json",
+            ALICE,
+            Value::Null,
+        ));
+        let cookie = login.headers()["set-cookie"].to_str().unwrap();
+        let session = Request::get("/api/2/devices/alice.json")
+            .header("Cookie", cookie.split(';').next().unwrap())
+            .body(Vec::new())
+            .unwrap();
+        assert_eq!(200, server.handle(session).status());
+        assert_eq!(
+            32 + "sessionid=".len(),
+            cookie.split(';').next().unwrap().len()
+        );
+    }
+
+    #[test]
+    fn sessions_expire() {
+        let server = server().with_session_lifetime(Duration::from_secs(0));
+
+        let login = server.handle(request(
+            "POST",
+            "/api/2/auth/alice/login.json",
+            ALICE,
+            Value::Null,
+        ));
+        let cookie = login.headers()["set-cookie"].to_str().unwrap();
+        let session = Request::get("/api/2/devices/alice.json")
+            .header("Cookie", cookie.split(';').next().unwrap())
+            .body(Vec::new())
+            .unwrap();
+        assert_eq!(401, server.handle(session).status());
+    }
+
+    #[test]
+    fn unsupported_methods() {
+        let server = server();
+
+        let devices = server.handle(request(
+            "PUT",
+            "/api/2/devices/alice.json",
+            ALICE,
+            Value::Null,
+        ));
+        assert_eq!(405, devices.status());
+        let settings = server.handle(request(
+            "DELETE",
+            "/api/2/settings/alice/account.json",
+            ALICE,
+            Value::Null,
+        ));
+        assert_eq!(405, settings.status());
+        let unknown = server.handle(request("GET", "/api/2/unknown.json", ALICE, Value::Null));
+        assert_eq!(404, unknown.status());
+    }
+
+    #[test]
+    fn reads_keep_timestamp() {
+        let server = server();
+        let changes = || {
+            body(server.handle(request(
+                "GET",
+                "/api/2/subscriptions/alice/phone.json?since=0",
+                ALICE,
+                Value::Null,
+            )))["timestamp"]
+                .clone()
+        };
+
+        let first = changes();
+        assert_eq!(first, changes());
+        let upload = body(server.handle(request(
+            "POST",
+            "/api/2/subscriptions/alice/phone.json",
+            ALICE,
+            json!({"add": ["http://example.com/1.rss"], "remove": []}),
+        )));
+        assert_eq!(upload["timestamp"], changes());
+        assert!(upload["timestamp"].as_u64() > first.as_u64());
+    }
+
+    #[test]
+    fn subscriptions_and_updates() {
+        let server = server();
+
+        let upload = body(server.handle(request(
+            "POST",
+            "/api/2/subscriptions/alice/phone.json",
+            ALICE,
+            json!({"add": ["http://feeds2.feedburner.com/LinuxOutlaws", "http://example.com/1.rss"], "remove": []}),
+        )));
+        assert_eq!(
+            json!([[
+                "http://feeds2.feedburner.com/LinuxOutlaws",
+                "http://feeds.feedburner.com/LinuxOutlaws"
+            ]]),
+            upload["update_urls"]
+        );
+        let timestamp = upload["timestamp"].as_u64().unwrap();
+
+        server.handle(request(
+            "POST",
+            "/api/2/subscriptions/alice/phone.json",
+            ALICE,
+            json!({"add": [], "remove": ["http://example.com/1.rss"]}),
+        ));
+
+        let changes = body(server.handle(request(
+            "GET",
+            &format!("/api/2/subscriptions/alice/phone.json?since={}", timestamp),
+            ALICE,
+            Value::Null,
+        )));
+        assert_eq!(json!([]), changes["add"]);
+        assert_eq!(json!(["http://example.com/1.rss"]), changes["remove"]);
+
+        let simple = body(server.handle(request(
+            "GET",
+            "/subscriptions/alice/phone.json",
+            ALICE,
+            Value::Null,
+        )));
+        assert_eq!(json!(["http://feeds.feedburner.com/LinuxOutlaws"]), simple);
+
+        let devices = body(server.handle(request(
+            "GET",
+            "/api/2/devices/alice.json",
+            ALICE,
+            Value::Null,
+        )));
+        assert_eq!(1, devices[0]["subscriptions"]);
+
+        let updates = body(server.handle(request(
+            "GET",
+            "/api/2/updates/alice/phone.json?since=0&include_actions=false",
+            ALICE,
+            Value::Null,
+        )));
+        assert_eq!(
+            "http://feeds.feedburner.com/LinuxOutlaws",
+            updates["add"][0]["url"]
+        );
+    }
+
+    #[test]
+    fn simple_api_sanitizes_subscriptions() {
+        let server = server();
+
+        let upload = server.handle(request(
+            "PUT",
+            "/subscriptions/alice/phone.json",
+            ALICE,
+            json!(["http://feeds2.feedburner.com/LinuxOutlaws"]),
+        ));
+        assert_eq!(200, upload.status());
+
+        let simple = body(server.handle(request(
+            "GET",
+            "/subscriptions/alice/phone.json",
+            ALICE,
+            Value::Null,
+        )));
+        assert_eq!(json!(["http://feeds.feedburner.com/LinuxOutlaws"]), simple);
+    }
+
+    #[test]
+    fn episode_actions_settings_and_favorites() {
+        let server = server();
+
+        body(server.handle(request(
+            "POST",
+            "/api/2/episodes/alice.json",
+            ALICE,
+            json!([
+                {"podcast": "http://example.com/feed.rss", "episode": "http://example.com/1.mp3", "device": "phone", "action": "download"},
+                {"podcast": "http://example.com/feed.rss", "episode": "http://example.com/1.mp3", "device": "phone", "action": "play", "position": 10}
+            ]),
+        )));
+        let actions = body(server.handle(request(
+            "GET",
+            "/api/2/episodes/alice.json?aggregated=true",
+            ALICE,
+            Value::Null,
+        )));
+        assert_eq!(1, actions["actions"].as_array().unwrap().len());
+        assert_eq!("play", actions["actions"][0]["action"]);
+
+        let settings = body(server.handle(request(
+            "POST",
+            "/api/2/settings/alice/episode.json?podcast=http://example.com/feed.rss&episode=http://example.com/1.mp3",
+            ALICE,
+            json!({"set": {"is_favorite": "true"}, "remove": []}),
+        )));
+        assert_eq!(json!({"is_favorite": "true"}), settings);
+
+        let favorites = body(server.handle(request(
+            "GET",
+            "/api/2/favorites/alice.json",
+            ALICE,
+            Value::Null,
+        )));
+        assert_eq!("http://example.com/1.mp3", favorites[0]["url"]);
+    }
+}
@@ -3,11 +3,30 @@
 use crate::client::AuthenticatedClient;
 use crate::client::DeviceClient;
 use crate::error::Error;
-use serde::Serialize;
+use crate::profile::Endpoint;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use url::Url;
 
-#[derive(Serialize)]
+/// Scope of settings
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SettingsTarget {
+    /// account settings
+    Account,
+    /// settings of the device with the given ID
+    Device(String),
+    /// settings of the podcast with the given feed URL
+    Podcast(Url),
+    /// settings of an episode
+    Episode {
+        /// feed URL
+        podcast: Url,
+        /// media URL
+        episode: Url,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
 pub(crate) struct SaveSettingsRequest {
     pub(crate) set: HashMap<String, String>,
     pub(crate) remove: Vec<String>,
@@ -27,7 +46,7 @@ pub trait SaveAccountSettings {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     /// let mut set = HashMap::new();
     /// set.insert(String::from("setting1"), String::from("value1"));
     /// set.insert(String::from("setting2"), String::from("value2"));
@@ -65,7 +84,7 @@ pub trait SaveDeviceSettings {
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
     /// #
-    /// let client = DeviceClient::new(&username, &password, &deviceid);
+    /// let client = DeviceClient::new(&username, &password, &deviceid)?;
     /// let mut set = HashMap::new();
     /// set.insert(String::from("setting1"), String::from("value1"));
     /// set.insert(String::from("setting2"), String::from("value2"));
@@ -103,7 +122,7 @@ pub trait SavePodcastSettings {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     /// let mut set = HashMap::new();
     /// set.insert(String::from("setting1"), String::from("value1"));
     /// set.insert(String::from("setting2"), String::from("value2"));
@@ -142,7 +161,7 @@ pub trait SaveEpisodeSettings {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     /// let mut set = HashMap::new();
     /// set.insert(String::from("setting1"), String::from("value1"));
     /// set.insert(String::from("setting2"), String::from("value2"));
@@ -180,7 +199,7 @@ pub trait GetAccountSettings {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     ///
     /// let settings = client.get_account_settings()?;
     /// #
@@ -207,7 +226,7 @@ pub trait GetDeviceSettings {
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
     /// #
-    /// let client = DeviceClient::new(&username, &password, &deviceid);
+    /// let client = DeviceClient::new(&username, &password, &deviceid)?;
     ///
     /// let settings = client.get_device_settings()?;
     /// #
@@ -234,7 +253,7 @@ pub trait GetPodcastSettings {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     ///
     /// let settings = client.get_podcast_settings(Url::parse("http://goinglinux.com/mp3podcast.xml").unwrap())?;
     /// #
@@ -261,7 +280,7 @@ pub trait GetEpisodeSettings {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     ///
     /// let settings = client.get_episode_settings(Url::parse("http://example.com/feed1.rss").unwrap(), Url::parse("http://example.com/files/s01e20.mp3").unwrap())?;
     /// #
@@ -279,6 +298,7 @@ pub trait GetEpisodeSettings {
 }
 
 impl SaveAccountSettings for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
     fn save_account_settings(
         &self,
         set: HashMap<String, String>,
@@ -286,9 +306,11 @@ impl SaveAccountSettings for AuthenticatedClient {
     ) -> Result<HashMap<String, String>, Error> {
         Ok(self
             .post(
+                Endpoint::Settings,
                 &format!(
-                    "https://gpodder.net/api/2/settings/{}/account.json",
-                    self.username
+                    "{}/api/2/settings/{}/account.json",
+                    self.server(),
+                    self.username.path_segment()
                 ),
                 &SaveSettingsRequest { set, remove },
             )?
@@ -297,6 +319,7 @@ impl SaveAccountSettings for AuthenticatedClient {
 }
 
 impl SaveAccountSettings for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
     fn save_account_settings(
         &self,
         set: HashMap<String, String>,
@@ -307,6 +330,7 @@ impl SaveAccountSettings for DeviceClient {
 }
 
 impl SaveDeviceSettings for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
     fn save_device_settings(
         &self,
         set: HashMap<String, String>,
@@ -314,9 +338,11 @@ impl SaveDeviceSettings for DeviceClient {
     ) -> Result<HashMap<String, String>, Error> {
         Ok(self
             .post_with_query(
+                Endpoint::Settings,
                 &format!(
-                    "https://gpodder.net/api/2/settings/{}/device.json",
-                    self.authenticated_client.username
+                    "{}/api/2/settings/{}/device.json",
+                    self.server(),
+                    self.authenticated_client.username.path_segment()
                 ),
                 &SaveSettingsRequest { set, remove },
                 &[&("device", self.device_id.as_str())],
@@ -326,6 +352,7 @@ impl SaveDeviceSettings for DeviceClient {
 }
 
 impl SavePodcastSettings for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
     fn save_podcast_settings(
         &self,
         set: HashMap<String, String>,
@@ -334,9 +361,11 @@ impl SavePodcastSettings for AuthenticatedClient {
     ) -> Result<HashMap<String, String>, Error> {
         Ok(self
             .post_with_query(
+                Endpoint::Settings,
                 &format!(
-                    "https://gpodder.net/api/2/settings/{}/podcast.json",
-                    self.username
+                    "{}/api/2/settings/{}/podcast.json",
+                    self.server(),
+                    self.username.path_segment()
                 ),
                 &SaveSettingsRequest { set, remove },
                 &[&("podcast", podcast.as_str())],
@@ -346,6 +375,7 @@ impl SavePodcastSettings for AuthenticatedClient {
 }
 
 impl SavePodcastSettings for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
     fn save_podcast_settings(
         &self,
         set: HashMap<String, String>,
@@ -358,6 +388,7 @@ impl SavePodcastSettings for DeviceClient {
 }
 
 impl SaveEpisodeSettings for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
     fn save_episode_settings(
         &self,
         set: HashMap<String, String>,
@@ -367,9 +398,11 @@ impl SaveEpisodeSettings for AuthenticatedClient {
     ) -> Result<HashMap<String, String>, Error> {
         Ok(self
             .post_with_query(
+                Endpoint::Settings,
                 &format!(
-                    "https://gpodder.net/api/2/settings/{}/episode.json",
-                    self.username
+                    "{}/api/2/settings/{}/episode.json",
+                    self.server(),
+                    self.username.path_segment()
                 ),
                 &SaveSettingsRequest { set, remove },
                 &[
@@ -382,6 +415,7 @@ impl SaveEpisodeSettings for AuthenticatedClient {
 }
 
 impl SaveEpisodeSettings for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(set = set.len(), remove = remove.len())))]
     fn save_episode_settings(
         &self,
         set: HashMap<String, String>,
@@ -395,29 +429,38 @@ impl SaveEpisodeSettings for DeviceClient {
 }
 
 impl GetAccountSettings for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_account_settings(&self) -> Result<HashMap<String, String>, Error> {
         Ok(self
-            .get(&format!(
-                "https://gpodder.net/api/2/settings/{}/account.json",
-                self.username
-            ))?
+            .get(
+                Endpoint::Settings,
+                &format!(
+                    "{}/api/2/settings/{}/account.json",
+                    self.server(),
+                    self.username.path_segment()
+                ),
+            )?
             .json()?)
     }
 }
 
 impl GetAccountSettings for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_account_settings(&self) -> Result<HashMap<String, String>, Error> {
         self.authenticated_client.get_account_settings()
     }
 }
 
 impl GetDeviceSettings for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_device_settings(&self) -> Result<HashMap<String, String>, Error> {
         Ok(self
             .get_with_query(
+                Endpoint::Settings,
                 &format!(
-                    "https://gpodder.net/api/2/settings/{}/device.json",
-                    self.authenticated_client.username
+                    "{}/api/2/settings/{}/device.json",
+                    self.server(),
+                    self.authenticated_client.username.path_segment()
                 ),
                 &[&("device", self.device_id.as_str())],
             )?
@@ -426,12 +469,15 @@ impl GetDeviceSettings for DeviceClient {
 }
 
 impl GetPodcastSettings for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_podcast_settings(&self, podcast: Url) -> Result<HashMap<String, String>, Error> {
         Ok(self
             .get_with_query(
+                Endpoint::Settings,
                 &format!(
-                    "https://gpodder.net/api/2/settings/{}/podcast.json",
-                    self.username
+                    "{}/api/2/settings/{}/podcast.json",
+                    self.server(),
+                    self.username.path_segment()
                 ),
                 &[&("podcast", podcast.as_str())],
             )?
@@ -440,12 +486,14 @@ impl GetPodcastSettings for AuthenticatedClient {
 }
 
 impl GetPodcastSettings for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_podcast_settings(&self, podcast: Url) -> Result<HashMap<String, String>, Error> {
         self.authenticated_client.get_podcast_settings(podcast)
     }
 }
 
 impl GetEpisodeSettings for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_episode_settings(
         &self,
         podcast: Url,
@@ -453,9 +501,11 @@ impl GetEpisodeSettings for AuthenticatedClient {
     ) -> Result<HashMap<String, String>, Error> {
         Ok(self
             .get_with_query(
+                Endpoint::Settings,
                 &format!(
-                    "https://gpodder.net/api/2/settings/{}/episode.json",
-                    self.username
+                    "{}/api/2/settings/{}/episode.json",
+                    self.server(),
+                    self.username.path_segment()
                 ),
                 &[
                     &("podcast", podcast.as_str()),
@@ -467,6 +517,7 @@ impl GetEpisodeSettings for AuthenticatedClient {
 }
 
 impl GetEpisodeSettings for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_episode_settings(
         &self,
         podcast: Url,
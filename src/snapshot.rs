@@ -0,0 +1,349 @@
+//! Snapshots of an account and the changes between them
+//!
+//! An [AccountSnapshot] captures devices, subscriptions, settings and favorites of an account at a point in time. [diff] lists what changed between two snapshots, e.g. to audit what a client did to the account between two syncs.
+
+use crate::backup::{self, DeviceBackup};
+use crate::client::AuthenticatedClient;
+use crate::device::Device;
+use crate::directory::Episode;
+use crate::error::Error;
+use crate::subscription::{subscription_changes, DeviceSubscriptionChanges};
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use url::Url;
+
+/// State of an account at a point in time
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AccountSnapshot {
+    /// UTC timestamp when the snapshot was taken
+    pub created: NaiveDateTime,
+    /// devices with their subscriptions and settings
+    pub devices: Vec<DeviceBackup>,
+    /// account settings
+    pub account_settings: HashMap<String, String>,
+    /// settings of all subscribed podcasts which have settings
+    pub podcast_settings: BTreeMap<Url, HashMap<String, String>>,
+    /// favorite episodes
+    pub favorites: Vec<Episode>,
+}
+
+/// Scope of a [SettingChange]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SettingScope {
+    /// account settings
+    Account,
+    /// settings of the device with the given ID
+    Device(String),
+    /// settings of the podcast with the given feed URL
+    Podcast(Url),
+}
+
+/// Setting which was added, changed or removed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SettingChange {
+    /// scope of the setting
+    pub scope: SettingScope,
+    /// setting key
+    pub key: String,
+    /// value in the older snapshot
+    pub old: Option<String>,
+    /// value in the newer snapshot
+    pub new: Option<String>,
+}
+
+/// Changes between two [snapshots](AccountSnapshot), see [diff]
+///
+/// Its [Display](fmt::Display) implementation lists all changes in a diff-like format.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AccountChanges {
+    /// devices which didn't exist in the older snapshot
+    pub added_devices: Vec<Device>,
+    /// devices whose caption or type changed, as in the newer snapshot
+    pub changed_devices: Vec<Device>,
+    /// added and removed subscriptions per device
+    pub subscriptions: Vec<DeviceSubscriptionChanges>,
+    /// added, changed and removed settings
+    pub settings: Vec<SettingChange>,
+    /// episodes which became favorites
+    pub added_favorites: Vec<Episode>,
+    /// episodes which are no favorites anymore
+    pub removed_favorites: Vec<Episode>,
+}
+
+impl AccountSnapshot {
+    /// Take a snapshot of the account of the given client.
+    ///
+    /// Settings and favorites are left empty if the [profile](AuthenticatedClient::profile) of the client doesn't support them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mygpoclient::client::AuthenticatedClient;
+    /// use mygpoclient::snapshot::{diff, AccountSnapshot};
+    /// use std::fs;
+    ///
+    /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+    /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+    /// #
+    /// let client = AuthenticatedClient::new(&username, &password)?;
+    ///
+    /// let before: AccountSnapshot = serde_json::from_slice(&fs::read("snapshot.json")?)?;
+    /// let after = AccountSnapshot::create(&client)?;
+    /// print!("{}", diff(&before, &after));
+    /// fs::write("snapshot.json", serde_json::to_vec(&after)?)?;
+    /// #
+    /// # Ok::<(), mygpoclient::error::Error>(())
+    /// ```
+    pub fn create(client: &AuthenticatedClient) -> Result<AccountSnapshot, Error> {
+        let devices = backup::device_backups(client)?;
+        let podcast_settings = backup::podcast_settings(client, &devices)?;
+
+        Ok(AccountSnapshot {
+            created: Utc::now().naive_utc(),
+            account_settings: backup::account_settings(client)?,
+            podcast_settings,
+            favorites: backup::favorites(client)?,
+            devices,
+        })
+    }
+}
+
+impl AccountChanges {
+    /// Whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self == &AccountChanges::default()
+    }
+}
+
+/// Changes from snapshot `a` to the newer snapshot `b`
+///
+/// Devices can't be deleted, so devices which are missing in `b` are ignored.
+pub fn diff(a: &AccountSnapshot, b: &AccountSnapshot) -> AccountChanges {
+    let mut changes = AccountChanges::default();
+    let empty_settings = HashMap::new();
+
+    for device_b in &b.devices {
+        let device = &device_b.device;
+        let device_a = a
+            .devices
+            .iter()
+            .find(|device_a| device_a.device.id == device.id);
+
+        match device_a {
+            None => changes.added_devices.push(device.clone()),
+            Some(device_a)
+                if device_a.device.caption != device.caption
+                    || device_a.device.device_type != device.device_type =>
+            {
+                changes.changed_devices.push(device.clone())
+            }
+            Some(_) => {}
+        }
+
+        let empty_subscriptions = Vec::new();
+        let subscriptions = subscription_changes(
+            &device.id,
+            device_a.map_or(&empty_subscriptions, |device| &device.subscriptions),
+            &device_b.subscriptions,
+        );
+        if !subscriptions.add.is_empty() || !subscriptions.remove.is_empty() {
+            changes.subscriptions.push(subscriptions);
+        }
+
+        setting_changes(
+            &mut changes.settings,
+            SettingScope::Device(device.id.to_string()),
+            device_a.map_or(&empty_settings, |device| &device.settings),
+            &device_b.settings,
+        );
+    }
+
+    setting_changes(
+        &mut changes.settings,
+        SettingScope::Account,
+        &a.account_settings,
+        &b.account_settings,
+    );
+
+    let podcasts: BTreeSet<&Url> = a
+        .podcast_settings
+        .keys()
+        .chain(b.podcast_settings.keys())
+        .collect();
+    for podcast in podcasts {
+        setting_changes(
+            &mut changes.settings,
+            SettingScope::Podcast(podcast.clone()),
+            a.podcast_settings.get(podcast).unwrap_or(&empty_settings),
+            b.podcast_settings.get(podcast).unwrap_or(&empty_settings),
+        );
+    }
+
+    changes.added_favorites = b
+        .favorites
+        .iter()
+        .filter(|favorite| !a.favorites.contains(favorite))
+        .cloned()
+        .collect();
+    changes.removed_favorites = a
+        .favorites
+        .iter()
+        .filter(|favorite| !b.favorites.contains(favorite))
+        .cloned()
+        .collect();
+
+    changes
+}
+
+fn setting_changes(
+    changes: &mut Vec<SettingChange>,
+    scope: SettingScope,
+    a: &HashMap<String, String>,
+    b: &HashMap<String, String>,
+) {
+    let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    for key in keys {
+        let (old, new) = (a.get(key), b.get(key));
+        if old != new {
+            changes.push(SettingChange {
+                scope: scope.clone(),
+                key: key.clone(),
+                old: old.cloned(),
+                new: new.cloned(),
+            });
+        }
+    }
+}
+
+impl fmt::Display for SettingScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingScope::Account => write!(f, "account"),
+            SettingScope::Device(device) => write!(f, "device {}", device),
+            SettingScope::Podcast(podcast) => write!(f, "podcast {}", podcast),
+        }
+    }
+}
+
+impl fmt::Display for AccountChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        backup::write_devices(f, &self.added_devices, &self.changed_devices)?;
+        backup::write_subscriptions(f, &self.subscriptions)?;
+        for change in &self.settings {
+            match (&change.old, &change.new) {
+                (None, Some(new)) => {
+                    writeln!(f, "+ {} setting {} = {}", change.scope, change.key, new)?
+                }
+                (Some(old), None) => {
+                    writeln!(f, "- {} setting {} = {}", change.scope, change.key, old)?
+                }
+                (old, new) => writeln!(
+                    f,
+                    "~ {} setting {} = {} -> {}",
+                    change.scope,
+                    change.key,
+                    old.as_deref().unwrap_or_default(),
+                    new.as_deref().unwrap_or_default()
+                )?,
+            }
+        }
+        backup::write_favorites(f, '+', &self.added_favorites)?;
+        backup::write_favorites(f, '-', &self.removed_favorites)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, AccountSnapshot, SettingChange, SettingScope};
+    use crate::backup::DeviceBackup;
+    use crate::test_util::{created, device_backup, url};
+    use std::collections::{BTreeMap, HashMap};
+
+    fn snapshot(devices: Vec<DeviceBackup>) -> AccountSnapshot {
+        AccountSnapshot {
+            created: created(),
+            devices,
+            account_settings: HashMap::new(),
+            podcast_settings: BTreeMap::new(),
+            favorites: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn diff_of_identical_snapshots_is_empty() {
+        let a = snapshot(vec![device_backup(
+            "phone",
+            "My Phone",
+            vec![url("http://example.com/feed1.rss")],
+        )]);
+
+        assert!(diff(&a, &a.clone()).is_empty());
+    }
+
+    #[test]
+    fn diff_lists_changes() {
+        let mut a = snapshot(vec![device_backup(
+            "phone",
+            "Old Phone",
+            vec![url("http://example.com/feed1.rss")],
+        )]);
+        a.account_settings
+            .insert(String::from("public"), String::from("true"));
+        a.account_settings
+            .insert(String::from("theme"), String::from("dark"));
+
+        let mut b = snapshot(vec![
+            device_backup(
+                "phone",
+                "My Phone",
+                vec![url("http://example.com/feed2.rss")],
+            ),
+            device_backup("laptop", "My Laptop", Vec::new()),
+        ]);
+        b.account_settings
+            .insert(String::from("public"), String::from("false"));
+        b.podcast_settings.insert(
+            url("http://example.com/feed2.rss"),
+            vec![(String::from("speed"), String::from("1.5"))]
+                .into_iter()
+                .collect(),
+        );
+
+        let changes = diff(&a, &b);
+
+        assert_eq!(
+            SettingChange {
+                scope: SettingScope::Account,
+                key: String::from("public"),
+                old: Some(String::from("true")),
+                new: Some(String::from("false")),
+            },
+            changes.settings[0]
+        );
+        assert_eq!(
+            "+ device Mobile My Laptop (id=laptop)\n\
+             ~ device Mobile My Phone (id=phone)\n\
+             + subscription phone http://example.com/feed2.rss\n\
+             - subscription phone http://example.com/feed1.rss\n\
+             ~ account setting public = true -> false\n\
+             - account setting theme = dark\n\
+             + podcast http://example.com/feed2.rss setting speed = 1.5\n",
+            changes.to_string()
+        );
+    }
+
+    #[test]
+    fn snapshot_json_round_trip() {
+        let a = snapshot(vec![device_backup(
+            "phone",
+            "My Phone",
+            vec![url("http://example.com/feed1.rss")],
+        )]);
+
+        let json = serde_json::to_string(&a).unwrap();
+
+        assert_eq!(a, serde_json::from_str(&json).unwrap());
+    }
+}
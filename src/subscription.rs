@@ -2,7 +2,9 @@
 
 use crate::client::AuthenticatedClient;
 use crate::client::DeviceClient;
+use crate::device::ListDevices;
 use crate::error::Error;
+use crate::profile::Endpoint;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::fmt;
@@ -34,12 +36,23 @@ pub struct Podcast {
     pub mygpo_link: Url,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub(crate) struct UploadSubscriptionChangesRequest {
     pub(crate) add: Vec<Url>,
     pub(crate) remove: Vec<Url>,
 }
 
+/// Subscription changes of a single device
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceSubscriptionChanges {
+    /// device ID
+    pub device: String,
+    /// subscriptions to be added
+    pub add: Vec<Url>,
+    /// subscriptions to be removed
+    pub remove: Vec<Url>,
+}
+
 /// Response to [upload_subscription_changes](SubscriptionChanges::upload_subscription_changes)
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Ord, PartialOrd, Hash)]
 pub struct UploadSubscriptionChangesResponse {
@@ -62,6 +75,29 @@ pub struct GetSubscriptionChangesResponse {
     pub remove: Vec<Url>,
 }
 
+/// How [merge_subscriptions](MergeSubscriptions::merge_subscriptions) combines the subscriptions of two devices
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeMode {
+    /// target device gets exactly the subscriptions of the source device
+    Replace,
+    /// subscriptions of the source device are added to the target device
+    Union,
+    /// target device keeps only subscriptions which the source device has, too
+    Intersection,
+}
+
+/// Result of [merge_subscriptions](MergeSubscriptions::merge_subscriptions)
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeSubscriptionsReport {
+    /// feeds added to the target device
+    pub added: Vec<Url>,
+    /// feeds removed from the target device
+    pub removed: Vec<Url>,
+    /// response to the upload of the changes; `None` if nothing had to be changed
+    pub response: Option<UploadSubscriptionChangesResponse>,
+}
+
 /// see [get_all_subscriptions](GetAllSubscriptions::get_all_subscriptions)
 pub trait GetAllSubscriptions {
     /// Get All Subscriptions
@@ -77,7 +113,7 @@ pub trait GetAllSubscriptions {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     ///
     /// let subscriptions = client.get_all_subscriptions()?;
     /// #
@@ -104,7 +140,7 @@ pub trait SubscriptionsOfDevice {
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
     /// #
-    /// let client = DeviceClient::new(&username, &password, &deviceid);
+    /// let client = DeviceClient::new(&username, &password, &deviceid)?;
     ///
     /// let subscriptions = client.get_subscriptions_of_device()?;
     /// #
@@ -139,7 +175,7 @@ pub trait SubscriptionChanges {
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
     /// #
-    /// let client = DeviceClient::new(&username, &password, &deviceid);
+    /// let client = DeviceClient::new(&username, &password, &deviceid)?;
     ///
     /// # let url1 = Url::parse("http://example.com/feed.rss").unwrap();
     /// # let url2 = Url::parse("http://example.org/podcast.php").unwrap();
@@ -173,7 +209,7 @@ pub trait SubscriptionChanges {
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
     /// #
-    /// let client = DeviceClient::new(&username, &password, &deviceid);
+    /// let client = DeviceClient::new(&username, &password, &deviceid)?;
     ///
     /// let subscription_changes = client.get_subscription_changes(0)?;
     /// #
@@ -189,38 +225,117 @@ pub trait SubscriptionChanges {
     ) -> Result<GetSubscriptionChangesResponse, Error>;
 }
 
+/// see [merge_subscriptions](MergeSubscriptions::merge_subscriptions)
+pub trait MergeSubscriptions {
+    /// Copy or merge the subscriptions of one device into another device.
+    ///
+    /// The target device is created if it doesn't exist yet.
+    ///
+    /// # Parameters
+    ///
+    /// - `source_device_id`: ID of the device whose subscriptions are copied
+    /// - `target_device_id`: ID of the device whose subscriptions are changed
+    /// - `mode`: see [MergeMode]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mygpoclient::client::AuthenticatedClient;
+    /// use mygpoclient::subscription::{MergeMode, MergeSubscriptions};
+    ///
+    /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
+    /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
+    /// # let deviceid = std::env::var("GPODDER_NET_DEVICEID").unwrap();
+    /// #
+    /// let client = AuthenticatedClient::new(&username, &password)?;
+    ///
+    /// let report = client.merge_subscriptions(&deviceid, &deviceid, MergeMode::Replace)?;
+    /// assert!(report.added.is_empty() && report.removed.is_empty());
+    /// #
+    /// # Ok::<(), mygpoclient::error::Error>(())
+    /// ```
+    fn merge_subscriptions(
+        &self,
+        source_device_id: &str,
+        target_device_id: &str,
+        mode: MergeMode,
+    ) -> Result<MergeSubscriptionsReport, Error>;
+}
+
+impl MergeMode {
+    // subscriptions to add to and to remove from the target device
+    pub(crate) fn changes(self, source: &[Url], target: &[Url]) -> (Vec<Url>, Vec<Url>) {
+        let missing_in_target = || {
+            source
+                .iter()
+                .filter(|url| !target.contains(url))
+                .cloned()
+                .collect()
+        };
+        let missing_in_source = || {
+            target
+                .iter()
+                .filter(|url| !source.contains(url))
+                .cloned()
+                .collect()
+        };
+
+        match self {
+            MergeMode::Replace => (missing_in_target(), missing_in_source()),
+            MergeMode::Union => (missing_in_target(), Vec::new()),
+            MergeMode::Intersection => (Vec::new(), missing_in_source()),
+        }
+    }
+}
+
 impl GetAllSubscriptions for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_all_subscriptions(&self) -> Result<Vec<Podcast>, Error> {
         Ok(self
-            .get(&format!(
-                "https://gpodder.net/subscriptions/{}.json",
-                self.username
-            ))?
+            .get(
+                Endpoint::Subscriptions,
+                &format!(
+                    "{}/subscriptions/{}.json",
+                    self.server(),
+                    self.username.path_segment()
+                ),
+            )?
             .json()?)
     }
 }
 
 impl GetAllSubscriptions for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_all_subscriptions(&self) -> Result<Vec<Podcast>, Error> {
         self.as_ref().get_all_subscriptions()
     }
 }
 
 impl SubscriptionsOfDevice for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
     fn get_subscriptions_of_device(&self) -> Result<Vec<Url>, Error> {
         Ok(self
-            .get(&format!(
-                "https://gpodder.net/subscriptions/{}/{}.json",
-                self.authenticated_client.username, self.device_id
-            ))?
+            .get(
+                Endpoint::Subscriptions,
+                &format!(
+                    "{}/subscriptions/{}/{}.json",
+                    self.server(),
+                    self.authenticated_client.username.path_segment(),
+                    self.device_id.path_segment()
+                ),
+            )?
             .json()?) // TODO handle response?
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(subscriptions = subscriptions.len())))]
     fn upload_subscriptions_of_device(&self, subscriptions: &[Url]) -> Result<(), Error> {
         self.put(
+            Endpoint::Subscriptions,
             &format!(
-                "https://gpodder.net/subscriptions/{}/{}.json",
-                self.authenticated_client.username, self.device_id
+                "{}/subscriptions/{}/{}.json",
+                self.server(),
+                self.authenticated_client.username.path_segment(),
+                self.device_id.path_segment()
             ),
             subscriptions,
         )?; // TODO handle response?
@@ -229,6 +344,7 @@ impl SubscriptionsOfDevice for DeviceClient {
 }
 
 impl SubscriptionChanges for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(add = add.len(), remove = remove.len())))]
     fn upload_subscription_changes(
         &self,
         add: &[Url],
@@ -240,24 +356,31 @@ impl SubscriptionChanges for DeviceClient {
         };
         Ok(self
             .post(
+                Endpoint::SubscriptionChanges,
                 &format!(
-                    "https://gpodder.net/api/2/subscriptions/{}/{}.json",
-                    self.authenticated_client.username, self.device_id
+                    "{}/api/2/subscriptions/{}/{}.json",
+                    self.server(),
+                    self.authenticated_client.username.path_segment(),
+                    self.device_id.path_segment()
                 ),
                 &input,
             )?
             .json()?)
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(since = timestamp)))]
     fn get_subscription_changes(
         &self,
         timestamp: u64,
     ) -> Result<GetSubscriptionChangesResponse, Error> {
         Ok(self
             .get_with_query(
+                Endpoint::SubscriptionChanges,
                 &format!(
-                    "https://gpodder.net/api/2/subscriptions/{}/{}.json",
-                    self.authenticated_client.username, self.device_id
+                    "{}/api/2/subscriptions/{}/{}.json",
+                    self.server(),
+                    self.authenticated_client.username.path_segment(),
+                    self.device_id.path_segment()
                 ),
                 &[&("since", timestamp)],
             )?
@@ -265,6 +388,57 @@ impl SubscriptionChanges for DeviceClient {
     }
 }
 
+impl MergeSubscriptions for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    fn merge_subscriptions(
+        &self,
+        source_device_id: &str,
+        target_device_id: &str,
+        mode: MergeMode,
+    ) -> Result<MergeSubscriptionsReport, Error> {
+        let devices = self.list_devices()?;
+        if !devices.iter().any(|device| device.id == source_device_id) {
+            return Err(Error::UnknownDevice(source_device_id.to_owned()));
+        }
+
+        let source = self
+            .device(source_device_id)?
+            .get_subscriptions_of_device()?;
+        let target_client = self.device(target_device_id)?;
+        let target = if devices.iter().any(|device| device.id == target_device_id) {
+            target_client.get_subscriptions_of_device()?
+        } else {
+            Vec::new()
+        };
+
+        let (added, removed) = mode.changes(&source, &target);
+        let response = if added.is_empty() && removed.is_empty() {
+            None
+        } else {
+            Some(target_client.upload_subscription_changes(&added, &removed)?)
+        };
+
+        Ok(MergeSubscriptionsReport {
+            added,
+            removed,
+            response,
+        })
+    }
+}
+
+impl MergeSubscriptions for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    fn merge_subscriptions(
+        &self,
+        source_device_id: &str,
+        target_device_id: &str,
+        mode: MergeMode,
+    ) -> Result<MergeSubscriptionsReport, Error> {
+        self.as_ref()
+            .merge_subscriptions(source_device_id, target_device_id, mode)
+    }
+}
+
 impl PartialEq for Podcast {
     fn eq(&self, other: &Self) -> bool {
         self.url == other.url
@@ -311,9 +485,31 @@ impl fmt::Display for GetSubscriptionChangesResponse {
     }
 }
 
+/// Changes which turn the `current` subscriptions of `device` into the `wanted` ones
+pub(crate) fn subscription_changes(
+    device: &str,
+    current: &[Url],
+    wanted: &[Url],
+) -> DeviceSubscriptionChanges {
+    DeviceSubscriptionChanges {
+        device: device.to_owned(),
+        add: wanted
+            .iter()
+            .filter(|url| !current.contains(url))
+            .cloned()
+            .collect(),
+        remove: current
+            .iter()
+            .filter(|url| !wanted.contains(url))
+            .cloned()
+            .collect(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::GetSubscriptionChangesResponse;
+    use super::MergeMode;
     use super::Podcast;
     use super::UploadSubscriptionChangesResponse;
     use std::cmp::Ordering;
@@ -321,6 +517,28 @@ mod tests {
     use std::hash::{Hash, Hasher};
     use url::Url;
 
+    #[test]
+    fn merge_modes() {
+        let a = Url::parse("http://example.com/a.rss").unwrap();
+        let b = Url::parse("http://example.com/b.rss").unwrap();
+        let c = Url::parse("http://example.com/c.rss").unwrap();
+        let source = vec![a.clone(), b.clone()];
+        let target = vec![b, c.clone()];
+
+        assert_eq!(
+            (vec![a.clone()], vec![c.clone()]),
+            MergeMode::Replace.changes(&source, &target)
+        );
+        assert_eq!(
+            (vec![a], Vec::new()),
+            MergeMode::Union.changes(&source, &target)
+        );
+        assert_eq!(
+            (Vec::new(), vec![c]),
+            MergeMode::Intersection.changes(&source, &target)
+        );
+    }
+
     #[test]
     fn equal_subscription_means_equal_hash() {
         let subscription1 = Podcast {
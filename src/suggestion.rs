@@ -3,6 +3,7 @@
 use crate::client::AuthenticatedClient;
 use crate::client::DeviceClient;
 use crate::error::Error;
+use crate::profile::Endpoint;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::fmt;
@@ -47,7 +48,7 @@ pub trait RetrieveSuggestedPodcasts {
     /// # let username = std::env::var("GPODDER_NET_USERNAME").unwrap();
     /// # let password = std::env::var("GPODDER_NET_PASSWORD").unwrap();
     /// #
-    /// let client = AuthenticatedClient::new(&username, &password);
+    /// let client = AuthenticatedClient::new(&username, &password)?;
     ///
     /// let max_results = 3;
     /// let suggestions = client.retrieve_suggested_podcasts(max_results)?;
@@ -64,17 +65,19 @@ pub trait RetrieveSuggestedPodcasts {
 }
 
 impl RetrieveSuggestedPodcasts for AuthenticatedClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(max_results = max_results)))]
     fn retrieve_suggested_podcasts(&self, max_results: u8) -> Result<Vec<Suggestion>, Error> {
         Ok(self
-            .get(&format!(
-                "https://gpodder.net/suggestions/{}.json",
-                max_results
-            ))?
+            .get(
+                Endpoint::Suggestions,
+                &format!("{}/suggestions/{}.json", self.server(), max_results),
+            )?
             .json()?)
     }
 }
 
 impl RetrieveSuggestedPodcasts for DeviceClient {
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(max_results = max_results)))]
     fn retrieve_suggested_podcasts(&self, max_results: u8) -> Result<Vec<Suggestion>, Error> {
         self.as_ref().retrieve_suggested_podcasts(max_results)
     }
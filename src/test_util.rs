@@ -0,0 +1,67 @@
+//! Fixtures shared by the unit tests
+
+use crate::backup::DeviceBackup;
+use crate::cassette::{Interaction, RecordedRequest, RecordedResponse};
+use crate::device::{Device, DeviceType};
+use crate::id::DeviceId;
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::{BTreeMap, HashMap};
+use url::Url;
+
+pub(crate) fn url(url: &str) -> Url {
+    Url::parse(url).unwrap()
+}
+
+/// Interaction answering the request with status 200 and the given body
+pub(crate) fn interaction(
+    method: &str,
+    url: &str,
+    request_body: Option<&str>,
+    response_body: &str,
+) -> Interaction {
+    Interaction {
+        request: RecordedRequest {
+            method: method.to_owned(),
+            url: url.to_owned(),
+            headers: BTreeMap::new(),
+            body: request_body.map(str::to_owned),
+        },
+        response: RecordedResponse {
+            status: 200,
+            headers: BTreeMap::new(),
+            body: response_body.to_owned(),
+        },
+    }
+}
+
+/// Successful login at the given URL, which starts the session `session_id`
+pub(crate) fn login(url: &str, session_id: &str) -> Interaction {
+    let mut login = interaction("POST", url, None, "");
+    login.response.headers.insert(
+        String::from("set-cookie"),
+        format!("sessionid={}; Path=/", session_id),
+    );
+    login
+}
+
+/// Mobile device without settings
+pub(crate) fn device_backup(id: &str, caption: &str, subscriptions: Vec<Url>) -> DeviceBackup {
+    DeviceBackup {
+        device: Device {
+            id: DeviceId::new(id).unwrap(),
+            caption: caption.to_owned(),
+            device_type: DeviceType::Mobile,
+            subscriptions: subscriptions.len() as u16,
+        },
+        subscriptions,
+        settings: HashMap::new(),
+    }
+}
+
+/// Creation time of backups and snapshots
+pub(crate) fn created() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2020, 6, 1)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+}
@@ -0,0 +1,662 @@
+//! In-memory fake of a gpodder.net account for unit tests
+//!
+//! [FakeAccount] implements the API traits of the [subscription](crate::subscription), [episode](crate::episode), [device](crate::device), [settings](crate::settings), [favorite](crate::favorite), [directory](crate::directory) and [suggestion](crate::suggestion) modules against in-memory state, so that code bounded by these traits can be tested without network access. Like gpodder.net, it assigns increasing timestamps and sanitizes uploaded URLs, reporting the rewrites as `update_urls`. Errors can be injected per [Endpoint].
+//!
+//! Requires the `testing` feature.
+
+use crate::device::{
+    Device, DeviceType, DeviceUpdates, GetDeviceUpdates, ListDevices, UpdateDeviceData,
+};
+use crate::directory::{
+    Episode, PodcastSearch, PodcastToplist, RetrieveEpisodeData, RetrievePodcastData,
+    RetrievePodcastsForTag, RetrieveTopTags, Tag,
+};
+use crate::episode::{
+    EpisodeAction, EpisodeActionQuery, GetEpisodeActions, GetEpisodeActionsResponse,
+    UploadEpisodeActions, UploadEpisodeActionsResponse,
+};
+use crate::error::Error;
+use crate::favorite::GetFavoriteEpisodes;
+use crate::normalize::sanitize_urls;
+use crate::profile::Endpoint;
+use crate::server::{device_updates, MemoryStorage, Storage};
+use crate::settings::{
+    GetAccountSettings, GetDeviceSettings, GetEpisodeSettings, GetPodcastSettings,
+    SaveAccountSettings, SaveDeviceSettings, SaveEpisodeSettings, SavePodcastSettings,
+    SettingsTarget,
+};
+use crate::subscription::{
+    subscription_changes, GetAllSubscriptions, GetSubscriptionChangesResponse, MergeMode,
+    MergeSubscriptions, MergeSubscriptionsReport, Podcast, SubscriptionChanges,
+    SubscriptionsOfDevice, UploadSubscriptionChangesResponse,
+};
+use crate::suggestion::{RetrieveSuggestedPodcasts, Suggestion};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// username of the fake account
+const USERNAME: &str = "fake";
+
+/// Fake of a [DeviceClient](crate::client::DeviceClient), see [module documentation](self)
+///
+/// Clones and fakes created with [device](FakeAccount::device) share the account.
+///
+/// # Examples
+///
+/// ```
+/// use mygpoclient::error::Error;
+/// use mygpoclient::profile::Endpoint;
+/// use mygpoclient::subscription::SubscriptionChanges;
+/// use mygpoclient::testing::FakeAccount;
+/// use url::Url;
+///
+/// fn subscribe<C: SubscriptionChanges>(client: &C, feed: &str) -> Result<u64, Error> {
+///     let url = Url::parse(feed).unwrap();
+///     Ok(client.upload_subscription_changes(&[url], &[])?.timestamp)
+/// }
+///
+/// let account = FakeAccount::new("phone");
+/// let timestamp = subscribe(&account, "http://example.com/feed.rss")?;
+/// assert_eq!(1, account.get_subscription_changes(timestamp - 1)?.add.len());
+///
+/// account.fail_next(Endpoint::SubscriptionChanges, Error::UnknownDevice(String::from("phone")));
+/// assert!(subscribe(&account, "http://example.com/feed.rss").is_err());
+/// #
+/// # Ok::<(), mygpoclient::error::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct FakeAccount {
+    device_id: String,
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    storage: MemoryStorage,
+    tags: Mutex<BTreeMap<Url, Vec<String>>>,
+    errors: Mutex<HashMap<Endpoint, VecDeque<Error>>>,
+}
+
+impl FakeAccount {
+    /// Create empty account with a fake for the given device
+    pub fn new(device_id: &str) -> FakeAccount {
+        let shared = Shared::default();
+        shared.storage.add_user(USERNAME, "");
+        FakeAccount {
+            device_id: device_id.to_owned(),
+            shared: Arc::new(shared),
+        }
+    }
+
+    /// Fake for another device of the same account
+    pub fn device(&self, device_id: &str) -> FakeAccount {
+        FakeAccount {
+            device_id: device_id.to_owned(),
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Add podcast with tags to the directory
+    pub fn add_podcast(&self, podcast: Podcast, tags: &[&str]) {
+        self.shared.tags.lock().unwrap().insert(
+            podcast.url.clone(),
+            tags.iter().map(|&tag| tag.to_owned()).collect(),
+        );
+        self.shared.storage.add_podcast(podcast);
+    }
+
+    /// Add episode to the directory
+    pub fn add_episode(&self, episode: Episode) {
+        self.shared.storage.add_episode(episode);
+    }
+
+    /// Let the next request to the given endpoint fail with `error`.
+    ///
+    /// Errors for the same endpoint are returned in the order they were injected.
+    pub fn fail_next(&self, endpoint: Endpoint, error: Error) {
+        self.shared
+            .errors
+            .lock()
+            .unwrap()
+            .entry(endpoint)
+            .or_default()
+            .push_back(error);
+    }
+
+    /// Fail with an injected error or access the storage
+    fn storage(&self, endpoint: Endpoint) -> Result<&MemoryStorage, Error> {
+        let mut errors = self.shared.errors.lock().unwrap();
+        match errors.get_mut(&endpoint).and_then(VecDeque::pop_front) {
+            Some(error) => Err(error),
+            None => Ok(&self.shared.storage),
+        }
+    }
+
+    /// Subscriptions of all devices
+    fn all_subscriptions(&self, storage: &MemoryStorage) -> Result<Vec<Url>, Error> {
+        let mut urls: Vec<Url> = Vec::new();
+        for device in storage.devices(USERNAME)? {
+            for url in storage.subscriptions(USERNAME, &device.id)? {
+                if !urls.contains(&url) {
+                    urls.push(url);
+                }
+            }
+        }
+        Ok(urls)
+    }
+
+    /// Directory podcasts with the most subscribers first
+    fn popular_podcasts(&self, storage: &MemoryStorage, scale_logo: Option<u16>) -> Vec<Podcast> {
+        let mut podcasts = storage.podcasts();
+        podcasts.sort_by_key(|podcast| Reverse(podcast.subscribers));
+        if scale_logo.is_some() {
+            for podcast in &mut podcasts {
+                podcast.scaled_logo_url = podcast.logo_url.clone();
+            }
+        }
+        podcasts
+    }
+}
+
+impl GetAllSubscriptions for FakeAccount {
+    fn get_all_subscriptions(&self) -> Result<Vec<Podcast>, Error> {
+        let storage = self.storage(Endpoint::Subscriptions)?;
+        self.all_subscriptions(storage)?
+            .iter()
+            .map(|url| storage.podcast(url))
+            .collect()
+    }
+}
+
+impl SubscriptionsOfDevice for FakeAccount {
+    fn get_subscriptions_of_device(&self) -> Result<Vec<Url>, Error> {
+        self.storage(Endpoint::Subscriptions)?
+            .subscriptions(USERNAME, &self.device_id)
+    }
+
+    fn upload_subscriptions_of_device(&self, subscriptions: &[Url]) -> Result<(), Error> {
+        let storage = self.storage(Endpoint::Subscriptions)?;
+        let wanted = sanitize_urls(subscriptions, &mut Vec::new());
+        let current = storage.subscriptions(USERNAME, &self.device_id)?;
+        let changes = subscription_changes(&self.device_id, &current, &wanted);
+        storage.apply_subscription_changes(
+            USERNAME,
+            &self.device_id,
+            &changes.add,
+            &changes.remove,
+        )?;
+        Ok(())
+    }
+}
+
+impl SubscriptionChanges for FakeAccount {
+    fn upload_subscription_changes(
+        &self,
+        add: &[Url],
+        remove: &[Url],
+    ) -> Result<UploadSubscriptionChangesResponse, Error> {
+        let storage = self.storage(Endpoint::SubscriptionChanges)?;
+        if add.iter().any(|url| remove.contains(url)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the same feed is added and removed",
+            )
+            .into());
+        }
+        let mut update_urls = Vec::new();
+        let add = sanitize_urls(add, &mut update_urls);
+        let remove = sanitize_urls(remove, &mut update_urls);
+        let timestamp =
+            storage.apply_subscription_changes(USERNAME, &self.device_id, &add, &remove)?;
+        Ok(UploadSubscriptionChangesResponse {
+            timestamp,
+            update_urls,
+        })
+    }
+
+    fn get_subscription_changes(
+        &self,
+        timestamp: u64,
+    ) -> Result<GetSubscriptionChangesResponse, Error> {
+        self.storage(Endpoint::SubscriptionChanges)?
+            .subscription_changes(USERNAME, &self.device_id, timestamp)
+    }
+}
+
+impl MergeSubscriptions for FakeAccount {
+    fn merge_subscriptions(
+        &self,
+        source_device_id: &str,
+        target_device_id: &str,
+        mode: MergeMode,
+    ) -> Result<MergeSubscriptionsReport, Error> {
+        let storage = self.storage(Endpoint::Subscriptions)?;
+        if !storage
+            .devices(USERNAME)?
+            .iter()
+            .any(|device| device.id == source_device_id)
+        {
+            return Err(Error::UnknownDevice(source_device_id.to_owned()));
+        }
+
+        let source = storage.subscriptions(USERNAME, source_device_id)?;
+        let target = storage.subscriptions(USERNAME, target_device_id)?;
+        let (added, removed) = mode.changes(&source, &target);
+        let response = if added.is_empty() && removed.is_empty() {
+            None
+        } else {
+            Some(
+                self.device(target_device_id)
+                    .upload_subscription_changes(&added, &removed)?,
+            )
+        };
+
+        Ok(MergeSubscriptionsReport {
+            added,
+            removed,
+            response,
+        })
+    }
+}
+
+impl UploadEpisodeActions for FakeAccount {
+    fn upload_episode_actions(
+        &self,
+        actions: &[EpisodeAction],
+    ) -> Result<UploadEpisodeActionsResponse, Error> {
+        let storage = self.storage(Endpoint::EpisodeActions)?;
+        let mut update_urls = Vec::new();
+        let actions: Vec<EpisodeAction> = actions
+            .iter()
+            .filter_map(|action| {
+                let mut action = action.clone();
+                action.podcast = sanitize_urls(&[action.podcast], &mut update_urls).pop()?;
+                action.episode = sanitize_urls(&[action.episode], &mut update_urls).pop()?;
+                Some(action)
+            })
+            .collect();
+        let timestamp = storage.add_episode_actions(USERNAME, &actions)?;
+        Ok(UploadEpisodeActionsResponse {
+            timestamp,
+            update_urls,
+        })
+    }
+}
+
+impl GetEpisodeActions for FakeAccount {
+    fn get_episode_actions(
+        &self,
+        query: &EpisodeActionQuery,
+    ) -> Result<GetEpisodeActionsResponse, Error> {
+        self.storage(Endpoint::EpisodeActions)?
+            .episode_actions(USERNAME, query)
+    }
+}
+
+impl UpdateDeviceData for FakeAccount {
+    fn update_device_data<T: Into<Option<String>>, U: Into<Option<DeviceType>>>(
+        &self,
+        caption: T,
+        device_type: U,
+    ) -> Result<(), Error> {
+        self.storage(Endpoint::Devices)?.update_device(
+            USERNAME,
+            &self.device_id,
+            caption.into(),
+            device_type.into(),
+        )
+    }
+}
+
+impl ListDevices for FakeAccount {
+    fn list_devices(&self) -> Result<Vec<Device>, Error> {
+        self.storage(Endpoint::Devices)?.devices(USERNAME)
+    }
+}
+
+impl GetDeviceUpdates for FakeAccount {
+    fn get_device_updates(
+        &self,
+        since: u64,
+        include_actions: bool,
+    ) -> Result<DeviceUpdates, Error> {
+        device_updates(
+            self.storage(Endpoint::DeviceUpdates)?,
+            USERNAME,
+            &self.device_id,
+            since,
+            include_actions,
+        )
+    }
+}
+
+impl SaveAccountSettings for FakeAccount {
+    fn save_account_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error> {
+        self.storage(Endpoint::Settings)?.save_settings(
+            USERNAME,
+            &SettingsTarget::Account,
+            set,
+            remove,
+        )
+    }
+}
+
+impl SaveDeviceSettings for FakeAccount {
+    fn save_device_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+    ) -> Result<HashMap<String, String>, Error> {
+        self.storage(Endpoint::Settings)?.save_settings(
+            USERNAME,
+            &SettingsTarget::Device(self.device_id.clone()),
+            set,
+            remove,
+        )
+    }
+}
+
+impl SavePodcastSettings for FakeAccount {
+    fn save_podcast_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+        podcast: Url,
+    ) -> Result<HashMap<String, String>, Error> {
+        self.storage(Endpoint::Settings)?.save_settings(
+            USERNAME,
+            &SettingsTarget::Podcast(podcast),
+            set,
+            remove,
+        )
+    }
+}
+
+impl SaveEpisodeSettings for FakeAccount {
+    fn save_episode_settings(
+        &self,
+        set: HashMap<String, String>,
+        remove: Vec<String>,
+        podcast: Url,
+        episode: Url,
+    ) -> Result<HashMap<String, String>, Error> {
+        self.storage(Endpoint::Settings)?.save_settings(
+            USERNAME,
+            &SettingsTarget::Episode { podcast, episode },
+            set,
+            remove,
+        )
+    }
+}
+
+impl GetAccountSettings for FakeAccount {
+    fn get_account_settings(&self) -> Result<HashMap<String, String>, Error> {
+        self.storage(Endpoint::Settings)?
+            .settings(USERNAME, &SettingsTarget::Account)
+    }
+}
+
+impl GetDeviceSettings for FakeAccount {
+    fn get_device_settings(&self) -> Result<HashMap<String, String>, Error> {
+        self.storage(Endpoint::Settings)?
+            .settings(USERNAME, &SettingsTarget::Device(self.device_id.clone()))
+    }
+}
+
+impl GetPodcastSettings for FakeAccount {
+    fn get_podcast_settings(&self, podcast: Url) -> Result<HashMap<String, String>, Error> {
+        self.storage(Endpoint::Settings)?
+            .settings(USERNAME, &SettingsTarget::Podcast(podcast))
+    }
+}
+
+impl GetEpisodeSettings for FakeAccount {
+    fn get_episode_settings(
+        &self,
+        podcast: Url,
+        episode: Url,
+    ) -> Result<HashMap<String, String>, Error> {
+        self.storage(Endpoint::Settings)?
+            .settings(USERNAME, &SettingsTarget::Episode { podcast, episode })
+    }
+}
+
+impl GetFavoriteEpisodes for FakeAccount {
+    fn get_favorite_episodes(&self) -> Result<Vec<Episode>, Error> {
+        self.storage(Endpoint::Favorites)?.favorites(USERNAME)
+    }
+}
+
+impl RetrieveTopTags for FakeAccount {
+    fn retrieve_top_tags(&self, count: u8) -> Result<Vec<Tag>, Error> {
+        self.storage(Endpoint::Directory)?;
+        let mut usage: BTreeMap<String, u16> = BTreeMap::new();
+        for tag in self.shared.tags.lock().unwrap().values().flatten() {
+            *usage.entry(tag.clone()).or_default() += 1;
+        }
+        let mut tags: Vec<Tag> = usage
+            .into_iter()
+            .map(|(tag, usage)| Tag {
+                title: tag.clone(),
+                tag,
+                usage,
+            })
+            .collect();
+        tags.sort_by_key(|tag| Reverse(tag.usage));
+        tags.truncate(count.into());
+        Ok(tags)
+    }
+}
+
+impl RetrievePodcastsForTag for FakeAccount {
+    fn retrieve_podcasts_for_tag(&self, tag: &str, count: u8) -> Result<Vec<Podcast>, Error> {
+        let storage = self.storage(Endpoint::Directory)?;
+        let tags = self.shared.tags.lock().unwrap();
+        let mut podcasts: Vec<Podcast> = self
+            .popular_podcasts(storage, None)
+            .into_iter()
+            .filter(|podcast| {
+                tags.get(&podcast.url)
+                    .is_some_and(|tags| tags.iter().any(|podcast_tag| podcast_tag == tag))
+            })
+            .collect();
+        podcasts.truncate(count.into());
+        Ok(podcasts)
+    }
+}
+
+impl RetrievePodcastData for FakeAccount {
+    fn retrieve_podcast_data(&self, url: Url) -> Result<Podcast, Error> {
+        self.storage(Endpoint::Directory)?.podcast(&url)
+    }
+}
+
+impl RetrieveEpisodeData for FakeAccount {
+    fn retrieve_episode_data(&self, podcast: Url, url: Url) -> Result<Episode, Error> {
+        self.storage(Endpoint::Directory)?.episode(&podcast, &url)
+    }
+}
+
+impl PodcastToplist for FakeAccount {
+    fn podcast_toplist(&self, number: u8, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error> {
+        let storage = self.storage(Endpoint::Directory)?;
+        let mut podcasts = self.popular_podcasts(storage, scale_logo);
+        podcasts.truncate(number.into());
+        Ok(podcasts)
+    }
+}
+
+impl PodcastSearch for FakeAccount {
+    fn podcast_search(&self, q: &str, scale_logo: Option<u16>) -> Result<Vec<Podcast>, Error> {
+        let storage = self.storage(Endpoint::Directory)?;
+        let q = q.to_lowercase();
+        Ok(self
+            .popular_podcasts(storage, scale_logo)
+            .into_iter()
+            .filter(|podcast| {
+                podcast.title.to_lowercase().contains(&q)
+                    || podcast.description.to_lowercase().contains(&q)
+                    || podcast.url.as_str().to_lowercase().contains(&q)
+            })
+            .collect())
+    }
+}
+
+impl RetrieveSuggestedPodcasts for FakeAccount {
+    /// Suggests the directory podcasts with the most subscribers, which aren't subscribed on any device
+    fn retrieve_suggested_podcasts(&self, max_results: u8) -> Result<Vec<Suggestion>, Error> {
+        let storage = self.storage(Endpoint::Suggestions)?;
+        let subscriptions = self.all_subscriptions(storage)?;
+        Ok(self
+            .popular_podcasts(storage, None)
+            .into_iter()
+            .filter(|podcast| !subscriptions.contains(&podcast.url))
+            .take(max_results.into())
+            .map(|podcast| Suggestion {
+                website: podcast
+                    .website
+                    .clone()
+                    .unwrap_or_else(|| podcast.url.clone()),
+                mygpo_link: podcast.mygpo_link,
+                description: podcast.description,
+                subscribers: podcast.subscribers,
+                title: podcast.title,
+                url: podcast.url,
+                subscribers_last_week: podcast.subscribers_last_week,
+                logo_url: podcast.logo_url,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FakeAccount;
+    use crate::device::{DeviceType, ListDevices, UpdateDeviceData};
+    use crate::directory::{PodcastSearch, RetrievePodcastsForTag, RetrieveTopTags};
+    use crate::error::Error;
+    use crate::profile::Endpoint;
+    use crate::subscription::{
+        GetAllSubscriptions, MergeMode, MergeSubscriptions, Podcast, SubscriptionChanges,
+    };
+    use crate::suggestion::RetrieveSuggestedPodcasts;
+    use crate::test_util::url;
+
+    fn podcast(feed: &str, title: &str, subscribers: u16) -> Podcast {
+        Podcast {
+            url: url(feed),
+            title: title.to_owned(),
+            author: None,
+            description: String::new(),
+            subscribers,
+            subscribers_last_week: subscribers,
+            logo_url: None,
+            scaled_logo_url: None,
+            website: None,
+            mygpo_link: url("http://gpodder.net/podcast/11171"),
+        }
+    }
+
+    #[test]
+    fn subscription_changes() {
+        let phone = FakeAccount::new("phone");
+
+        let first = phone
+            .upload_subscription_changes(
+                &[
+                    url("http://feeds2.feedburner.com/LinuxOutlaws"),
+                    url("http://example.com/1.rss"),
+                ],
+                &[],
+            )
+            .unwrap();
+        assert_eq!(
+            vec![(
+                url("http://feeds2.feedburner.com/LinuxOutlaws"),
+                url("http://feeds.feedburner.com/LinuxOutlaws")
+            )],
+            first.update_urls
+        );
+        let second = phone
+            .upload_subscription_changes(&[], &[url("http://example.com/1.rss")])
+            .unwrap();
+        assert!(second.timestamp > first.timestamp);
+
+        let changes = phone.get_subscription_changes(first.timestamp).unwrap();
+        assert!(changes.add.is_empty());
+        assert_eq!(vec![url("http://example.com/1.rss")], changes.remove);
+        assert!(phone
+            .upload_subscription_changes(
+                &[url("http://example.com/2.rss")],
+                &[url("http://example.com/2.rss")]
+            )
+            .is_err());
+
+        let laptop = phone.device("laptop");
+        let report = laptop
+            .merge_subscriptions("phone", "laptop", MergeMode::Union)
+            .unwrap();
+        assert_eq!(
+            vec![url("http://feeds.feedburner.com/LinuxOutlaws")],
+            report.added
+        );
+        assert_eq!(2, laptop.list_devices().unwrap().len());
+        assert_eq!(1, laptop.get_all_subscriptions().unwrap().len());
+    }
+
+    #[test]
+    fn injected_errors() {
+        let account = FakeAccount::new("phone");
+        account.fail_next(
+            Endpoint::Devices,
+            Error::UnknownDevice(String::from("phone")),
+        );
+
+        assert!(matches!(
+            account.update_device_data(String::from("Phone"), DeviceType::Mobile),
+            Err(Error::UnknownDevice(_))
+        ));
+        account
+            .update_device_data(String::from("Phone"), DeviceType::Mobile)
+            .unwrap();
+        assert_eq!("Phone", account.list_devices().unwrap()[0].caption);
+    }
+
+    #[test]
+    fn directory_and_suggestions() {
+        let account = FakeAccount::new("phone");
+        account.add_podcast(
+            podcast("http://example.com/linux.rss", "Linux Outlaws", 100),
+            &["linux", "technology"],
+        );
+        account.add_podcast(
+            podcast("http://example.com/news.rss", "Daily News", 200),
+            &["news"],
+        );
+        account.add_podcast(
+            podcast("http://example.com/tech.rss", "Tech Talk", 50),
+            &["technology"],
+        );
+        account
+            .upload_subscription_changes(&[url("http://example.com/news.rss")], &[])
+            .unwrap();
+
+        let tags = account.retrieve_top_tags(1).unwrap();
+        assert_eq!("technology", tags[0].tag);
+        assert_eq!(2, tags[0].usage);
+        let technology = account.retrieve_podcasts_for_tag("technology", 10).unwrap();
+        assert_eq!("Linux Outlaws", technology[0].title);
+        assert_eq!(1, account.podcast_search("linux", None).unwrap().len());
+
+        let suggestions = account.retrieve_suggested_podcasts(10).unwrap();
+        assert_eq!(2, suggestions.len());
+        assert_eq!("Linux Outlaws", suggestions[0].title);
+    }
+}
@@ -0,0 +1,170 @@
+//! Spans of HTTP requests, requires the `tracing` feature
+//!
+//! Only the method, the path of the URL, the status and sizes are recorded. Headers, query parameters, bodies and the user info of the URL are left out, so credentials never end up in traces.
+
+use crate::error::Error;
+use crate::profile::Endpoint;
+use reqwest::blocking::{Request, Response};
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::span::EnteredSpan;
+
+/// Entered span of a request, which is closed by [finish](RequestSpan::finish)
+pub(crate) struct RequestSpan {
+    span: EnteredSpan,
+    start: Instant,
+}
+
+impl RequestSpan {
+    /// Enter span for a request to the given endpoint
+    pub(crate) fn enter(endpoint: Option<Endpoint>, request: &Request) -> RequestSpan {
+        let request_bytes = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map_or(0, <[u8]>::len);
+        let span = tracing::debug_span!(
+            "http_request",
+            endpoint = endpoint.map(display),
+            method = %request.method(),
+            path = request.url().path(),
+            request_bytes,
+            status = Empty,
+            response_bytes = Empty,
+            latency_ms = Empty,
+            failed = Empty,
+        );
+        RequestSpan {
+            span: span.entered(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Record outcome and latency of the request
+    pub(crate) fn finish(self, result: &Result<Response, Error>) {
+        self.span
+            .record("latency_ms", self.start.elapsed().as_millis() as u64);
+        match result {
+            Ok(response) => {
+                self.span.record("status", response.status().as_u16());
+                if let Some(length) = response.content_length() {
+                    self.span.record("response_bytes", length);
+                }
+            }
+            Err(_) => {
+                self.span.record("failed", true);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cassette::Cassette;
+    use crate::client::DeviceClient;
+    use crate::subscription::SubscriptionChanges;
+    use crate::test_util::{interaction, url};
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Subscriber which collects span names and field values
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl Recorder {
+        fn push(&self, name: &str, value: String) {
+            self.0.lock().unwrap().push((name.to_owned(), value));
+        }
+
+        fn value(&self, name: &str) -> Option<String> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
+    impl Visit for Recorder {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.push(field.name(), value.to_owned());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.push(field.name(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            self.push("span", span.metadata().name().to_owned());
+            span.record(&mut self.clone());
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, values: &Record<'_>) {
+            values.record(&mut self.clone());
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut self.clone());
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn spans_of_api_calls() {
+        let cassette = Cassette::from_interactions(vec![interaction(
+            "POST",
+            "https://gpodder.example.com/api/2/subscriptions/alice/phone.json",
+            Some(r#"{"add": ["http://example.com/feed.rss"], "remove": []}"#),
+            r#"{"timestamp": 12347, "update_urls": []}"#,
+        )]);
+        let client =
+            DeviceClient::with_server("https://gpodder.example.com", "alice", "hunter2", "phone")
+                .unwrap()
+                .with_cassette(cassette);
+        let recorder = Recorder::default();
+
+        tracing::subscriber::with_default(recorder.clone(), || {
+            client
+                .upload_subscription_changes(&[url("http://example.com/feed.rss")], &[])
+                .unwrap();
+        });
+
+        assert_eq!(
+            Some(String::from("upload_subscription_changes")),
+            recorder.value("span")
+        );
+        assert_eq!(Some(String::from("1")), recorder.value("add"));
+        assert_eq!(
+            Some(String::from("subscription changes")),
+            recorder.value("endpoint")
+        );
+        assert_eq!(Some(String::from("POST")), recorder.value("method"));
+        assert_eq!(
+            Some(String::from("/api/2/subscriptions/alice/phone.json")),
+            recorder.value("path")
+        );
+        assert_eq!(Some(String::from("200")), recorder.value("status"));
+        assert!(recorder.value("latency_ms").is_some());
+        assert!(!recorder
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(_, value)| value.contains("hunter2")));
+    }
+}
@@ -3,6 +3,7 @@ extern crate mygpoclient;
 use std::env;
 use url::Url;
 
+use mygpoclient::cassette::Cassette;
 use mygpoclient::client::DeviceClient;
 use mygpoclient::directory::PodcastSearch;
 use mygpoclient::directory::PodcastToplist;
@@ -14,7 +15,7 @@ use mygpoclient::error::Error;
 
 #[test]
 fn test_retrieve_top_tags_device_client() -> Result<(), Error> {
-    let client = get_device_client();
+    let client = get_device_client("retrieve_top_tags");
     let max_results = 5;
     let tags = client.retrieve_top_tags(max_results)?;
     assert_eq!(max_results as usize, tags.len());
@@ -24,7 +25,7 @@ fn test_retrieve_top_tags_device_client() -> Result<(), Error> {
 
 #[test]
 fn test_retrieve_podcasts_for_tag_device_client() -> Result<(), Error> {
-    let client = get_device_client();
+    let client = get_device_client("retrieve_podcasts_for_tag");
     let max_results = 5;
     let podcasts = client.retrieve_podcasts_for_tag("new", max_results)?;
     assert!(podcasts.len() <= max_results as usize);
@@ -34,7 +35,7 @@ fn test_retrieve_podcasts_for_tag_device_client() -> Result<(), Error> {
 
 #[test]
 fn test_retrieve_podcast_data_device_client() -> Result<(), Error> {
-    let client = get_device_client();
+    let client = get_device_client("retrieve_podcast_data");
     let url = Url::parse("http://feeds.feedburner.com/coverville").unwrap();
     client.retrieve_podcast_data(url)?;
 
@@ -43,7 +44,7 @@ fn test_retrieve_podcast_data_device_client() -> Result<(), Error> {
 
 #[test]
 fn test_retrieve_episode_data_device_client() -> Result<(), Error> {
-    let client = get_device_client();
+    let client = get_device_client("retrieve_episode_data");
     let url = Url::parse(
         "https://www.podtrac.com/pts/redirect.mp3/audio.wnyc.org/otm/otm011520_podextra.mp3",
     )
@@ -56,7 +57,7 @@ fn test_retrieve_episode_data_device_client() -> Result<(), Error> {
 
 #[test]
 fn test_podcast_toplist_device_client() -> Result<(), Error> {
-    let client = get_device_client();
+    let client = get_device_client("podcast_toplist");
     let max_results = 5;
     let podcasts = client.podcast_toplist(max_results, Some(256))?;
     assert!(podcasts.len() <= max_results as usize);
@@ -66,17 +67,31 @@ fn test_podcast_toplist_device_client() -> Result<(), Error> {
 
 #[test]
 fn test_podcast_search_device_client() -> Result<(), Error> {
-    let client = get_device_client();
+    let client = get_device_client("podcast_search");
     let podcasts = client.podcast_search("raumzeit", Some(256))?;
-    assert!(podcasts.len() > 0);
+    assert!(!podcasts.is_empty());
 
     Ok(())
 }
 
-fn get_device_client() -> DeviceClient {
-    let username = env::var("GPODDER_NET_USERNAME").unwrap();
-    let password = env::var("GPODDER_NET_PASSWORD").unwrap();
-    let deviceid = env::var("GPODDER_NET_DEVICEID").unwrap();
-
-    DeviceClient::new(&username, &password, &deviceid)
+/// Replay the cassette `tests/cassettes/directory/<name>.json`, or record it with the credentials of `set-credentials.sh` if `GPODDER_NET_RECORD` is set
+fn get_device_client(cassette: &str) -> DeviceClient {
+    let path = format!(
+        "{}/tests/cassettes/directory/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        cassette
+    );
+    if env::var_os("GPODDER_NET_RECORD").is_some() {
+        let username = env::var("GPODDER_NET_USERNAME").unwrap();
+        let password = env::var("GPODDER_NET_PASSWORD").unwrap();
+        let deviceid = env::var("GPODDER_NET_DEVICEID").unwrap();
+
+        DeviceClient::new(&username, &password, &deviceid)
+            .unwrap()
+            .with_cassette(Cassette::record(path))
+    } else {
+        DeviceClient::new("alice", "secret", "phone")
+            .unwrap()
+            .with_cassette(Cassette::replay(path).unwrap())
+    }
 }
@@ -0,0 +1,24 @@
+extern crate mygpoclient;
+
+use std::env;
+
+use mygpoclient::client::DeviceClient;
+use mygpoclient::episode::{EpisodeActionQuery, GetEpisodeActions};
+use mygpoclient::error::Error;
+
+#[test]
+fn test_get_episode_actions_device_client() -> Result<(), Error> {
+    let username = env::var("GPODDER_NET_USERNAME").unwrap();
+    let password = env::var("GPODDER_NET_PASSWORD").unwrap();
+    let deviceid = env::var("GPODDER_NET_DEVICEID").unwrap();
+
+    let client = DeviceClient::new(&username, &password, &deviceid)?;
+    let response = client.get_episode_actions(&EpisodeActionQuery::new().aggregated(true))?;
+
+    assert!(response
+        .actions
+        .iter()
+        .all(|action| action.device.as_deref() == Some(deviceid.as_str())));
+
+    Ok(())
+}
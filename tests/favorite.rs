@@ -12,7 +12,7 @@ fn test_get_favorite_episodes_device_client() -> Result<(), Error> {
     let password = env::var("GPODDER_NET_PASSWORD").unwrap();
     let deviceid = env::var("GPODDER_NET_DEVICEID").unwrap();
 
-    let client = DeviceClient::new(&username, &password, &deviceid);
+    let client = DeviceClient::new(&username, &password, &deviceid)?;
     client.get_favorite_episodes()?;
 
     Ok(())
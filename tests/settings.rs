@@ -25,7 +25,7 @@ fn test_save_account_settings_device_client() -> Result<(), Error> {
     assert!(set
         .iter()
         .all(|(key, value)| settings.get_key_value(key).unwrap() == (key, value)));
-    assert!(remove.iter().all(|key| settings.get(key).is_none()));
+    assert!(remove.iter().all(|key| !settings.contains_key(key)));
     Ok(())
 }
 
@@ -45,7 +45,7 @@ fn test_save_podcast_settings_device_client() -> Result<(), Error> {
     assert!(set
         .iter()
         .all(|(key, value)| settings.get_key_value(key).unwrap() == (key, value)));
-    assert!(remove.iter().all(|key| settings.get(key).is_none()));
+    assert!(remove.iter().all(|key| !settings.contains_key(key)));
     Ok(())
 }
 
@@ -66,7 +66,7 @@ fn test_save_episode_settings_device_client() -> Result<(), Error> {
     assert!(set
         .iter()
         .all(|(key, value)| settings.get_key_value(key).unwrap() == (key, value)));
-    assert!(remove.iter().all(|key| settings.get(key).is_none()));
+    assert!(remove.iter().all(|key| !settings.contains_key(key)));
     Ok(())
 }
 
@@ -99,5 +99,5 @@ fn get_device_client() -> DeviceClient {
     let password = env::var("GPODDER_NET_PASSWORD").unwrap();
     let deviceid = env::var("GPODDER_NET_DEVICEID").unwrap();
 
-    DeviceClient::new(&username, &password, &deviceid)
+    DeviceClient::new(&username, &password, &deviceid).unwrap()
 }
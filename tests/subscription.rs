@@ -9,7 +9,7 @@ use mygpoclient::client::DeviceClient;
 use mygpoclient::error::Error;
 use mygpoclient::subscription::{GetAllSubscriptions, SubscriptionChanges, SubscriptionsOfDevice};
 
-const DUMMY_PODCAST_URL: &'static str = "http://ubuntupodcast.org/feed/";
+const DUMMY_PODCAST_URL: &str = "http://ubuntupodcast.org/feed/";
 
 #[test]
 fn test_subscription() -> Result<(), Error> {
@@ -17,7 +17,7 @@ fn test_subscription() -> Result<(), Error> {
     let password = env::var("GPODDER_NET_PASSWORD").unwrap();
     let deviceid = env::var("GPODDER_NET_DEVICEID").unwrap();
 
-    let client = DeviceClient::new(&username, &password, &deviceid);
+    let client = DeviceClient::new(&username, &password, &deviceid)?;
 
     let subscriptions = client.get_subscriptions_of_device()?;
 
@@ -76,7 +76,7 @@ fn test_subscription_changes() -> Result<(), Error> {
     let password = env::var("GPODDER_NET_PASSWORD").unwrap();
     let deviceid = env::var("GPODDER_NET_DEVICEID").unwrap();
 
-    let client = DeviceClient::new(&username, &password, &deviceid);
+    let client = DeviceClient::new(&username, &password, &deviceid)?;
 
     let subscriptions = client.get_subscriptions_of_device()?;
 
@@ -13,7 +13,7 @@ fn test_retrieve_suggested_podcasts_device_client() -> Result<(), Error> {
     let deviceid = env::var("GPODDER_NET_DEVICEID").unwrap();
 
     let max_results = 3;
-    let client = DeviceClient::new(&username, &password, &deviceid);
+    let client = DeviceClient::new(&username, &password, &deviceid)?;
     let suggestions = client.retrieve_suggested_podcasts(max_results)?;
     assert!(suggestions.len() <= max_results as usize);
 